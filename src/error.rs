@@ -0,0 +1,76 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::keyboard_mapping::LayoutId;
+
+/// Error returned by the platform-facing functions of this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The operation has no implementation for the current OS.
+    UnsupportedPlatform { operation: &'static str },
+    /// The XKB rules registry could not be read or parsed.
+    RegistryUnreadable { path: PathBuf, reason: String },
+    /// A file could not be read or written.
+    FileAccess { path: PathBuf, reason: String },
+    /// Layout data in some file format could not be parsed.
+    InvalidData {
+        format: &'static str,
+        reason: String,
+    },
+    /// xkbcommon rejected the RMLVO names it was given.
+    KeymapCompile { layouts: String },
+    /// No installed layout has the requested id.
+    LayoutNotFound { id: LayoutId },
+    /// Which layout is active could not be worked out.
+    NoActiveLayout,
+    /// An external program the operation depends on is not installed.
+    ToolMissing { tool: &'static str },
+    /// An external program or system API ran but reported failure.
+    ToolFailed { tool: &'static str, reason: String },
+}
+
+impl LayoutError {
+    /// Classifies a failure to spawn `tool`: a missing executable becomes
+    /// [`LayoutError::ToolMissing`], anything else [`LayoutError::ToolFailed`].
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn from_spawn(tool: &'static str, err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::NotFound {
+            LayoutError::ToolMissing { tool }
+        } else {
+            LayoutError::ToolFailed {
+                tool,
+                reason: err.to_string(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::UnsupportedPlatform { operation } => {
+                write!(f, "{operation} is not implemented for this OS")
+            }
+            LayoutError::RegistryUnreadable { path, reason } => {
+                write!(f, "failed to read {}: {reason}", path.display())
+            }
+            LayoutError::FileAccess { path, reason } => {
+                write!(f, "cannot access {}: {reason}", path.display())
+            }
+            LayoutError::InvalidData { format, reason } => write!(f, "invalid {format}: {reason}"),
+            LayoutError::KeymapCompile { layouts } => {
+                write!(f, "failed to compile keymap for layouts \"{layouts}\"")
+            }
+            LayoutError::LayoutNotFound { id } => write!(f, "no installed layout with id \"{id}\""),
+            LayoutError::NoActiveLayout => f.write_str("cannot tell which layout is active"),
+            LayoutError::ToolMissing { tool } => write!(f, "{tool} is not installed"),
+            LayoutError::ToolFailed { tool, reason } => write!(f, "{tool} failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use super::*;
+
+#[test]
+fn test_from_spawn_not_found() {
+    let err = io::Error::new(io::ErrorKind::NotFound, "no such file");
+    assert_eq!(
+        LayoutError::from_spawn("xclip", err),
+        LayoutError::ToolMissing { tool: "xclip" }
+    );
+}
+
+#[test]
+fn test_from_spawn_other_error() {
+    let err = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+    assert_eq!(
+        LayoutError::from_spawn("xclip", err),
+        LayoutError::ToolFailed {
+            tool: "xclip",
+            reason: "denied".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_display() {
+    let err = LayoutError::RegistryUnreadable {
+        path: PathBuf::from("/usr/share/X11/xkb/rules/evdev.xml"),
+        reason: "not found".to_string(),
+    };
+    assert_eq!(
+        err.to_string(),
+        "failed to read /usr/share/X11/xkb/rules/evdev.xml: not found"
+    );
+    assert_eq!(
+        LayoutError::UnsupportedPlatform {
+            operation: "keyboard_mapping::list_layouts"
+        }
+        .to_string(),
+        "keyboard_mapping::list_layouts is not implemented for this OS"
+    );
+}
+
+#[test]
+fn test_display_file_access() {
+    let err = LayoutError::FileAccess {
+        path: PathBuf::from("layouts.json"),
+        reason: "permission denied".to_string(),
+    };
+    assert_eq!(
+        err.to_string(),
+        "cannot access layouts.json: permission denied"
+    );
+}
+
+#[test]
+fn test_display_no_active_layout() {
+    assert_eq!(
+        LayoutError::NoActiveLayout.to_string(),
+        "cannot tell which layout is active"
+    );
+}
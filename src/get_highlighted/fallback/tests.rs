@@ -1,15 +1,21 @@
 use super::*;
 
 #[test]
-#[should_panic(expected = "get_highlighted: get_highlighted_text is not implemented for this OS")]
-fn test_get_highlighted_text_panics() {
-    get_highlighted_text();
+fn test_get_highlighted_text_unsupported() {
+    assert_eq!(
+        get_highlighted_text(),
+        Err(LayoutError::UnsupportedPlatform {
+            operation: "get_highlighted::get_highlighted_text"
+        })
+    );
 }
 
 #[test]
-#[should_panic(
-    expected = "get_highlighted: replace_highlighted_text is not implemented for this OS"
-)]
-fn test_replace_highlighted_text_panics() {
-    replace_highlighted_text("test");
+fn test_replace_highlighted_text_unsupported() {
+    assert_eq!(
+        replace_highlighted_text("test"),
+        Err(LayoutError::UnsupportedPlatform {
+            operation: "get_highlighted::replace_highlighted_text"
+        })
+    );
 }
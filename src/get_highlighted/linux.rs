@@ -1,76 +1,56 @@
 #![cfg(target_os = "linux")]
 
-use std::process::Command;
+use std::process::{Command, Output};
 
-pub fn get_highlighted_text() -> Option<String> {
+use crate::error::LayoutError;
+
+/// Clipboard readers tried in order, first non-empty output wins.
+const SELECTION_READERS: &[(&str, &[&str])] = &[
+    ("wl-paste", &["-p"]),
+    ("wl-paste", &[]),
+    ("xclip", &["-o", "-selection", "primary"]),
+    ("xclip", &["-o"]),
+    ("xsel", &["-o"]),
+    ("xsel", &["-o", "-b"]),
+];
+
+pub fn get_highlighted_text() -> Result<Option<String>, LayoutError> {
+    let mut any_tool_ran = false;
     if is_wsl()
         && let Ok(out) = Command::new("powershell.exe")
             .args(["-NoProfile", "-Command", "Get-Clipboard"])
             .output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s.replace("\r\n", "\n"));
-        }
-    }
-    if let Ok(out) = Command::new("wl-paste").arg("-p").output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
-        }
-    }
-
-    if let Ok(out) = Command::new("wl-paste").output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
-        }
-    }
-
-    if let Ok(out) = Command::new("xclip")
-        .args(["-o", "-selection", "primary"])
-        .output()
-        && out.status.success()
     {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
+        any_tool_ran = true;
+        if let Some(s) = non_empty_stdout(&out) {
+            return Ok(Some(s.replace("\r\n", "\n")));
         }
     }
 
-    if let Ok(out) = Command::new("xclip").args(["-o"]).output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
+    for (tool, args) in SELECTION_READERS {
+        if let Ok(out) = Command::new(tool).args(*args).output() {
+            any_tool_ran = true;
+            if let Some(s) = non_empty_stdout(&out) {
+                return Ok(Some(s));
+            }
         }
     }
 
-    if let Ok(out) = Command::new("xsel").arg("-o").output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
-        }
+    if any_tool_ran {
+        Ok(None)
+    } else {
+        Err(LayoutError::ToolMissing {
+            tool: "wl-paste, xclip or xsel",
+        })
     }
+}
 
-    if let Ok(out) = Command::new("xsel").args(["-o", "-b"]).output()
-        && out.status.success()
-    {
-        let s = String::from_utf8_lossy(&out.stdout).to_string();
-        if !s.is_empty() {
-            return Some(s);
-        }
+fn non_empty_stdout(out: &Output) -> Option<String> {
+    if !out.status.success() {
+        return None;
     }
-
-    None
+    let s = String::from_utf8_lossy(&out.stdout).to_string();
+    if s.is_empty() { None } else { Some(s) }
 }
 
 fn is_wsl() -> bool {
@@ -81,23 +61,30 @@ fn is_wsl() -> bool {
             .unwrap_or(false)
 }
 
-pub fn replace_highlighted_text(new_text: &str) -> Result<(), String> {
+fn type_with(tool: &'static str, args: &[&str], text: &str) -> Result<(), LayoutError> {
+    let status = Command::new(tool)
+        .args(args)
+        .arg(text)
+        .status()
+        .map_err(|e| LayoutError::from_spawn(tool, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(LayoutError::ToolFailed {
+            tool,
+            reason: status.to_string(),
+        })
+    }
+}
+
+pub fn replace_highlighted_text(new_text: &str) -> Result<(), LayoutError> {
     if Command::new("wtype")
         .args(["--"])
         .status()
         .map(|_| true)
         .unwrap_or(false)
     {
-        let status = Command::new("wtype")
-            .arg("--")
-            .arg(new_text)
-            .status()
-            .map_err(|e| e.to_string())?;
-        return if status.success() {
-            Ok(())
-        } else {
-            Err("wtype failed".to_string())
-        };
+        return type_with("wtype", &["--"], new_text);
     }
 
     if Command::new("xdotool")
@@ -106,23 +93,12 @@ pub fn replace_highlighted_text(new_text: &str) -> Result<(), String> {
         .map(|s| s.success())
         .unwrap_or(false)
     {
-        let status = Command::new("xdotool")
-            .args(["type", "--clearmodifiers", "--"])
-            .arg(new_text)
-            .status()
-            .map_err(|e| e.to_string())?;
-        return if status.success() {
-            Ok(())
-        } else {
-            Err("xdotool failed".to_string())
-        };
-    }
-
-    if is_wsl() {
-        return Err("WSL typing not supported without GUI input tool (wtype/xdotool)".to_string());
+        return type_with("xdotool", &["type", "--clearmodifiers", "--"], new_text);
     }
 
-    Err("no typing tool available (wtype or xdotool)".to_string())
+    Err(LayoutError::ToolMissing {
+        tool: "wtype or xdotool",
+    })
 }
 
 #[cfg(all(test, target_os = "linux"))]
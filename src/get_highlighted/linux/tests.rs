@@ -14,3 +14,14 @@ fn test_replace_highlighted_text_smoke() {
 fn test_is_wsl_detection() {
     let _ = is_wsl();
 }
+
+#[test]
+fn test_type_with_missing_tool() {
+    let result = type_with("layout-lib-missing-tool", &["--"], "text");
+    assert_eq!(
+        result,
+        Err(LayoutError::ToolMissing {
+            tool: "layout-lib-missing-tool"
+        })
+    );
+}
@@ -1,6 +1,8 @@
 #![cfg(target_os = "windows")]
 
 use std::mem;
+
+use crate::error::LayoutError;
 use windows::Win32::{
     System::Com::{
         CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx,
@@ -19,7 +21,7 @@ use windows::Win32::{
     },
 };
 
-fn type_unicode_text(text: &str) {
+fn type_unicode_text(text: &str) -> Result<(), LayoutError> {
     unsafe {
         let utf16: Vec<u16> = text.encode_utf16().collect();
         let mut inputs: Vec<INPUT> = Vec::with_capacity(utf16.len() * 2);
@@ -49,14 +51,23 @@ fn type_unicode_text(text: &str) {
                 },
             });
         }
-        if !inputs.is_empty() {
-            let _ = SendInput(&inputs, mem::size_of::<INPUT>() as i32);
+        if inputs.is_empty() {
+            return Ok(());
+        }
+        let sent = SendInput(&inputs, mem::size_of::<INPUT>() as i32);
+        if sent as usize == inputs.len() {
+            Ok(())
+        } else {
+            Err(LayoutError::ToolFailed {
+                tool: "SendInput",
+                reason: format!("sent {sent} of {} inputs", inputs.len()),
+            })
         }
     }
 }
 
-pub fn get_highlighted_text() -> Option<String> {
-    try_uia_get_selection_text()
+pub fn get_highlighted_text() -> Result<Option<String>, LayoutError> {
+    Ok(try_uia_get_selection_text())
 }
 
 fn try_uia_get_selection_text() -> Option<String> {
@@ -131,10 +142,8 @@ fn find_selected_text_in_tree(
             return Some(text);
         }
 
-        if let Ok(children) = root.FindAll(
-            TreeScope_Children,
-            &automation.CreateTrueCondition().unwrap(),
-        ) {
+        let condition = automation.CreateTrueCondition().ok()?;
+        if let Ok(children) = root.FindAll(TreeScope_Children, &condition) {
             let count = children.Length().unwrap_or(0);
             for i in 0..count {
                 if let Ok(child) = children.GetElement(i)
@@ -158,9 +167,8 @@ impl Drop for CoUninitGuard {
     }
 }
 
-pub fn replace_highlighted_text(new_text: &str) -> Result<(), String> {
-    type_unicode_text(new_text);
-    Ok(())
+pub fn replace_highlighted_text(new_text: &str) -> Result<(), LayoutError> {
+    type_unicode_text(new_text)
 }
 
 #[cfg(all(test, target_os = "windows"))]
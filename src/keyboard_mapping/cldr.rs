@@ -0,0 +1,419 @@
+//! Unicode CLDR keyboard definitions (the LDML `<keyboard>` format of the
+//! CLDR `keyboards/` directory), read on every platform.
+
+use quick_xml::Reader;
+use quick_xml::de::from_str;
+use quick_xml::events::Event;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::physical_key::PhysicalKey;
+use super::types::{DeadKey, KeyStroke, KeyboardLayout, LayoutId, LayoutMap, ShiftLevel};
+use crate::error::LayoutError;
+
+const FORMAT: &str = "CLDR keyboard";
+
+impl LayoutMap {
+    /// Map of the CLDR keyboard `xml`, e.g. `de-t-k0-windows.xml`.
+    ///
+    /// Each [`ShiftLevel`] uses the first `<keyMap>` whose `modifiers` match
+    /// it: Shift is the left Shift key, AltGr is right Alt, Ctrl+Alt or
+    /// Option, and Caps is Caps Lock. Keys are matched by their ISO
+    /// position (`C01` is `KeyA`). Simple transforms of two characters turn
+    /// keys that type a non-alphanumeric character into dead keys, e.g.
+    /// `^` followed by `a` into `â`, with the transform of the character and
+    /// a space as the accent; longer transforms are not represented.
+    ///
+    /// The layout id is the keyboard's `locale` (`de-t-k0-windows`), and its
+    /// language is the locale before the `-t-` extension.
+    pub fn from_cldr_keyboard(xml: &str) -> Result<LayoutMap, LayoutError> {
+        let raw: RawKeyboard = from_str(xml).map_err(|err| invalid(err.to_string()))?;
+        if raw.locale.is_empty() {
+            return Err(invalid("the keyboard has no locale"));
+        }
+        Ok(raw.layout_map())
+    }
+
+    /// Like [`LayoutMap::from_cldr_keyboard`] for the file at `path`.
+    pub fn from_cldr_keyboard_file(path: impl AsRef<Path>) -> Result<LayoutMap, LayoutError> {
+        let path = path.as_ref();
+        Self::from_cldr_keyboard(&read(path)?).map_err(|err| in_file(err, path))
+    }
+}
+
+/// Maps of every CLDR keyboard under `dir`, such as a checkout of CLDR's
+/// `keyboards/` directory, in path order.
+///
+/// Subdirectories are searched too. XML files whose root element is not
+/// `<keyboard>`, like the `_platform.xml` files, are skipped; a keyboard
+/// file that cannot be read is an error naming the file.
+pub fn load_cldr_keyboards(dir: impl AsRef<Path>) -> Result<Vec<LayoutMap>, LayoutError> {
+    let mut paths = Vec::new();
+    collect_xml_files(dir.as_ref(), &mut paths)?;
+    paths.sort();
+    let mut maps = Vec::new();
+    for path in paths {
+        let xml = read(&path)?;
+        if root_element(&xml).as_deref() == Some("keyboard") {
+            maps.push(LayoutMap::from_cldr_keyboard(&xml).map_err(|err| in_file(err, &path))?);
+        }
+    }
+    Ok(maps)
+}
+
+fn collect_xml_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), LayoutError> {
+    let access = |err: std::io::Error| LayoutError::FileAccess {
+        path: dir.to_path_buf(),
+        reason: err.to_string(),
+    };
+    for entry in fs::read_dir(dir).map_err(access)? {
+        let path = entry.map_err(access)?.path();
+        if path.is_dir() {
+            collect_xml_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "xml") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Name of the first element of `xml`, skipping the declaration, doctype
+/// and comments.
+fn root_element(xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(element) | Event::Empty(element) => {
+                return Some(String::from_utf8_lossy(element.name().as_ref()).into_owned());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, LayoutError> {
+    fs::read_to_string(path).map_err(|err| LayoutError::FileAccess {
+        path: path.to_path_buf(),
+        reason: err.to_string(),
+    })
+}
+
+fn invalid(reason: impl Into<String>) -> LayoutError {
+    LayoutError::InvalidData {
+        format: FORMAT,
+        reason: reason.into(),
+    }
+}
+
+/// Prefixes the reason of an [`LayoutError::InvalidData`] with `path`.
+fn in_file(err: LayoutError, path: &Path) -> LayoutError {
+    match err {
+        LayoutError::InvalidData { format, reason } => LayoutError::InvalidData {
+            format,
+            reason: format!("{}: {reason}", path.display()),
+        },
+        err => err,
+    }
+}
+
+/// Key at an ISO 9995 position as CLDR names them: row `A` (space bar) to
+/// `E` (digits) and the column, `00` being left of the main block.
+fn key_from_iso(iso: &str) -> Option<PhysicalKey> {
+    use PhysicalKey::*;
+    const E: [PhysicalKey; 14] = [
+        Backquote, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9, Digit0,
+        Minus, Equal, IntlYen,
+    ];
+    const D: [PhysicalKey; 13] = [
+        KeyQ,
+        KeyW,
+        KeyE,
+        KeyR,
+        KeyT,
+        KeyY,
+        KeyU,
+        KeyI,
+        KeyO,
+        KeyP,
+        BracketLeft,
+        BracketRight,
+        Backslash,
+    ];
+    const C: [PhysicalKey; 12] = [
+        KeyA, KeyS, KeyD, KeyF, KeyG, KeyH, KeyJ, KeyK, KeyL, Semicolon, Quote, Backslash,
+    ];
+    const B: [PhysicalKey; 12] = [
+        IntlBackslash,
+        KeyZ,
+        KeyX,
+        KeyC,
+        KeyV,
+        KeyB,
+        KeyN,
+        KeyM,
+        Comma,
+        Period,
+        Slash,
+        IntlRo,
+    ];
+    let (row, column) = iso.split_at_checked(1)?;
+    let column: usize = column.parse().ok()?;
+    match row {
+        "E" => E.get(column).copied(),
+        "D" => column.checked_sub(1).and_then(|i| D.get(i).copied()),
+        "C" => column.checked_sub(1).and_then(|i| C.get(i).copied()),
+        "B" => B.get(column).copied(),
+        "A" if column == 3 => Some(Space),
+        _ => None,
+    }
+}
+
+/// Replaces CLDR's `\u{…}` escapes with the characters they name.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("\\u{") {
+        unescaped.push_str(&rest[..start]);
+        let escape = &rest[start + 3..];
+        let decoded = escape.split_once('}').and_then(|(hex, after)| {
+            let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+            Some((c, after))
+        });
+        match decoded {
+            Some((c, after)) => {
+                unescaped.push(c);
+                rest = after;
+            }
+            None => {
+                unescaped.push_str("\\u{");
+                rest = escape;
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Modifiers held for a level, by class: Shift, Alt (Option), Ctrl, Cmd
+/// and Caps Lock.
+type Held = [Option<Side>; 5];
+
+/// Modifier combinations that select `level`. AltGr has several spellings
+/// across platforms, so it is tried as each of them.
+fn held_for_level(level: ShiftLevel) -> Vec<Held> {
+    let shift = matches!(level, ShiftLevel::Shift | ShiftLevel::ShiftAltGr).then_some(Side::Left);
+    let caps = (level == ShiftLevel::Caps).then_some(Side::Left);
+    if matches!(level, ShiftLevel::AltGr | ShiftLevel::ShiftAltGr) {
+        vec![
+            [shift, Some(Side::Right), None, None, caps],
+            [shift, Some(Side::Left), Some(Side::Left), None, caps],
+        ]
+    } else {
+        vec![[shift, None, None, None, caps]]
+    }
+}
+
+/// Whether one `+`-joined alternative of a `modifiers` attribute, such as
+/// `shift+altR+caps?`, matches `held`: every term without `?` must be held
+/// on its side, and every held modifier must be named.
+fn modifiers_match(alternative: &str, held: &Held) -> bool {
+    let mut named = [false; 5];
+    for term in alternative.split('+').filter(|term| !term.is_empty()) {
+        let (name, optional) = match term.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (term, false),
+        };
+        let (class, side) = match name {
+            "shift" => (0, None),
+            "shiftL" => (0, Some(Side::Left)),
+            "shiftR" => (0, Some(Side::Right)),
+            "alt" | "opt" => (1, None),
+            "altL" | "optL" => (1, Some(Side::Left)),
+            "altR" | "optR" => (1, Some(Side::Right)),
+            "ctrl" => (2, None),
+            "ctrlL" => (2, Some(Side::Left)),
+            "ctrlR" => (2, Some(Side::Right)),
+            "cmd" => (3, None),
+            "caps" => (4, None),
+            _ => return false,
+        };
+        match held[class] {
+            Some(held_side) if side.is_none_or(|side| side == held_side) => named[class] = true,
+            _ if optional => {}
+            _ => return false,
+        }
+    }
+    held.iter()
+        .zip(named)
+        .all(|(held, named)| held.is_none() || named)
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyboard {
+    #[serde(rename = "@locale", default)]
+    locale: String,
+    #[serde(default)]
+    names: RawNames,
+    #[serde(rename = "keyMap", default)]
+    key_maps: Vec<RawKeyMap>,
+    #[serde(default)]
+    transforms: Vec<RawTransforms>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawNames {
+    #[serde(rename = "name", default)]
+    names: Vec<RawName>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawName {
+    #[serde(rename = "@value")]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyMap {
+    #[serde(rename = "@modifiers", default)]
+    modifiers: String,
+    #[serde(rename = "map", default)]
+    maps: Vec<RawMap>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMap {
+    #[serde(rename = "@iso")]
+    iso: String,
+    #[serde(rename = "@to")]
+    to: String,
+    #[serde(rename = "@transform", default)]
+    transform: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTransforms {
+    #[serde(rename = "@type")]
+    kind: String,
+    #[serde(rename = "transform", default)]
+    transforms: Vec<RawTransform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTransform {
+    #[serde(rename = "@from")]
+    from: String,
+    #[serde(rename = "@to")]
+    to: String,
+}
+
+impl RawKeyMap {
+    fn matches(&self, level: ShiftLevel) -> bool {
+        let mut alternatives = self.modifiers.split_whitespace().peekable();
+        if alternatives.peek().is_none() {
+            return level == ShiftLevel::Base;
+        }
+        let held = held_for_level(level);
+        alternatives.any(|alternative| held.iter().any(|held| modifiers_match(alternative, held)))
+    }
+}
+
+impl RawKeyboard {
+    fn layout_map(&self) -> LayoutMap {
+        let dead_keys = self.dead_keys();
+        let mut layout_map = LayoutMap {
+            layout: self.keyboard_layout(),
+            ..Default::default()
+        };
+        for level in ShiftLevel::ALL {
+            let Some(key_map) = self.key_maps.iter().find(|map| map.matches(level)) else {
+                continue;
+            };
+            let mut seen = Vec::new();
+            for map in &key_map.maps {
+                let Some(key) = key_from_iso(&map.iso) else {
+                    continue;
+                };
+                if seen.contains(&key) {
+                    continue;
+                }
+                seen.push(key);
+                let output = unescape(&map.to);
+                let stroke = KeyStroke::new(key, level);
+                let transforms = map.transform.as_deref() != Some("no");
+                match dead_keys.get(output.as_str()).filter(|_| transforms) {
+                    Some(dead_key) => layout_map.insert_dead_key(stroke, dead_key.clone()),
+                    None if !output.is_empty() => layout_map.insert_level(stroke, output),
+                    None => {}
+                }
+            }
+        }
+        layout_map.layout.direction = layout_map.base_direction();
+        layout_map
+    }
+
+    /// Dead keys made from two-character simple transforms, by the
+    /// non-alphanumeric character that starts them.
+    fn dead_keys(&self) -> HashMap<String, DeadKey> {
+        let mut combinations: HashMap<char, BTreeMap<char, String>> = HashMap::new();
+        for transforms in self.transforms.iter().filter(|t| t.kind == "simple") {
+            for transform in &transforms.transforms {
+                let from: Vec<char> = unescape(&transform.from).chars().collect();
+                if let [dead, next] = from[..]
+                    && !dead.is_alphanumeric()
+                {
+                    combinations
+                        .entry(dead)
+                        .or_default()
+                        .entry(next)
+                        .or_insert_with(|| unescape(&transform.to));
+                }
+            }
+        }
+        combinations
+            .into_iter()
+            .map(|(dead, mut combinations)| {
+                let accent = combinations
+                    .remove(&' ')
+                    .unwrap_or_else(|| dead.to_string());
+                let dead_key = DeadKey {
+                    accent,
+                    combinations,
+                };
+                (dead.to_string(), dead_key)
+            })
+            .collect()
+    }
+
+    fn keyboard_layout(&self) -> KeyboardLayout {
+        let name = self
+            .names
+            .names
+            .first()
+            .map_or_else(|| self.locale.clone(), |name| name.value.clone());
+        let language = self
+            .locale
+            .split_once("-t-")
+            .map_or(self.locale.as_str(), |(language, _)| language);
+        let mut layout = KeyboardLayout {
+            id: LayoutId::new(&self.locale),
+            lang_name: name.clone(),
+            description: name,
+            native_id: self.locale.clone(),
+            ..Default::default()
+        };
+        layout.set_language_tag(language);
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests;
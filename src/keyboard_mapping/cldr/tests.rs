@@ -0,0 +1,263 @@
+use super::*;
+use crate::keyboard_mapping::shift_text_language;
+use crate::keyboard_mapping::temp_path;
+use crate::keyboard_mapping::types::KeyboardDirection;
+
+/// Abridged from CLDR's `keyboards/windows/de-t-k0-windows.xml`.
+const GERMAN: &str = r##"<?xml version="1.0" encoding="UTF-8" ?>
+<!DOCTYPE keyboard SYSTEM "../dtd/ldmlKeyboard.dtd">
+<keyboard locale="de-t-k0-windows">
+	<version platform="10.0" number="$Revision$"/>
+	<names>
+		<name value="German"/>
+	</names>
+	<keyMap>
+		<map iso="E00" to="^"/> <!-- dead circumflex -->
+		<map iso="E01" to="1"/>
+		<map iso="E12" to="´"/>
+		<map iso="D01" to="q"/>
+		<map iso="D06" to="z"/>
+		<map iso="D11" to="ü"/>
+		<map iso="C01" to="a"/>
+		<map iso="C12" to="#"/>
+		<map iso="B00" to="&lt;"/>
+		<map iso="B01" to="y"/>
+		<map iso="A03" to=" "/>
+	</keyMap>
+	<keyMap modifiers="shift">
+		<map iso="E00" to="°"/>
+		<map iso="E01" to="!"/>
+		<map iso="E12" to="`"/>
+		<map iso="D01" to="Q"/>
+		<map iso="C01" to="A"/>
+		<map iso="B00" to="&gt;"/>
+		<map iso="A03" to=" "/>
+	</keyMap>
+	<keyMap modifiers="caps">
+		<map iso="D01" to="Q"/>
+		<map iso="D11" to="Ü"/>
+	</keyMap>
+	<keyMap modifiers="altR+caps? ctrl+alt+caps?">
+		<map iso="D01" to="@"/>
+		<map iso="B00" to="|"/>
+		<map iso="E12" to="\u{22}" transform="no"/>
+	</keyMap>
+	<transforms type="simple">
+		<transform from="^ " to="^"/>
+		<transform from="^a" to="â"/>
+		<transform from="´a" to="á"/>
+		<transform from="´ " to="´"/>
+		<transform from="`a" to="à"/>
+		<transform from="aa" to="å"/>
+		<transform from="^^a" to="ä"/>
+	</transforms>
+</keyboard>
+"##;
+
+/// Abridged from CLDR's `keyboards/osx/he-t-k0-osx.xml`.
+const HEBREW: &str = r#"<keyboard locale="he-t-k0-osx">
+	<names><name value="Hebrew"/></names>
+	<keyMap>
+		<map iso="C01" to="ש"/>
+		<map iso="D01" to="/"/>
+	</keyMap>
+	<keyMap modifiers="opt+caps?">
+		<map iso="C01" to="\u{5B0}"/>
+	</keyMap>
+	<keyMap modifiers="shift+opt+caps?">
+		<map iso="C01" to="\u{5B8}"/>
+	</keyMap>
+</keyboard>
+"#;
+
+fn output(map: &LayoutMap, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+    map.get(key, level)
+}
+
+#[test]
+fn test_from_cldr_keyboard_levels() {
+    let map = LayoutMap::from_cldr_keyboard(GERMAN).unwrap();
+    assert_eq!(output(&map, PhysicalKey::KeyQ, ShiftLevel::Base), Some("q"));
+    assert_eq!(output(&map, PhysicalKey::KeyY, ShiftLevel::Base), Some("z"));
+    assert_eq!(
+        output(&map, PhysicalKey::KeyQ, ShiftLevel::Shift),
+        Some("Q")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyQ, ShiftLevel::AltGr),
+        Some("@")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::BracketLeft, ShiftLevel::Caps),
+        Some("Ü")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::IntlBackslash, ShiftLevel::Shift),
+        Some(">")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Backslash, ShiftLevel::Base),
+        Some("#")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Space, ShiftLevel::Base),
+        Some(" ")
+    );
+    // `\u{22}` escapes, and `transform="no"` keeps the key plain.
+    assert_eq!(
+        output(&map, PhysicalKey::Equal, ShiftLevel::AltGr),
+        Some("\"")
+    );
+}
+
+#[test]
+fn test_from_cldr_keyboard_transforms() {
+    let map = LayoutMap::from_cldr_keyboard(GERMAN).unwrap();
+    let circumflex = &map.dead_keys[&KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base)];
+    assert_eq!(circumflex.accent, "^");
+    assert_eq!(circumflex.combinations[&'a'], "â");
+    assert!(!circumflex.combinations.contains_key(&' '));
+
+    let typed = map.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Shift),
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("à"));
+    assert!(
+        map.dead_keys
+            .contains_key(&KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Base))
+    );
+
+    // Letters that start transforms stay letters.
+    assert_eq!(output(&map, PhysicalKey::KeyA, ShiftLevel::Base), Some("a"));
+    // The Shift level is not a dead key: `°` starts no transform.
+    assert_eq!(
+        output(&map, PhysicalKey::Backquote, ShiftLevel::Shift),
+        Some("°")
+    );
+}
+
+#[test]
+fn test_from_cldr_keyboard_dead_key_replay() {
+    let map = LayoutMap::from_cldr_keyboard(GERMAN).unwrap();
+    let typed = map.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Base),
+        KeyStroke::new(PhysicalKey::Space, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("´"));
+    assert_eq!(shift_text_language("´á", &map, &map), "´á");
+    assert_eq!(shift_text_language("^â a", &map, &map), "^â a");
+}
+
+#[test]
+fn test_from_cldr_keyboard_metadata() {
+    let layout = LayoutMap::from_cldr_keyboard(GERMAN).unwrap().layout;
+    assert_eq!(layout.id, LayoutId::new("de-t-k0-windows"));
+    assert_eq!(layout.native_id, "de-t-k0-windows");
+    assert_eq!(layout.description, "German");
+    assert_eq!(layout.language_tag.as_deref(), Some("de"));
+    assert_eq!(layout.languages, ["deu"]);
+    assert_eq!(layout.short_label, "DE");
+    assert_eq!(layout.direction, KeyboardDirection::LTR);
+
+    let hebrew = LayoutMap::from_cldr_keyboard(HEBREW).unwrap();
+    assert_eq!(hebrew.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(hebrew.layout.scripts, ["Hebr"]);
+    assert_eq!(
+        output(&hebrew, PhysicalKey::KeyA, ShiftLevel::AltGr),
+        Some("\u{5B0}")
+    );
+    assert_eq!(
+        output(&hebrew, PhysicalKey::KeyA, ShiftLevel::ShiftAltGr),
+        Some("\u{5B8}")
+    );
+    assert_eq!(output(&hebrew, PhysicalKey::KeyA, ShiftLevel::Shift), None);
+}
+
+#[test]
+fn test_from_cldr_keyboard_errors() {
+    assert!(matches!(
+        LayoutMap::from_cldr_keyboard("<keyboard><keyMap>"),
+        Err(LayoutError::InvalidData { .. })
+    ));
+    let error = LayoutMap::from_cldr_keyboard("<keyboard/>").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid CLDR keyboard: the keyboard has no locale"
+    );
+}
+
+#[test]
+fn test_load_cldr_keyboards() {
+    let root = temp_path("cldr-keyboards");
+    fs::create_dir_all(root.join("windows")).unwrap();
+    fs::create_dir_all(root.join("osx")).unwrap();
+    fs::write(root.join("windows").join("de-t-k0-windows.xml"), GERMAN).unwrap();
+    fs::write(root.join("osx").join("he-t-k0-osx.xml"), HEBREW).unwrap();
+    fs::write(
+        root.join("osx").join("_platform.xml"),
+        r#"<?xml version="1.0"?><platform id="osx"><hardwareMap/></platform>"#,
+    )
+    .unwrap();
+    fs::write(root.join("README.txt"), "not a keyboard").unwrap();
+    let maps = load_cldr_keyboards(&root);
+
+    fs::write(root.join("broken.xml"), "<keyboard><keyMap>").unwrap();
+    let broken = load_cldr_keyboards(&root);
+    fs::remove_dir_all(&root).unwrap();
+
+    let ids: Vec<_> = maps.unwrap().into_iter().map(|map| map.layout.id).collect();
+    assert_eq!(
+        ids,
+        [
+            LayoutId::new("he-t-k0-osx"),
+            LayoutId::new("de-t-k0-windows")
+        ]
+    );
+    let error = broken.unwrap_err().to_string();
+    assert!(error.contains("broken.xml"), "{error}");
+    assert!(matches!(
+        load_cldr_keyboards(&root),
+        Err(LayoutError::FileAccess { .. })
+    ));
+}
+
+#[test]
+fn test_key_from_iso() {
+    assert_eq!(key_from_iso("E00"), Some(PhysicalKey::Backquote));
+    assert_eq!(key_from_iso("E10"), Some(PhysicalKey::Digit0));
+    assert_eq!(key_from_iso("D13"), Some(PhysicalKey::Backslash));
+    assert_eq!(key_from_iso("C12"), Some(PhysicalKey::Backslash));
+    assert_eq!(key_from_iso("B11"), Some(PhysicalKey::IntlRo));
+    assert_eq!(key_from_iso("A03"), Some(PhysicalKey::Space));
+    assert_eq!(key_from_iso("D00"), None);
+    assert_eq!(key_from_iso("A01"), None);
+    assert_eq!(key_from_iso("X"), None);
+}
+
+#[test]
+fn test_modifiers_match() {
+    let held = |level| held_for_level(level);
+    let any =
+        |alternative: &str, level| held(level).iter().any(|h| modifiers_match(alternative, h));
+    assert!(any("shift", ShiftLevel::Shift));
+    assert!(any("shiftL+caps?", ShiftLevel::Shift));
+    assert!(!any("shiftR", ShiftLevel::Shift));
+    assert!(!any("shift", ShiftLevel::Base));
+    assert!(any("altR+caps?", ShiftLevel::AltGr));
+    assert!(any("ctrl+alt", ShiftLevel::AltGr));
+    assert!(any("opt", ShiftLevel::AltGr));
+    assert!(!any("altR", ShiftLevel::ShiftAltGr));
+    assert!(any("shift+altR", ShiftLevel::ShiftAltGr));
+    assert!(any("caps", ShiftLevel::Caps));
+    assert!(!any("cmd", ShiftLevel::Base));
+    assert!(!any("unknown", ShiftLevel::Base));
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape("\\u{22}"), "\"");
+    assert_eq!(unescape("a\\u{1F600}b"), "a😀b");
+    assert_eq!(unescape("\\u{zz}"), "\\u{zz}");
+    assert_eq!(unescape("plain"), "plain");
+}
@@ -0,0 +1,78 @@
+//! Layouts compiled into the crate, for machines without layout data such
+//! as CI containers. Enabled by the `embedded-layouts` feature.
+
+use std::sync::OnceLock;
+
+use super::language::language_subtag;
+use super::profile::KeyboardProfile;
+use super::types::LayoutMap;
+
+/// One profile per layout. Each was captured from the xkeyboard-config
+/// layout named in its `native_id`, except that the letter keys of `kr`
+/// type the jamo of the standard two-set Korean layout, which XKB leaves
+/// to the input method.
+const LAYOUT_FILES: &[&str] = &[
+    include_str!("embedded/layouts/us.toml"),
+    include_str!("embedded/layouts/gb.toml"),
+    include_str!("embedded/layouts/il.toml"),
+    include_str!("embedded/layouts/ru.toml"),
+    include_str!("embedded/layouts/ua.toml"),
+    include_str!("embedded/layouts/ar.toml"),
+    include_str!("embedded/layouts/fa.toml"),
+    include_str!("embedded/layouts/de.toml"),
+    include_str!("embedded/layouts/fr.toml"),
+    include_str!("embedded/layouts/es.toml"),
+    include_str!("embedded/layouts/gr.toml"),
+    include_str!("embedded/layouts/tr.toml"),
+    include_str!("embedded/layouts/kr.toml"),
+    include_str!("embedded/layouts/th.toml"),
+];
+
+/// Every embedded layout: `us`, `gb`, `il`, `ru`, `ua`, `ar`, `fa`, `de`,
+/// `fr`, `es`, `gr`, `tr`, `kr` and `th`, in that order. Parsed on first
+/// use.
+pub fn embedded_layouts() -> &'static [LayoutMap] {
+    static LAYOUTS: OnceLock<Vec<LayoutMap>> = OnceLock::new();
+    LAYOUTS.get_or_init(|| {
+        LAYOUT_FILES
+            .iter()
+            .flat_map(|toml| {
+                KeyboardProfile::from_toml(toml)
+                    .expect("embedded layouts are valid profiles")
+                    .layouts
+            })
+            .collect()
+    })
+}
+
+/// The embedded layout called `name`, by its id (`"fa"`) or the XKB layout
+/// it was captured from (`"ir"`), ignoring case.
+pub fn embedded_layout(name: &str) -> Option<&'static LayoutMap> {
+    let matches = |candidate: &str| candidate.eq_ignore_ascii_case(name);
+    let layouts = embedded_layouts();
+    layouts
+        .iter()
+        .find(|map| matches(map.layout.id.as_str()))
+        .or_else(|| layouts.iter().find(|map| matches(&map.layout.native_id)))
+}
+
+/// Embedded layouts for `language`, given as a BCP-47 tag (`"he-IL"`), a
+/// primary subtag (`"he"`) or an ISO 639-2 code (`"heb"`). The region is
+/// ignored, so `"en"` and `"en-US"` both give `us` and `gb`.
+pub fn embedded_layouts_for_language(language: &str) -> Vec<&'static LayoutMap> {
+    let primary = |tag: &str| language_subtag(tag.split(['-', '_']).next().unwrap_or_default());
+    let wanted = primary(language);
+    embedded_layouts()
+        .iter()
+        .filter(|map| {
+            map.layout
+                .language_tag
+                .iter()
+                .chain(&map.layout.languages)
+                .any(|tag| primary(tag) == wanted)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
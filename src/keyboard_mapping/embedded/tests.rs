@@ -0,0 +1,88 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::shift_text_language;
+use crate::keyboard_mapping::types::{KeyStroke, KeyboardDirection, LayoutId, ShiftLevel};
+
+fn layout(name: &str) -> &'static LayoutMap {
+    embedded_layout(name).unwrap()
+}
+
+#[test]
+fn test_embedded_layouts_are_all_present() {
+    let ids: Vec<&str> = embedded_layouts()
+        .iter()
+        .map(|map| map.layout.id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "us", "gb", "il", "ru", "ua", "ar", "fa", "de", "fr", "es", "gr", "tr", "kr", "th"
+        ]
+    );
+    for map in embedded_layouts() {
+        assert!(map.layout.language_tag.is_some(), "{}", map.layout.id);
+        assert!(!map.layout.scripts.is_empty(), "{}", map.layout.id);
+        assert!(
+            map.get(PhysicalKey::KeyA, ShiftLevel::Base).is_some(),
+            "{}",
+            map.layout.id
+        );
+    }
+}
+
+#[test]
+fn test_embedded_layout_by_name() {
+    assert_eq!(layout("il").layout.description, "Hebrew");
+    assert_eq!(layout("IL").layout.id, LayoutId::new("il"));
+    assert_eq!(layout("ir").layout.id, LayoutId::new("fa"));
+    assert_eq!(layout("ara").layout.id, LayoutId::new("ar"));
+    assert_eq!(layout("ar").layout.direction, KeyboardDirection::RTL);
+    assert!(embedded_layout("zz").is_none());
+}
+
+#[test]
+fn test_embedded_layouts_for_language() {
+    let ids = |language: &str| -> Vec<&str> {
+        embedded_layouts_for_language(language)
+            .into_iter()
+            .map(|map| map.layout.id.as_str())
+            .collect()
+    };
+    assert_eq!(ids("he"), ["il"]);
+    assert_eq!(ids("heb"), ["il"]);
+    assert_eq!(ids("uk-UA"), ["ua"]);
+    assert_eq!(ids("en-US"), ["us", "gb"]);
+    assert_eq!(ids("el"), ["gr"]);
+    assert_eq!(ids("ko"), ["kr"]);
+    assert!(ids("xx").is_empty());
+}
+
+#[test]
+fn test_embedded_layouts_convert_text() {
+    assert_eq!(
+        shift_text_language("akuo", layout("us"), layout("il")),
+        "שלום"
+    );
+    assert_eq!(
+        shift_text_language("ghbdtn", layout("us"), layout("ru")),
+        "привет"
+    );
+    assert_eq!(
+        shift_text_language("gksrmf", layout("us"), layout("kr")),
+        "ㅎㅏㄴㄱㅡㄹ"
+    );
+    assert_eq!(
+        shift_text_language("Qwerty", layout("us"), layout("de")),
+        "Qwertz"
+    );
+}
+
+#[test]
+fn test_embedded_layouts_keep_dead_keys() {
+    let german = layout("de");
+    let typed = german.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Base),
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("é"));
+}
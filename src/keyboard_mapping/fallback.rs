@@ -1,23 +1,102 @@
 #![cfg(not(any(target_os = "windows", target_os = "linux")))]
 
-pub fn get_layout(_index: usize) -> Option<KeyboardLayout> {
-    panic!("keyboard_mapping: get_layout is not implemented for this OS");
+use super::types::{KeyboardLayout, LayoutId, LayoutMap};
+use super::watch::LayoutWatcher;
+use crate::error::LayoutError;
+
+pub fn current_layout() -> Result<LayoutId, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::current_layout",
+    })
+}
+
+pub fn activate_layout(_id: &LayoutId) -> Result<LayoutId, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::activate_layout",
+    })
+}
+
+pub fn watch_layout_changes() -> Result<LayoutWatcher, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::watch_layout_changes",
+    })
+}
+
+pub fn get_layout(_id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::get_layout",
+    })
+}
+
+pub fn list_layouts() -> Result<Vec<KeyboardLayout>, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::list_layouts",
+    })
 }
 
-pub fn list_layouts() -> Vec<KeyboardLayout> {
-    panic!("keyboard_mapping: list_layouts is not implemented for this OS");
+pub fn vk_to_char_map_for_layout(_id: &LayoutId) -> Result<LayoutMap, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::vk_to_char_map_for_layout",
+    })
 }
 
-pub fn vk_to_char_map_for_layout(_index: u32) -> LayoutMap {
-    panic!("keyboard_mapping: vk_to_char_map_for_layout is not implemented for this OS");
+pub fn vk_to_char_map_default() -> Result<LayoutMap, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::vk_to_char_map_default",
+    })
 }
 
-pub fn vk_to_char_map_default() -> LayoutMap {
-    panic!("keyboard_mapping: vk_to_char_map_default is not implemented for this OS");
+pub fn all_layout_vk_maps() -> Result<Vec<LayoutMap>, LayoutError> {
+    Err(LayoutError::UnsupportedPlatform {
+        operation: "keyboard_mapping::all_layout_vk_maps",
+    })
 }
 
-pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
-    panic!("keyboard_mapping: all_layout_vk_maps is not implemented for this OS");
+/// Handle over the installed layouts. No backend exists for this OS, so every
+/// operation reports [`LayoutError::UnsupportedPlatform`].
+#[derive(Default)]
+pub struct KeyboardContext {}
+
+impl KeyboardContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn invalidate(&mut self) {}
+
+    pub fn all_layout_vk_maps(&mut self) -> Result<&[LayoutMap], LayoutError> {
+        Err(LayoutError::UnsupportedPlatform {
+            operation: "keyboard_mapping::all_layout_vk_maps",
+        })
+    }
+
+    pub fn list_layouts(&mut self) -> Result<Vec<KeyboardLayout>, LayoutError> {
+        list_layouts()
+    }
+
+    pub fn get_layout(&mut self, id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+        get_layout(id)
+    }
+
+    pub fn current_layout(&mut self) -> Result<LayoutId, LayoutError> {
+        current_layout()
+    }
+
+    pub fn activate_layout(&mut self, id: &LayoutId) -> Result<LayoutId, LayoutError> {
+        activate_layout(id)
+    }
+
+    pub fn vk_to_char_map_for_layout(&mut self, _id: &LayoutId) -> Result<&LayoutMap, LayoutError> {
+        Err(LayoutError::UnsupportedPlatform {
+            operation: "keyboard_mapping::vk_to_char_map_for_layout",
+        })
+    }
+
+    pub fn vk_to_char_map_default(&mut self) -> Result<&LayoutMap, LayoutError> {
+        Err(LayoutError::UnsupportedPlatform {
+            operation: "keyboard_mapping::vk_to_char_map_default",
+        })
+    }
 }
 
 #[cfg(test)]
@@ -1,35 +1,60 @@
 use super::*;
 
+fn assert_unsupported<T: std::fmt::Debug>(result: Result<T, LayoutError>, name: &str) {
+    match result {
+        Err(LayoutError::UnsupportedPlatform { operation }) => {
+            assert_eq!(operation, format!("keyboard_mapping::{name}"))
+        }
+        other => panic!("expected UnsupportedPlatform, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_get_layout_unsupported() {
+    assert_unsupported(get_layout(&LayoutId::new("us")), "get_layout");
+}
+
+#[test]
+fn test_current_layout_unsupported() {
+    assert_unsupported(current_layout(), "current_layout");
+}
+
+#[test]
+fn test_watch_layout_changes_unsupported() {
+    assert_unsupported(watch_layout_changes().map(|_| ()), "watch_layout_changes");
+}
+
+#[test]
+fn test_activate_layout_unsupported() {
+    assert_unsupported(activate_layout(&LayoutId::new("us")), "activate_layout");
+}
+
 #[test]
-#[should_panic(expected = "keyboard_mapping: get_layout is not implemented for this OS")]
-fn test_get_layout_panics() {
-    get_layout(0);
+fn test_list_layouts_unsupported() {
+    assert_unsupported(list_layouts(), "list_layouts");
 }
 
 #[test]
-#[should_panic(expected = "keyboard_mapping: list_layouts is not implemented for this OS")]
-fn test_list_layouts_panics() {
-    list_layouts();
+fn test_vk_to_char_map_for_layout_unsupported() {
+    assert_unsupported(
+        vk_to_char_map_for_layout(&LayoutId::new("us")),
+        "vk_to_char_map_for_layout",
+    );
 }
 
 #[test]
-#[should_panic(
-    expected = "keyboard_mapping: vk_to_char_map_for_layout is not implemented for this OS"
-)]
-fn test_vk_to_char_map_for_layout_panics() {
-    vk_to_char_map_for_layout(0);
+fn test_vk_to_char_map_default_unsupported() {
+    assert_unsupported(vk_to_char_map_default(), "vk_to_char_map_default");
 }
 
 #[test]
-#[should_panic(
-    expected = "keyboard_mapping: vk_to_char_map_default is not implemented for this OS"
-)]
-fn test_vk_to_char_map_default_panics() {
-    vk_to_char_map_default();
+fn test_all_layout_vk_maps_unsupported() {
+    assert_unsupported(all_layout_vk_maps(), "all_layout_vk_maps");
 }
 
 #[test]
-#[should_panic(expected = "keyboard_mapping: all_layout_vk_maps is not implemented for this OS")]
-fn test_all_layout_vk_maps_panics() {
-    all_layout_vk_maps();
+fn test_context_unsupported() {
+    let mut context = KeyboardContext::new();
+    assert_unsupported(context.list_layouts(), "list_layouts");
+    assert_unsupported(context.all_layout_vk_maps(), "all_layout_vk_maps");
 }
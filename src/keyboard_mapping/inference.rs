@@ -0,0 +1,295 @@
+//! Guessing which layout a text was meant to be typed on, by reading it back
+//! through every layout and asking a [`LanguageModel`] which reading is real
+//! language.
+
+use std::ops::Range;
+
+use super::language_model::{LanguageModel, LanguageModels};
+use super::remap::{rank_layouts, shift_text_language};
+use super::types::LayoutMap;
+
+/// Per-character log probability given to readings on layouts without a
+/// model: a uniform guess among this many characters. Ordinary text scores
+/// better under its own language's model, keyboard gibberish worse.
+const UNMODELLED_ALPHABET: f64 = 200.0;
+
+/// The layout a text was most likely meant for, see [`infer_layout`].
+#[derive(Debug, Clone)]
+pub struct LayoutGuess<'a> {
+    /// Layout the user meant to type on.
+    pub layout: &'a LayoutMap,
+    /// The text as it reads when its keystrokes are replayed on `layout`.
+    pub text: String,
+    /// Probability in `0.0..=1.0` that `layout` is the right one, among the
+    /// layouts that were compared.
+    pub confidence: f64,
+}
+
+/// Guesses the layout `text` was meant to be typed on.
+///
+/// The text is attributed to the layout that types it (as
+/// [`get_text_layout_map`](super::remap::get_text_layout_map) does), its
+/// keystrokes are replayed on every layout in `layout_maps`, and each reading
+/// is scored with the model of that layout's language. `"akuo"` typed on a US layout reads `"שלום"` on a
+/// Hebrew one, which the Hebrew model finds far more plausible than the
+/// English model finds `"akuo"`.
+///
+/// The scores are normalised into a posterior over the layouts, so the
+/// confidences of all layouts sum to one; ties go to the layout that types
+/// the text. Returns `None` when no layout types the text.
+pub fn infer_layout<'a>(
+    text: &str,
+    layout_maps: &'a [LayoutMap],
+    models: &LanguageModels,
+) -> Option<LayoutGuess<'a>> {
+    let (_, guesses) = read_on_every_layout(text, layout_maps, models)?;
+    best_guess(guesses)
+}
+
+/// Minimum confidence [`detect_mistyped_with`] needs before it suggests a
+/// correction. Short words are often plausible on more than one layout, and
+/// leaving them alone is cheaper than rewriting correct text.
+const MISTYPED_CONFIDENCE: f64 = 0.8;
+
+/// Text that was typed on the wrong layout, see [`detect_mistyped_with`].
+#[derive(Debug, Clone)]
+pub struct Correction<'a> {
+    /// Layout the text was typed on.
+    pub from: &'a LayoutMap,
+    /// Layout the user meant to type on.
+    pub to: &'a LayoutMap,
+    /// The same keystrokes replayed on `to`.
+    pub corrected: String,
+    /// Confidence that `corrected` is what the user meant: the
+    /// [`LayoutGuess::confidence`] of every layout that reads the text as
+    /// `corrected`, summed.
+    pub confidence: f64,
+}
+
+/// Checks whether `text` reads better on another layout than on the one
+/// that typed it, using the bundled language models.
+///
+/// Returns `None` for correctly typed text. See [`detect_mistyped_with`].
+#[cfg(feature = "language-models")]
+pub fn detect_mistyped<'a>(text: &str, layout_maps: &'a [LayoutMap]) -> Option<Correction<'a>> {
+    detect_mistyped_with(text, layout_maps, LanguageModels::bundled())
+}
+
+/// Checks whether `text` reads better on another layout than on the one
+/// that typed it, e.g. `"akuo"` that should have been `"שלום"`.
+///
+/// The layouts are compared as in [`infer_layout`]. A correction is returned
+/// only when a reading other than the text itself wins with a confidence of
+/// at least 80%; correctly typed or ambiguous text gives `None`.
+pub fn detect_mistyped_with<'a>(
+    text: &str,
+    layout_maps: &'a [LayoutMap],
+    models: &LanguageModels,
+) -> Option<Correction<'a>> {
+    let (source, guesses) = read_on_every_layout(text, layout_maps, models)?;
+    // Layouts that read the text the same way, e.g. US and German for most
+    // Latin words, back the same correction, so their confidences add up.
+    let mut readings: Vec<(LayoutGuess<'a>, f64)> = Vec::new();
+    for guess in guesses {
+        match readings
+            .iter_mut()
+            .find(|(best, _)| best.text == guess.text)
+        {
+            Some((best, total)) => {
+                *total += guess.confidence;
+                if guess.confidence > best.confidence {
+                    *best = guess;
+                }
+            }
+            None => {
+                let confidence = guess.confidence;
+                readings.push((guess, confidence));
+            }
+        }
+    }
+    let (guess, confidence) = readings
+        .into_iter()
+        .reduce(|best, next| if next.1 > best.1 { next } else { best })?;
+    if guess.text == text || confidence < MISTYPED_CONFIDENCE {
+        return None;
+    }
+    Some(Correction {
+        from: source,
+        to: guess.layout,
+        corrected: guess.text,
+        confidence,
+    })
+}
+
+/// One whitespace-separated word of a text, see [`mistyped_segments_with`].
+#[derive(Debug, Clone)]
+pub struct TextSegment<'a> {
+    /// Byte range of the word in the text.
+    pub range: Range<usize>,
+    /// How the word should have been typed, when it looks mistyped.
+    pub correction: Option<Correction<'a>>,
+}
+
+/// Splits `text` into words and checks each one on its own with
+/// [`detect_mistyped_with`], so that in `"meeting at 10 with שגןג"` only the
+/// last word is flagged.
+///
+/// Words are separated by whitespace only. Punctuation stays part of the
+/// word, because a key that types `,` on the layout in use may be the letter
+/// `ת` on the intended one; each layout is read both with the word's leading
+/// and trailing punctuation converted and with it kept, and the reading the
+/// language model prefers is used.
+pub fn mistyped_segments_with<'a>(
+    text: &str,
+    layout_maps: &'a [LayoutMap],
+    models: &LanguageModels,
+) -> Vec<TextSegment<'a>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let word = &rest[start..];
+        let len = word.find(char::is_whitespace).unwrap_or(word.len());
+        let offset = text.len() - rest.len() + start;
+        segments.push(TextSegment {
+            range: offset..offset + len,
+            correction: detect_mistyped_with(&word[..len], layout_maps, models),
+        });
+        rest = &word[len..];
+    }
+    segments
+}
+
+/// `text` with every word that looks mistyped replaced by its correction,
+/// using the bundled language models. See [`correct_mixed_text_with`].
+#[cfg(feature = "language-models")]
+pub fn correct_mixed_text(text: &str, layout_maps: &[LayoutMap]) -> String {
+    correct_mixed_text_with(text, layout_maps, LanguageModels::bundled())
+}
+
+/// `text` with every word that looks mistyped replaced by its correction.
+/// Correctly typed words and the whitespace between words are kept as they
+/// are. See [`mistyped_segments_with`].
+pub fn correct_mixed_text_with(
+    text: &str,
+    layout_maps: &[LayoutMap],
+    models: &LanguageModels,
+) -> String {
+    let mut corrected = String::with_capacity(text.len());
+    let mut copied = 0;
+    for segment in mistyped_segments_with(text, layout_maps, models) {
+        if let Some(correction) = segment.correction {
+            corrected.push_str(&text[copied..segment.range.start]);
+            corrected.push_str(&correction.corrected);
+            copied = segment.range.end;
+        }
+    }
+    corrected.push_str(&text[copied..]);
+    corrected
+}
+
+/// The layout that types `text`, and the text's reading on every layout with
+/// its confidence. The source layout comes first.
+fn read_on_every_layout<'a>(
+    text: &str,
+    layout_maps: &'a [LayoutMap],
+    models: &LanguageModels,
+) -> Option<(&'a LayoutMap, Vec<LayoutGuess<'a>>)> {
+    let source = rank_layouts(text, layout_maps)
+        .into_iter()
+        .next()
+        .filter(|best| best.matched > 0)?
+        .layout;
+    let targets = std::iter::once(source).chain(
+        layout_maps
+            .iter()
+            .filter(|target| !std::ptr::eq(source, *target)),
+    );
+    let readings: Vec<(&LayoutMap, String, f64)> = targets
+        .map(|target| {
+            let model = models.for_layout(&target.layout);
+            let (reading, score) = if std::ptr::eq(source, target) {
+                (text.to_string(), reading_log_likelihood(text, model))
+            } else {
+                alternative_readings(text, source, target)
+                    .into_iter()
+                    .map(|reading| {
+                        let score = reading_log_likelihood(&reading, model);
+                        (reading, score)
+                    })
+                    .reduce(|best, next| if next.1 > best.1 { next } else { best })
+                    .expect("there is always a whole-text reading")
+            };
+            (target, reading, score)
+        })
+        .collect();
+
+    let confidences = softmax(readings.iter().map(|(_, _, score)| *score));
+    let guesses = readings
+        .into_iter()
+        .zip(confidences)
+        .map(|((layout, text, _), confidence)| LayoutGuess {
+            layout,
+            text,
+            confidence,
+        })
+        .collect();
+    Some((source, guesses))
+}
+
+/// Ways to read `text` on `target`: every keystroke replayed, and, when the
+/// text starts or ends with punctuation, the same with the punctuation kept.
+/// A key that types `,` on one layout may type a letter on another, so
+/// whether a trailing `,` belongs to the word depends on the layout.
+fn alternative_readings(text: &str, source: &LayoutMap, target: &LayoutMap) -> Vec<String> {
+    let mut readings = vec![shift_text_language(text, source, target)];
+    let is_punctuation = |c: char| !c.is_alphanumeric();
+    let start = text.len() - text.trim_start_matches(is_punctuation).len();
+    let end = text.trim_end_matches(is_punctuation).len();
+    if start < end && (start > 0 || end < text.len()) {
+        readings.push(format!(
+            "{}{}{}",
+            &text[..start],
+            shift_text_language(&text[start..end], source, target),
+            &text[end..]
+        ));
+    }
+    readings
+}
+
+/// The most confident guess, the earliest one on ties.
+fn best_guess(guesses: Vec<LayoutGuess<'_>>) -> Option<LayoutGuess<'_>> {
+    guesses.into_iter().reduce(|best, guess| {
+        if guess.confidence > best.confidence {
+            guess
+        } else {
+            best
+        }
+    })
+}
+
+fn reading_log_likelihood(reading: &str, model: Option<&LanguageModel>) -> f64 {
+    match model {
+        Some(model) => model.log_likelihood(reading),
+        None => {
+            // Same length convention as the models: every character plus
+            // the word boundary after the text.
+            let chars = reading
+                .split_whitespace()
+                .map(|word| word.chars().count() + 1);
+            -(chars.sum::<usize>() as f64) * UNMODELLED_ALPHABET.ln()
+        }
+    }
+}
+
+/// Softmax of log likelihoods: the posterior of each reading when every
+/// layout is equally likely beforehand.
+fn softmax(scores: impl Iterator<Item = f64>) -> Vec<f64> {
+    let scores: Vec<f64> = scores.collect();
+    let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = scores.iter().map(|score| (score - max).exp()).collect();
+    let total: f64 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,256 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutId, ShiftLevel,
+};
+use std::collections::HashMap;
+
+const ENGLISH: &str = "hello how are you today, the weather is nice. we are going home at 10";
+const HEBREW: &str = "שלום מה שלומך היום, מזג האוויר יפה. ואנחנו הולכים הביתה את הספר, שלום";
+
+fn create_test_layout(
+    id: &str,
+    language: &str,
+    direction: KeyboardDirection,
+    pairs: &[(PhysicalKey, &str)],
+) -> LayoutMap {
+    let map: HashMap<KeyStroke, String> = pairs
+        .iter()
+        .map(|(key, output)| (KeyStroke::new(*key, ShiftLevel::Base), output.to_string()))
+        .collect();
+    LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new(id),
+            direction,
+            languages: vec![language.to_string()],
+            ..Default::default()
+        },
+        map,
+        ..Default::default()
+    }
+}
+
+fn create_layouts() -> Vec<LayoutMap> {
+    use PhysicalKey::*;
+    vec![
+        create_test_layout(
+            "us",
+            "eng",
+            KeyboardDirection::LTR,
+            &[
+                (KeyA, "a"),
+                (KeyE, "e"),
+                (KeyH, "h"),
+                (KeyK, "k"),
+                (KeyL, "l"),
+                (KeyO, "o"),
+                (KeyU, "u"),
+                (KeyT, "t"),
+                (KeyV, "v"),
+                (Semicolon, ";"),
+                (Comma, ","),
+                (Period, "."),
+                (Digit0, "0"),
+                (Digit1, "1"),
+            ],
+        ),
+        create_test_layout(
+            "il",
+            "heb",
+            KeyboardDirection::RTL,
+            &[
+                (KeyA, "ש"),
+                (KeyE, "ק"),
+                (KeyH, "י"),
+                (KeyK, "ל"),
+                (KeyL, "ך"),
+                (KeyO, "ם"),
+                (KeyU, "ו"),
+                (KeyT, "א"),
+                (KeyV, "ה"),
+                (Semicolon, "ף"),
+                (Comma, "ת"),
+                (Period, "ץ"),
+                (Digit0, "0"),
+                (Digit1, "1"),
+            ],
+        ),
+    ]
+}
+
+fn create_models() -> LanguageModels {
+    let mut models = LanguageModels::new();
+    models.insert(LanguageModel::train("en", ENGLISH));
+    models.insert(LanguageModel::train("he", HEBREW));
+    models
+}
+
+#[test]
+fn test_infer_layout_detects_hebrew_typed_on_english() {
+    let maps = create_layouts();
+    let guess = infer_layout("akuo", &maps, &create_models()).unwrap();
+    assert_eq!(guess.layout.layout.id, LayoutId::new("il"));
+    assert_eq!(guess.text, "שלום");
+    assert!(guess.confidence > 0.9, "{}", guess.confidence);
+}
+
+#[test]
+fn test_infer_layout_keeps_correct_text() {
+    let maps = create_layouts();
+    let guess = infer_layout("hello", &maps, &create_models()).unwrap();
+    assert_eq!(guess.layout.layout.id, LayoutId::new("us"));
+    assert_eq!(guess.text, "hello");
+    assert!(guess.confidence > 0.9, "{}", guess.confidence);
+}
+
+#[test]
+fn test_infer_layout_reads_hebrew_text_as_hebrew() {
+    let maps = create_layouts();
+    let guess = infer_layout("שלום", &maps, &create_models()).unwrap();
+    assert_eq!(guess.layout.layout.id, LayoutId::new("il"));
+    assert_eq!(guess.text, "שלום");
+}
+
+#[test]
+fn test_infer_layout_without_models_is_undecided() {
+    let maps = create_layouts();
+    let guess = infer_layout("akuo", &maps, &LanguageModels::new()).unwrap();
+    assert!((guess.confidence - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_infer_layout_no_typed_text() {
+    let maps = create_layouts();
+    assert!(infer_layout("xyz", &maps, &create_models()).is_none());
+    assert!(infer_layout("akuo", &[], &create_models()).is_none());
+}
+
+#[test]
+fn test_softmax_sums_to_one() {
+    let confidences = softmax([-10.0, -12.0, -30.0].into_iter());
+    assert!((confidences.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    assert!(confidences[0] > confidences[1] && confidences[1] > confidences[2]);
+}
+
+#[test]
+fn test_unmodelled_reading_counts_word_boundaries() {
+    let per_char = -UNMODELLED_ALPHABET.ln();
+    assert_eq!(reading_log_likelihood("", None), 0.0);
+    assert!((reading_log_likelihood("ab cd", None) - 6.0 * per_char).abs() < 1e-9);
+}
+
+#[cfg(feature = "language-models")]
+#[test]
+fn test_infer_layout_with_bundled_models() {
+    let maps = create_layouts();
+    let guess = infer_layout("akuo", &maps, LanguageModels::bundled()).unwrap();
+    assert_eq!(guess.text, "שלום");
+    assert!(guess.confidence > 0.9, "{}", guess.confidence);
+}
+
+#[test]
+fn test_infer_layout_prefers_source_on_ties() {
+    let maps = create_layouts();
+    let guess = infer_layout("hello", &maps, &LanguageModels::new()).unwrap();
+    assert_eq!(guess.layout.layout.id, LayoutId::new("us"));
+}
+
+#[test]
+fn test_detect_mistyped_suggests_correction() {
+    let maps = create_layouts();
+    let correction = detect_mistyped_with("akuo", &maps, &create_models()).unwrap();
+    assert_eq!(correction.from.layout.id, LayoutId::new("us"));
+    assert_eq!(correction.to.layout.id, LayoutId::new("il"));
+    assert_eq!(correction.corrected, "שלום");
+    assert!(correction.confidence >= MISTYPED_CONFIDENCE);
+}
+
+#[test]
+fn test_detect_mistyped_leaves_correct_text_alone() {
+    let maps = create_layouts();
+    let models = create_models();
+    assert!(detect_mistyped_with("hello", &maps, &models).is_none());
+    assert!(detect_mistyped_with("שלום", &maps, &models).is_none());
+}
+
+#[test]
+fn test_detect_mistyped_needs_evidence() {
+    let maps = create_layouts();
+    assert!(detect_mistyped_with("akuo", &maps, &LanguageModels::new()).is_none());
+    assert!(detect_mistyped_with("xyz", &maps, &create_models()).is_none());
+    assert!(detect_mistyped_with("", &maps, &create_models()).is_none());
+}
+
+#[test]
+fn test_detect_mistyped_keeps_trailing_punctuation() {
+    let maps = create_layouts();
+    let correction = detect_mistyped_with("akuo.", &maps, &create_models()).unwrap();
+    assert_eq!(correction.corrected, "שלום.");
+}
+
+#[test]
+fn test_detect_mistyped_converts_punctuation_that_is_a_letter() {
+    let maps = create_layouts();
+    let correction = detect_mistyped_with("t,", &maps, &create_models()).unwrap();
+    assert_eq!(correction.corrected, "את");
+}
+
+#[test]
+fn test_mistyped_segments_flag_only_mistyped_words() {
+    let maps = create_layouts();
+    let segments = mistyped_segments_with(" hello  akuo 10", &maps, &create_models());
+    let ranges: Vec<_> = segments
+        .iter()
+        .map(|segment| segment.range.clone())
+        .collect();
+    assert_eq!(ranges, [1..6, 8..12, 13..15]);
+    assert!(segments[0].correction.is_none());
+    assert_eq!(segments[1].correction.as_ref().unwrap().corrected, "שלום");
+    assert!(segments[2].correction.is_none());
+}
+
+#[test]
+fn test_correct_mixed_text_keeps_correct_words() {
+    let maps = create_layouts();
+    let models = create_models();
+    assert_eq!(
+        correct_mixed_text_with("hello akuo. at 10\n", &maps, &models),
+        "hello שלום. at 10\n"
+    );
+    assert_eq!(
+        correct_mixed_text_with("hello שלום", &maps, &models),
+        "hello שלום"
+    );
+    assert_eq!(correct_mixed_text_with("", &maps, &models), "");
+}
+
+#[cfg(feature = "language-models")]
+#[test]
+fn test_correct_mixed_text_with_bundled_models() {
+    let maps = create_layouts();
+    assert_eq!(correct_mixed_text("hello akuo", &maps), "hello שלום");
+}
+
+#[cfg(feature = "language-models")]
+#[test]
+fn test_detect_mistyped_with_bundled_models() {
+    let maps = create_layouts();
+    let correction = detect_mistyped("akuo", &maps).unwrap();
+    assert_eq!(correction.corrected, "שלום");
+    assert!(detect_mistyped("hello", &maps).is_none());
+}
+
+#[test]
+fn test_detect_mistyped_sums_layouts_with_same_reading() {
+    let mut maps = create_layouts();
+    let mut international = maps[0].clone();
+    international.layout.id = LayoutId::new("us(intl)");
+    maps.push(international);
+
+    let guess = infer_layout("יקךךם", &maps, &create_models()).unwrap();
+    assert!(guess.confidence < MISTYPED_CONFIDENCE);
+    let correction = detect_mistyped_with("יקךךם", &maps, &create_models()).unwrap();
+    assert_eq!(correction.from.layout.id, LayoutId::new("il"));
+    assert_eq!(correction.corrected, "hello");
+    assert!(correction.confidence >= MISTYPED_CONFIDENCE);
+}
@@ -0,0 +1,657 @@
+//! macOS keyboard layout files (`.keylayout`), as written by Ukelele, read
+//! on every platform.
+
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use super::physical_key::PhysicalKey;
+use super::types::{
+    DeadKey, KeyAction, KeyStroke, KeyboardLayout, LayoutId, LayoutMap, ShiftLevel,
+};
+use crate::error::LayoutError;
+
+const FORMAT: &str = "keylayout file";
+
+/// State of the dead-key state machine when no dead key is pending.
+const NO_STATE: &str = "none";
+
+/// How deep `baseMapSet`/`baseIndex` inheritance is followed before the
+/// file is treated as cyclic.
+const MAX_BASE_DEPTH: usize = 16;
+
+impl LayoutMap {
+    /// Map of the layout described by the `.keylayout` document `xml`.
+    ///
+    /// The key map set of the first `<layout>` element (the one for ANSI
+    /// hardware in Ukelele files) is used. Each [`ShiftLevel`] is looked up
+    /// in the `modifierMap` as the left Shift and Option keys and Caps Lock,
+    /// so AltGr is Option. Keys are matched to [`PhysicalKey`]s by their
+    /// macOS virtual key code, and key maps inherit from their
+    /// `baseMapSet`/`baseIndex`. Actions that enter a state become dead
+    /// keys, combining with every action that has an output in that state;
+    /// the state's terminator is the accent.
+    ///
+    /// The file names no language: the layout gets the keyboard's `name` as
+    /// its id and description, its `id` number as `native_id`, and a
+    /// direction from the characters it types.
+    pub fn from_keylayout(xml: &str) -> Result<LayoutMap, LayoutError> {
+        let raw: RawKeyboard = from_str(xml).map_err(|err| invalid(err.to_string()))?;
+        Keylayout::new(&raw)?.layout_map()
+    }
+
+    /// Like [`LayoutMap::from_keylayout`] for the file at `path`.
+    pub fn from_keylayout_file(path: impl AsRef<Path>) -> Result<LayoutMap, LayoutError> {
+        let path = path.as_ref();
+        let xml = fs::read_to_string(path).map_err(|err| LayoutError::FileAccess {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+        Self::from_keylayout(&xml)
+    }
+
+    /// `.keylayout` document of this layout, for
+    /// `~/Library/Keyboard Layouts` on macOS.
+    ///
+    /// Key maps 0 to 4 hold the Base, Shift, Caps Lock, Option and
+    /// Shift+Option levels, with the fallbacks of [`ShiftLevel::fallback`]
+    /// written out since macOS does not infer them. Each dead key enters a
+    /// state of its own, terminated by its accent, and keys typing a
+    /// character it combines with go through an action with an output in
+    /// that state. The keyboard is named after the description, and its `id`
+    /// is `native_id` when that is a number, otherwise a negative number
+    /// derived from the layout id as macOS expects of custom layouts.
+    pub fn to_keylayout(&self) -> String {
+        KeylayoutWriter::new(self).write()
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> LayoutError {
+    LayoutError::InvalidData {
+        format: FORMAT,
+        reason: reason.into(),
+    }
+}
+
+/// What a key does in the `none` state.
+enum Output {
+    Text(String),
+    /// Enters the named dead-key state.
+    Dead(String),
+}
+
+/// Modifiers held for a [`ShiftLevel`], always the left-hand keys.
+#[derive(Clone, Copy)]
+struct Pressed {
+    shift: bool,
+    option: bool,
+    caps: bool,
+}
+
+impl Pressed {
+    fn for_level(level: ShiftLevel) -> Pressed {
+        Pressed {
+            shift: matches!(level, ShiftLevel::Shift | ShiftLevel::ShiftAltGr),
+            option: matches!(level, ShiftLevel::AltGr | ShiftLevel::ShiftAltGr),
+            caps: level == ShiftLevel::Caps,
+        }
+    }
+
+    /// Whether the `keys` attribute of a `<modifier>` element matches: every
+    /// term without `?` must be held, and every held modifier must be named.
+    fn matches(self, keys: &str) -> bool {
+        // Shift, Option, Control, Command, Caps Lock.
+        let held = [self.shift, self.option, false, false, self.caps];
+        let mut named = [false; 5];
+        for term in keys.split_whitespace() {
+            let (name, optional) = match term.strip_suffix('?') {
+                Some(name) => (name, true),
+                None => (term, false),
+            };
+            let (class, left_side) = match name {
+                "shift" | "anyShift" => (0, true),
+                "rightShift" => (0, false),
+                "option" | "anyOption" => (1, true),
+                "rightOption" => (1, false),
+                "control" | "anyControl" => (2, true),
+                "rightControl" => (2, false),
+                "command" => (3, true),
+                "caps" => (4, true),
+                _ => continue,
+            };
+            if held[class] && left_side {
+                named[class] = true;
+            } else if !optional {
+                return false;
+            }
+        }
+        held.iter().zip(named).all(|(&held, named)| !held || named)
+    }
+}
+
+/// Lookup tables over a parsed `.keylayout` document.
+struct Keylayout<'a> {
+    raw: &'a RawKeyboard,
+    map_set: &'a str,
+    modifier_map: &'a RawModifierMap,
+}
+
+impl<'a> Keylayout<'a> {
+    fn new(raw: &'a RawKeyboard) -> Result<Self, LayoutError> {
+        let layout = raw
+            .layouts
+            .layouts
+            .iter()
+            .find(|layout| layout.first == 0)
+            .or_else(|| raw.layouts.layouts.first())
+            .ok_or_else(|| invalid("no <layout> element"))?;
+        let modifier_map = raw
+            .modifier_maps
+            .iter()
+            .find(|map| map.id == layout.modifiers)
+            .ok_or_else(|| invalid(format!("no modifierMap `{}`", layout.modifiers)))?;
+        if !raw.key_map_sets.iter().any(|set| set.id == layout.map_set) {
+            return Err(invalid(format!("no keyMapSet `{}`", layout.map_set)));
+        }
+        Ok(Keylayout {
+            raw,
+            map_set: &layout.map_set,
+            modifier_map,
+        })
+    }
+
+    fn layout_map(&self) -> Result<LayoutMap, LayoutError> {
+        let mut layout_map = LayoutMap {
+            layout: KeyboardLayout {
+                id: LayoutId::new(&self.raw.name),
+                lang_name: self.raw.name.clone(),
+                description: self.raw.name.clone(),
+                native_id: self.raw.id.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for level in ShiftLevel::ALL {
+            let index = self.key_map_index(Pressed::for_level(level));
+            for key in PhysicalKey::ALL {
+                let Some(raw_key) = self.key(self.map_set, index, key.mac_key_code(), 0)? else {
+                    continue;
+                };
+                let stroke = KeyStroke::new(key, level);
+                match self.output(raw_key) {
+                    Some(Output::Text(text)) if !text.is_empty() => {
+                        layout_map.insert_level(stroke, text)
+                    }
+                    Some(Output::Dead(state)) => {
+                        layout_map.insert_dead_key(stroke, self.dead_key(&state))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        layout_map.layout.direction = layout_map.base_direction();
+        Ok(layout_map)
+    }
+
+    /// Index of the key map the modifier map selects for `pressed`: the
+    /// first `keyMapSelect` with a matching `modifier`, or the default.
+    fn key_map_index(&self, pressed: Pressed) -> usize {
+        self.modifier_map
+            .selects
+            .iter()
+            .find(|select| {
+                select
+                    .modifiers
+                    .iter()
+                    .any(|modifier| pressed.matches(&modifier.keys))
+            })
+            .map_or(self.modifier_map.default_index, |select| select.map_index)
+    }
+
+    /// The `<key>` for `code` in key map `index` of `map_set`, following the
+    /// key map's base when it does not define the key itself.
+    fn key(
+        &self,
+        map_set: &str,
+        index: usize,
+        code: u16,
+        depth: usize,
+    ) -> Result<Option<&'a RawKey>, LayoutError> {
+        if depth > MAX_BASE_DEPTH {
+            return Err(invalid("keyMap bases form a cycle"));
+        }
+        let Some(key_map) = self
+            .raw
+            .key_map_sets
+            .iter()
+            .find(|set| set.id == map_set)
+            .and_then(|set| set.key_maps.iter().find(|map| map.index == index))
+        else {
+            return Ok(None);
+        };
+        if let Some(key) = key_map.keys.iter().find(|key| key.code == code) {
+            return Ok(Some(key));
+        }
+        match key_map.base_index {
+            Some(base_index) => {
+                let base_set = key_map.base_map_set.as_deref().unwrap_or(map_set);
+                self.key(base_set, base_index, code, depth + 1)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn action(&self, id: &str) -> Option<&'a RawAction> {
+        self.raw
+            .actions
+            .actions
+            .iter()
+            .find(|action| action.id == id)
+    }
+
+    fn output(&self, key: &RawKey) -> Option<Output> {
+        if let Some(output) = &key.output {
+            return Some(Output::Text(output.clone()));
+        }
+        let when = self
+            .action(key.action.as_deref()?)?
+            .whens
+            .iter()
+            .find(|when| when.state == NO_STATE)?;
+        match (&when.output, &when.next) {
+            (Some(output), _) => Some(Output::Text(output.clone())),
+            (None, Some(next)) => Some(Output::Dead(next.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// The dead key that enters `state`: every action with a single
+    /// character in the `none` state and an output in `state` combines.
+    fn dead_key(&self, state: &str) -> DeadKey {
+        let accent = self
+            .raw
+            .terminators
+            .whens
+            .iter()
+            .find(|when| when.state == state)
+            .and_then(|when| when.output.clone())
+            .unwrap_or_default();
+        let mut combinations = BTreeMap::new();
+        for action in &self.raw.actions.actions {
+            let output_in = |wanted: &str| {
+                action
+                    .whens
+                    .iter()
+                    .find(|when| when.state == wanted)
+                    .and_then(|when| when.output.as_deref())
+            };
+            let (Some(base), Some(composed)) = (output_in(NO_STATE), output_in(state)) else {
+                continue;
+            };
+            let mut chars = base.chars();
+            if let (Some(base), None) = (chars.next(), chars.next()) {
+                combinations.insert(base, composed.to_string());
+            }
+        }
+        DeadKey {
+            accent,
+            combinations,
+        }
+    }
+}
+
+/// Modifiers selecting each key map [`LayoutMap::to_keylayout`] writes, by
+/// index, and the level the map holds.
+const KEY_MAPS: [(&str, ShiftLevel); 5] = [
+    ("command?", ShiftLevel::Base),
+    ("anyShift caps? command?", ShiftLevel::Shift),
+    ("caps", ShiftLevel::Caps),
+    ("anyOption caps?", ShiftLevel::AltGr),
+    ("anyShift anyOption caps?", ShiftLevel::ShiftAltGr),
+];
+
+/// Hardware keyboard types, as `first`/`last` ranges of `<layout>`
+/// elements, that Ukelele maps to a layout's key map set.
+const KEYBOARD_TYPES: [(u16, u16); 8] = [
+    (0, 17),
+    (18, 18),
+    (21, 23),
+    (30, 30),
+    (194, 194),
+    (197, 197),
+    (200, 201),
+    (206, 207),
+];
+
+/// `text` escaped for an XML attribute. Control characters are written as
+/// character references, which XML 1.1 allows.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c if c.is_control() => escaped.push_str(&format!("&#x{:04X};", u32::from(c))),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `id` of the `<keyboard>`: `native_id` when it is a number, otherwise a
+/// negative number, as custom layouts use, hashed from the layout id.
+fn keyboard_id(layout: &KeyboardLayout) -> i32 {
+    if let Ok(id) = layout.native_id.parse() {
+        return id;
+    }
+    let hash = layout.id.as_str().bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte.into())
+    });
+    -1 - (hash % 32_000) as i32
+}
+
+/// Writes a [`LayoutMap`] as a `.keylayout` document.
+struct KeylayoutWriter<'a> {
+    layout_map: &'a LayoutMap,
+    /// Distinct dead keys; the one at index `i` enters state `dead{i + 1}`.
+    dead_keys: Vec<&'a DeadKey>,
+    /// Characters some dead key combines with.
+    bases: BTreeSet<char>,
+    out: String,
+}
+
+impl<'a> KeylayoutWriter<'a> {
+    fn new(layout_map: &'a LayoutMap) -> Self {
+        let mut strokes: Vec<_> = layout_map.dead_keys.iter().collect();
+        strokes.sort_by_key(|(stroke, _)| **stroke);
+        let mut dead_keys: Vec<&DeadKey> = Vec::new();
+        for (_, dead_key) in strokes {
+            if !dead_keys.contains(&dead_key) {
+                dead_keys.push(dead_key);
+            }
+        }
+        let bases = dead_keys
+            .iter()
+            .flat_map(|dead_key| dead_key.combinations.keys().copied())
+            .collect();
+        KeylayoutWriter {
+            layout_map,
+            dead_keys,
+            bases,
+            out: String::new(),
+        }
+    }
+
+    fn state(&self, dead_key: &DeadKey) -> String {
+        let index = self.dead_keys.iter().position(|known| *known == dead_key);
+        format!("dead{}", index.unwrap_or_default() + 1)
+    }
+
+    fn write(mut self) -> String {
+        let layout = &self.layout_map.layout;
+        let name = if layout.description.is_empty() {
+            layout.id.as_str()
+        } else {
+            &layout.description
+        };
+        self.line(0, r#"<?xml version="1.1" encoding="UTF-8"?>"#);
+        self.line(
+            0,
+            r#"<!DOCTYPE keyboard SYSTEM "file://localhost/System/Library/DTDs/KeyboardLayout.dtd">"#,
+        );
+        self.line(
+            0,
+            &format!(
+                r#"<keyboard group="126" id="{}" name="{}" maxout="{}">"#,
+                keyboard_id(layout),
+                escape(name),
+                self.max_output()
+            ),
+        );
+        self.line(1, "<layouts>");
+        for (first, last) in KEYBOARD_TYPES {
+            self.line(
+                2,
+                &format!(
+                    r#"<layout first="{first}" last="{last}" mapSet="ANSI" modifiers="Modifiers"/>"#
+                ),
+            );
+        }
+        self.line(1, "</layouts>");
+        self.line(1, r#"<modifierMap id="Modifiers" defaultIndex="0">"#);
+        for (index, (keys, _)) in KEY_MAPS.iter().enumerate() {
+            self.line(2, &format!(r#"<keyMapSelect mapIndex="{index}">"#));
+            self.line(3, &format!(r#"<modifier keys="{keys}"/>"#));
+            self.line(2, "</keyMapSelect>");
+        }
+        self.line(1, "</modifierMap>");
+        self.line(1, r#"<keyMapSet id="ANSI">"#);
+        let mut keys: Vec<PhysicalKey> = self.layout_map.keys().into_iter().collect();
+        keys.sort_by_key(|key| key.mac_key_code());
+        for (index, (_, level)) in KEY_MAPS.iter().enumerate() {
+            self.line(2, &format!(r#"<keyMap index="{index}">"#));
+            for &key in &keys {
+                if let Some(attribute) = self.key_attribute(KeyStroke::new(key, *level)) {
+                    self.line(
+                        3,
+                        &format!(r#"<key code="{}" {attribute}/>"#, key.mac_key_code()),
+                    );
+                }
+            }
+            self.line(2, "</keyMap>");
+        }
+        self.line(1, "</keyMapSet>");
+        self.write_actions();
+        self.line(0, "</keyboard>");
+        self.out
+    }
+
+    /// The `output` or `action` attribute of the key for `stroke`.
+    fn key_attribute(&self, stroke: KeyStroke) -> Option<String> {
+        match self.layout_map.action(stroke)? {
+            KeyAction::Text("") => None,
+            KeyAction::Text(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if self.bases.contains(&c) => {
+                        Some(format!(r#"action="{}""#, escape(text)))
+                    }
+                    _ => Some(format!(r#"output="{}""#, escape(text))),
+                }
+            }
+            KeyAction::Dead(dead_key) => Some(format!(r#"action="{}""#, self.state(dead_key))),
+        }
+    }
+
+    fn write_actions(&mut self) {
+        if self.dead_keys.is_empty() {
+            return;
+        }
+        self.line(1, "<actions>");
+        for index in 0..self.dead_keys.len() {
+            let state = format!("dead{}", index + 1);
+            self.line(2, &format!(r#"<action id="{state}">"#));
+            self.line(3, &format!(r#"<when state="{NO_STATE}" next="{state}"/>"#));
+            self.line(2, "</action>");
+        }
+        for base in self.bases.clone() {
+            let id = escape(&base.to_string());
+            self.line(2, &format!(r#"<action id="{id}">"#));
+            self.line(3, &format!(r#"<when state="{NO_STATE}" output="{id}"/>"#));
+            for (index, dead_key) in self.dead_keys.clone().into_iter().enumerate() {
+                if let Some(composed) = dead_key.combinations.get(&base) {
+                    self.line(
+                        3,
+                        &format!(
+                            r#"<when state="dead{}" output="{}"/>"#,
+                            index + 1,
+                            escape(composed)
+                        ),
+                    );
+                }
+            }
+            self.line(2, "</action>");
+        }
+        self.line(1, "</actions>");
+        self.line(1, "<terminators>");
+        for (index, dead_key) in self.dead_keys.clone().into_iter().enumerate() {
+            self.line(
+                2,
+                &format!(
+                    r#"<when state="dead{}" output="{}"/>"#,
+                    index + 1,
+                    escape(&dead_key.accent)
+                ),
+            );
+        }
+        self.line(1, "</terminators>");
+    }
+
+    /// Longest output in UTF-16 units, as `maxout` declares it.
+    fn max_output(&self) -> usize {
+        let dead_outputs = self.dead_keys.iter().flat_map(|dead_key| {
+            std::iter::once(&dead_key.accent).chain(dead_key.combinations.values())
+        });
+        self.layout_map
+            .map
+            .values()
+            .chain(dead_outputs)
+            .map(|output| output.encode_utf16().count())
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push('\t');
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyboard {
+    #[serde(rename = "@name", default)]
+    name: String,
+    #[serde(rename = "@id", default)]
+    id: String,
+    #[serde(default)]
+    layouts: RawLayouts,
+    #[serde(rename = "modifierMap", default)]
+    modifier_maps: Vec<RawModifierMap>,
+    #[serde(rename = "keyMapSet", default)]
+    key_map_sets: Vec<RawKeyMapSet>,
+    #[serde(default)]
+    actions: RawActions,
+    #[serde(default)]
+    terminators: RawTerminators,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawLayouts {
+    #[serde(rename = "layout", default)]
+    layouts: Vec<RawLayout>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLayout {
+    #[serde(rename = "@first", default)]
+    first: u32,
+    #[serde(rename = "@modifiers")]
+    modifiers: String,
+    #[serde(rename = "@mapSet")]
+    map_set: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawModifierMap {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@defaultIndex", default)]
+    default_index: usize,
+    #[serde(rename = "keyMapSelect", default)]
+    selects: Vec<RawKeyMapSelect>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyMapSelect {
+    #[serde(rename = "@mapIndex")]
+    map_index: usize,
+    #[serde(rename = "modifier", default)]
+    modifiers: Vec<RawModifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawModifier {
+    #[serde(rename = "@keys", default)]
+    keys: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyMapSet {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "keyMap", default)]
+    key_maps: Vec<RawKeyMap>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKeyMap {
+    #[serde(rename = "@index")]
+    index: usize,
+    #[serde(rename = "@baseMapSet", default)]
+    base_map_set: Option<String>,
+    #[serde(rename = "@baseIndex", default)]
+    base_index: Option<usize>,
+    #[serde(rename = "key", default)]
+    keys: Vec<RawKey>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawKey {
+    #[serde(rename = "@code")]
+    code: u16,
+    #[serde(rename = "@output", default)]
+    output: Option<String>,
+    #[serde(rename = "@action", default)]
+    action: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawActions {
+    #[serde(rename = "action", default)]
+    actions: Vec<RawAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAction {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "when", default)]
+    whens: Vec<RawWhen>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawTerminators {
+    #[serde(rename = "when", default)]
+    whens: Vec<RawWhen>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawWhen {
+    #[serde(rename = "@state")]
+    state: String,
+    #[serde(rename = "@output", default)]
+    output: Option<String>,
+    #[serde(rename = "@next", default)]
+    next: Option<String>,
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,304 @@
+use super::*;
+use crate::keyboard_mapping::temp_path;
+use crate::keyboard_mapping::types::KeyboardDirection;
+
+const SAMPLE: &str = r#"<?xml version="1.1" encoding="UTF-8"?>
+<!DOCTYPE keyboard SYSTEM "file://localhost/System/Library/DTDs/KeyboardLayout.dtd">
+<!--Last edited by Ukelele version 3.5.7 on 2024-05-02 at 10:12 (CEST)-->
+<keyboard group="126" id="-4242" name="Test Layout" maxout="1">
+    <layouts>
+        <layout first="0" last="17" modifiers="Modifiers" mapSet="ANSI"/>
+        <layout first="18" last="18" modifiers="Modifiers" mapSet="JIS"/>
+    </layouts>
+    <modifierMap id="Modifiers" defaultIndex="0">
+        <keyMapSelect mapIndex="0">
+            <modifier keys=""/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="1">
+            <modifier keys="anyShift caps?"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="2">
+            <modifier keys="caps"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="3">
+            <modifier keys="anyOption"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="4">
+            <modifier keys="rightShift rightOption"/>
+            <modifier keys="anyShift anyOption caps?"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="5">
+            <modifier keys="command anyShift? anyOption? caps?"/>
+        </keyMapSelect>
+    </modifierMap>
+    <keyMapSet id="ANSI">
+        <keyMap index="0">
+            <key code="0" output="a"/>
+            <key code="1" output="s"/>
+            <key code="12" output="q"/>
+            <key code="14" action="e"/>
+            <key code="18" output="1"/>
+            <key code="36" output="&#x000D;"/>
+            <key code="39" output="&#x0027;"/>
+            <key code="42" output="&lt;"/>
+            <key code="49" output=" "/>
+            <key code="127" output="x"/>
+        </keyMap>
+        <keyMap index="1">
+            <key code="0" output="A"/>
+            <key code="1" output="S"/>
+            <key code="12" output="Q"/>
+            <key code="14" action="E"/>
+            <key code="18" output="!"/>
+            <key code="39" output="&quot;"/>
+        </keyMap>
+        <keyMap index="2" baseMapSet="ANSI" baseIndex="1">
+            <key code="18" output="1"/>
+        </keyMap>
+        <keyMap index="3">
+            <key code="0" output="å"/>
+            <key code="12" output="œ"/>
+            <key code="14" action="acute"/>
+        </keyMap>
+        <keyMap index="4">
+            <key code="0" output="Å"/>
+        </keyMap>
+        <keyMap index="5" baseIndex="0"/>
+    </keyMapSet>
+    <keyMapSet id="JIS">
+        <keyMap index="0" baseMapSet="ANSI" baseIndex="0"/>
+    </keyMapSet>
+    <actions>
+        <action id="acute">
+            <when state="none" next="acute"/>
+        </action>
+        <action id="e">
+            <when state="none" output="e"/>
+            <when state="acute" output="é"/>
+        </action>
+        <action id="E">
+            <when state="none" output="E"/>
+            <when state="acute" output="É"/>
+        </action>
+    </actions>
+    <terminators>
+        <when state="acute" output="´"/>
+    </terminators>
+</keyboard>
+"#;
+
+const HEBREW: &str = r#"<?xml version="1.1" encoding="UTF-8"?>
+<keyboard group="126" id="-1" name="Hebrew Test">
+    <layouts>
+        <layout first="0" last="0" modifiers="m" mapSet="s"/>
+    </layouts>
+    <modifierMap id="m" defaultIndex="0">
+        <keyMapSelect mapIndex="0">
+            <modifier keys=""/>
+        </keyMapSelect>
+    </modifierMap>
+    <keyMapSet id="s">
+        <keyMap index="0">
+            <key code="0" output="ש"/>
+        </keyMap>
+    </keyMapSet>
+</keyboard>
+"#;
+
+fn output(map: &LayoutMap, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+    map.get(key, level)
+}
+
+#[test]
+fn test_from_keylayout_levels() {
+    let map = LayoutMap::from_keylayout(SAMPLE).unwrap();
+    assert_eq!(output(&map, PhysicalKey::KeyA, ShiftLevel::Base), Some("a"));
+    assert_eq!(
+        output(&map, PhysicalKey::KeyA, ShiftLevel::Shift),
+        Some("A")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyA, ShiftLevel::AltGr),
+        Some("å")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyA, ShiftLevel::ShiftAltGr),
+        Some("Å")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyQ, ShiftLevel::AltGr),
+        Some("œ")
+    );
+    assert_eq!(output(&map, PhysicalKey::KeyE, ShiftLevel::Base), Some("e"));
+    assert_eq!(
+        output(&map, PhysicalKey::Space, ShiftLevel::Base),
+        Some(" ")
+    );
+}
+
+#[test]
+fn test_from_keylayout_decodes_entities() {
+    let map = LayoutMap::from_keylayout(SAMPLE).unwrap();
+    assert_eq!(
+        output(&map, PhysicalKey::Quote, ShiftLevel::Base),
+        Some("'")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Quote, ShiftLevel::Shift),
+        Some("\"")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Backslash, ShiftLevel::Base),
+        Some("<")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Enter, ShiftLevel::Base),
+        Some("\r")
+    );
+}
+
+#[test]
+fn test_from_keylayout_caps_inherits_base_map() {
+    let map = LayoutMap::from_keylayout(SAMPLE).unwrap();
+    assert_eq!(output(&map, PhysicalKey::KeyS, ShiftLevel::Caps), Some("S"));
+    // Same as the unshifted key, so not stored as a separate level.
+    assert_eq!(output(&map, PhysicalKey::Digit1, ShiftLevel::Caps), None);
+}
+
+#[test]
+fn test_from_keylayout_dead_keys() {
+    let map = LayoutMap::from_keylayout(SAMPLE).unwrap();
+    let acute = &map.dead_keys[&KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::AltGr)];
+    assert_eq!(acute.accent, "´");
+    assert_eq!(acute.combinations[&'e'], "é");
+    assert_eq!(acute.combinations[&'E'], "É");
+    let typed = map.type_strokes(&[
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::AltGr),
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift),
+    ]);
+    assert_eq!(typed.as_deref(), Some("É"));
+}
+
+#[test]
+fn test_from_keylayout_metadata() {
+    let layout = LayoutMap::from_keylayout(SAMPLE).unwrap().layout;
+    assert_eq!(layout.id, LayoutId::new("Test Layout"));
+    assert_eq!(layout.description, "Test Layout");
+    assert_eq!(layout.native_id, "-4242");
+    assert_eq!(layout.direction, KeyboardDirection::LTR);
+
+    let hebrew = LayoutMap::from_keylayout(HEBREW).unwrap();
+    assert_eq!(hebrew.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(
+        output(&hebrew, PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+}
+
+#[test]
+fn test_modifier_matching() {
+    let shift = Pressed::for_level(ShiftLevel::Shift);
+    assert!(shift.matches("anyShift caps?"));
+    assert!(shift.matches("shift"));
+    assert!(!shift.matches(""));
+    assert!(!shift.matches("rightShift"));
+    assert!(!shift.matches("anyShift anyOption"));
+    assert!(Pressed::for_level(ShiftLevel::Base).matches("anyShift? caps?"));
+    assert!(!Pressed::for_level(ShiftLevel::Caps).matches("anyShift caps?"));
+    assert!(Pressed::for_level(ShiftLevel::ShiftAltGr).matches("anyShift anyOption"));
+}
+
+#[test]
+fn test_from_keylayout_file() {
+    let path = temp_path("test.keylayout");
+    fs::write(&path, HEBREW).unwrap();
+    let map = LayoutMap::from_keylayout_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(map.unwrap().layout.id, LayoutId::new("Hebrew Test"));
+    assert!(matches!(
+        LayoutMap::from_keylayout_file(&path),
+        Err(LayoutError::FileAccess { .. })
+    ));
+}
+
+#[test]
+fn test_from_keylayout_errors() {
+    assert!(matches!(
+        LayoutMap::from_keylayout("<keyboard"),
+        Err(LayoutError::InvalidData { .. })
+    ));
+    let missing_map = HEBREW.replace("modifiers=\"m\"", "modifiers=\"other\"");
+    assert_eq!(
+        LayoutMap::from_keylayout(&missing_map)
+            .unwrap_err()
+            .to_string(),
+        "invalid keylayout file: no modifierMap `other`"
+    );
+    let cyclic = HEBREW.replace(
+        "<keyMap index=\"0\">",
+        "<keyMap index=\"0\" baseIndex=\"0\">",
+    );
+    assert!(LayoutMap::from_keylayout(&cyclic).is_err());
+}
+
+#[test]
+fn test_to_keylayout_round_trip() {
+    for xml in [SAMPLE, HEBREW] {
+        let map = LayoutMap::from_keylayout(xml).unwrap();
+        assert_eq!(LayoutMap::from_keylayout(&map.to_keylayout()).unwrap(), map);
+    }
+}
+
+#[test]
+fn test_to_keylayout_writes_space_once() {
+    let mut map = LayoutMap::default();
+    map.layout.id = LayoutId::new("custom");
+    for (key, output) in [(PhysicalKey::KeyE, "e"), (PhysicalKey::Space, " ")] {
+        map.insert_level(KeyStroke::new(key, ShiftLevel::Base), output.to_string());
+    }
+    map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base),
+        DeadKey {
+            accent: "´".to_string(),
+            combinations: BTreeMap::from([(' ', "´".to_string()), ('e', "é".to_string())]),
+        },
+    );
+    let xml = map.to_keylayout();
+    assert_eq!(
+        xml.matches(r#"<when state="dead1" output="´"/>"#).count(),
+        2
+    );
+    assert_eq!(xml.matches(r#"<action id=" ">"#).count(), 1);
+    let read = LayoutMap::from_keylayout(&xml).unwrap();
+    assert_eq!((read.map, read.dead_keys), (map.map, map.dead_keys));
+}
+
+#[test]
+fn test_to_keylayout_document() {
+    let map = LayoutMap::from_keylayout(SAMPLE).unwrap();
+    let xml = map.to_keylayout();
+    assert!(xml.contains(r#"<keyboard group="126" id="-4242" name="Test Layout" maxout="1">"#));
+    assert!(xml.contains(r#"<key code="14" action="e"/>"#));
+    assert!(xml.contains(r#"<key code="39" output="&quot;"/>"#));
+    assert!(xml.contains(r#"<key code="36" output="&#x000D;"/>"#));
+    assert!(xml.contains(r#"<when state="dead1" output="´"/>"#));
+    // Fallback levels are spelled out: Option+S types `s`.
+    let option_map = &xml[xml.find(r#"<keyMap index="3">"#).unwrap()..];
+    assert!(option_map.contains(r#"<key code="1" output="s"/>"#));
+
+    let mut map = map;
+    map.layout.native_id = "not a number".to_string();
+    let id = keyboard_id(&map.layout);
+    assert!(id < 0);
+    assert!(
+        map.to_keylayout()
+            .contains(&format!(r#"<keyboard group="126" id="{id}""#))
+    );
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(escape("a<b>&\"c\""), "a&lt;b&gt;&amp;&quot;c&quot;");
+    assert_eq!(escape("\t"), "&#x0009;");
+    assert_eq!(escape("ש"), "ש");
+}
@@ -0,0 +1,754 @@
+//! Windows keyboard layout source files (`.klc`), as written by the
+//! Microsoft Keyboard Layout Creator, read on every platform.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use super::physical_key::PhysicalKey;
+use super::types::{DeadKey, KeyStroke, KeyboardLayout, LayoutId, LayoutMap, ShiftLevel};
+use crate::error::LayoutError;
+
+const FORMAT: &str = "KLC file";
+
+impl LayoutMap {
+    /// Map of the layout described by the KLC source `klc`.
+    ///
+    /// Every column of the `LAYOUT` table whose `SHIFTSTATE` is plain,
+    /// Shift, AltGr (Ctrl+Alt) or Shift+AltGr becomes a [`ShiftLevel`]; Ctrl
+    /// columns are skipped. Caps Lock follows the `Cap` column, including
+    /// `SGCap` rows. `LIGATURE` entries give multi-character outputs and
+    /// `DEADKEY` sections the combinations of dead keys, whose accent is the
+    /// output for Space. The layout is identified by the `KBD` name and
+    /// described by `LOCALENAME`, `LOCALEID` and `LANGUAGENAMES`.
+    pub fn from_klc(klc: &str) -> Result<LayoutMap, LayoutError> {
+        Ok(KlcFile::parse(klc)?.into_layout_map())
+    }
+
+    /// Like [`LayoutMap::from_klc`] for the raw bytes of a `.klc` file.
+    /// MSKLC saves UTF-16 with a byte order mark; UTF-8 is accepted too.
+    pub fn from_klc_bytes(bytes: &[u8]) -> Result<LayoutMap, LayoutError> {
+        Self::from_klc(&decode(bytes)?)
+    }
+
+    /// Like [`LayoutMap::from_klc_bytes`] for the file at `path`.
+    pub fn from_klc_file(path: impl AsRef<Path>) -> Result<LayoutMap, LayoutError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| LayoutError::FileAccess {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+        Self::from_klc_bytes(&bytes)
+    }
+
+    /// KLC source of this layout, which MSKLC can open and build.
+    ///
+    /// The Base, Shift, AltGr and Shift+AltGr levels become `LAYOUT`
+    /// columns next to an empty Ctrl column, and the Caps level becomes the
+    /// `Cap` value, or an `SGCap` row when Caps Lock types something other
+    /// than Shift. Longer outputs go to `LIGATURE`, and each dead key gets a
+    /// `DEADKEY` section named after its accent, with the accent as its
+    /// combination with Space. Keys without a place in a Windows layout, like
+    /// most of the keypad, are left out, and so is `IntlYen` next to a
+    /// `Backslash`, as both are `OEM_5`. The `KBD` name is the layout id cut
+    /// to the eight letters and digits Windows allows, and quotes are dropped
+    /// from the description.
+    pub fn to_klc(&self) -> String {
+        KlcWriter::new(self).write()
+    }
+
+    /// [`LayoutMap::to_klc`] as MSKLC saves it: UTF-16 with a byte order
+    /// mark and CRLF line ends.
+    pub fn to_klc_bytes(&self) -> Vec<u8> {
+        let text = self.to_klc().replace('\n', "\r\n");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+}
+
+/// Text of a `.klc` file: UTF-16 by its byte order mark, or by the zero
+/// high bytes of ASCII text when the mark is missing, otherwise UTF-8.
+fn decode(bytes: &[u8]) -> Result<String, LayoutError> {
+    let utf16 = |bytes: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| unit([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|err| invalid(err.to_string()))
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|err| invalid(err.to_string()))
+        }
+        [_, 0, ..] => utf16(bytes, u16::from_le_bytes),
+        _ => String::from_utf8(bytes.to_vec()).map_err(|err| invalid(err.to_string())),
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> LayoutError {
+    LayoutError::InvalidData {
+        format: FORMAT,
+        reason: reason.into(),
+    }
+}
+
+/// One character cell of the `LAYOUT` table.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    /// `-1`: the key types nothing in this column.
+    Empty,
+    Char(char),
+    /// A character followed by `@`: a dead key, looked up in `DEADKEY`.
+    Dead(char),
+    /// `%%`: the output is in the `LIGATURE` table.
+    Ligature,
+}
+
+/// A row of the `LAYOUT` table.
+struct Row {
+    /// `None` for scan codes without a [`PhysicalKey`].
+    key: Option<PhysicalKey>,
+    vk: String,
+    /// Caps Lock acts like Shift on this key.
+    caps_lock: bool,
+    cells: Vec<Cell>,
+    /// Outputs under Caps Lock of an `SGCap` row, from the line after it.
+    sgcaps: Option<Vec<Cell>>,
+}
+
+#[derive(Default)]
+struct KlcFile {
+    name: String,
+    description: String,
+    locale_name: Option<String>,
+    locale_id: Option<String>,
+    language_name: Option<String>,
+    /// Modifier bits of each table column: 1 Shift, 2 Ctrl, 4 Alt.
+    shift_states: Vec<u8>,
+    rows: Vec<Row>,
+    /// Ligatures by virtual key and column.
+    ligatures: HashMap<(String, usize), String>,
+    dead_keys: HashMap<char, BTreeMap<char, String>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    ShiftState,
+    Layout,
+    Ligature,
+    DeadKey(char),
+    LanguageNames,
+    Other,
+}
+
+impl KlcFile {
+    fn parse(klc: &str) -> Result<KlcFile, LayoutError> {
+        let mut file = KlcFile::default();
+        let mut section = Section::Header;
+        for (index, line) in klc.lines().enumerate() {
+            let at_line = |reason: String| invalid(format!("line {}: {reason}", index + 1));
+            let line = strip_comment(line).trim();
+            let mut tokens = line.split_whitespace();
+            let Some(first) = tokens.next() else {
+                continue;
+            };
+            let rest = line[first.len()..].trim();
+            match first {
+                "KBD" => {
+                    let (name, description) =
+                        rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    file.name = unquote(name).to_string();
+                    file.description = unquote(description.trim()).to_string();
+                    section = Section::Header;
+                }
+                "LOCALENAME" => file.locale_name = Some(unquote(rest).to_string()),
+                "LOCALEID" => file.locale_id = Some(unquote(rest).to_uppercase()),
+                "SHIFTSTATE" => section = Section::ShiftState,
+                "LAYOUT" => section = Section::Layout,
+                "LIGATURE" => section = Section::Ligature,
+                "DEADKEY" => {
+                    let dead = parse_char(unquote(rest)).map_err(at_line)?;
+                    file.dead_keys.entry(dead).or_default();
+                    section = Section::DeadKey(dead);
+                }
+                "LANGUAGENAMES" => section = Section::LanguageNames,
+                "COPYRIGHT" | "COMPANY" | "VERSION" | "ATTRIBUTES" | "KEYNAME" | "KEYNAME_EXT"
+                | "KEYNAME_DEAD" | "DESCRIPTIONS" => section = Section::Other,
+                "ENDKBD" => break,
+                _ => match section {
+                    Section::ShiftState => {
+                        let state = first
+                            .parse()
+                            .map_err(|_| at_line(format!("invalid shift state `{first}`")))?;
+                        file.shift_states.push(state);
+                    }
+                    Section::Layout => file.parse_layout_row(line).map_err(at_line)?,
+                    Section::Ligature => {
+                        let column = tokens
+                            .next()
+                            .and_then(|column| column.parse().ok())
+                            .ok_or_else(|| at_line("invalid ligature column".to_string()))?;
+                        let output = tokens
+                            .map(parse_char)
+                            .collect::<Result<String, _>>()
+                            .map_err(at_line)?;
+                        file.ligatures.insert((first.to_string(), column), output);
+                    }
+                    Section::DeadKey(dead) => {
+                        let base = parse_char(first).map_err(at_line)?;
+                        let composed = tokens
+                            .next()
+                            .ok_or_else(|| at_line("missing composed character".to_string()))?;
+                        let composed =
+                            parse_char(composed.trim_end_matches('@')).map_err(at_line)?;
+                        file.dead_keys
+                            .entry(dead)
+                            .or_default()
+                            .insert(base, composed.to_string());
+                    }
+                    Section::LanguageNames => {
+                        if file.language_name.is_none() && !rest.is_empty() {
+                            file.language_name = Some(unquote(rest).to_string());
+                        }
+                    }
+                    Section::Header | Section::Other => {}
+                },
+            }
+        }
+        if file.shift_states.is_empty() {
+            return Err(invalid("no SHIFTSTATE section"));
+        }
+        if file.rows.is_empty() {
+            return Err(invalid("no LAYOUT section"));
+        }
+        Ok(file)
+    }
+
+    /// Parses `SC VK_ Cap cells...`, or the Caps Lock line after an `SGCap`
+    /// row, which has `-1` for its scan code and virtual key.
+    fn parse_layout_row(&mut self, line: &str) -> Result<(), String> {
+        let mut tokens = line.split_whitespace();
+        let (Some(scan_code), Some(vk), Some(caps)) = (tokens.next(), tokens.next(), tokens.next())
+        else {
+            return Err(format!("incomplete LAYOUT row `{line}`"));
+        };
+        let cells = tokens.map(parse_cell).collect::<Result<Vec<_>, _>>()?;
+        if scan_code == "-1" {
+            let row = self
+                .rows
+                .last_mut()
+                .filter(|row| row.sgcaps.is_none())
+                .ok_or_else(|| "SGCap line without a LAYOUT row".to_string())?;
+            row.sgcaps = Some(cells);
+            return Ok(());
+        }
+        let scan_code = u16::from_str_radix(scan_code, 16)
+            .map_err(|_| format!("invalid scan code `{scan_code}`"))?;
+        let caps_lock = if caps.eq_ignore_ascii_case("SGCap") {
+            false
+        } else {
+            let caps: u8 = caps
+                .parse()
+                .map_err(|_| format!("invalid Cap value `{caps}`"))?;
+            caps & 1 != 0
+        };
+        self.rows.push(Row {
+            key: PhysicalKey::from_scan_code(scan_code),
+            vk: vk.to_string(),
+            caps_lock,
+            cells,
+            sgcaps: None,
+        });
+        Ok(())
+    }
+
+    fn into_layout_map(self) -> LayoutMap {
+        let column_of = |level: ShiftLevel| {
+            let state = match level {
+                ShiftLevel::Base => 0,
+                ShiftLevel::Shift => 1,
+                ShiftLevel::AltGr => 6,
+                ShiftLevel::ShiftAltGr => 7,
+                ShiftLevel::Caps => return None,
+            };
+            self.shift_states.iter().position(|&s| s == state)
+        };
+        let mut layout_map = LayoutMap {
+            layout: self.keyboard_layout(),
+            ..Default::default()
+        };
+        for row in &self.rows {
+            let Some(key) = row.key else {
+                continue;
+            };
+            let cell_at = |cells: &[Cell], level| {
+                let column = column_of(level)?;
+                match cells.get(column)? {
+                    Cell::Ligature => self
+                        .ligatures
+                        .get(&(row.vk.clone(), column))
+                        .map(|output| Output::Text(output.clone())),
+                    cell => Output::from_cell(cell),
+                }
+            };
+            for level in ShiftLevel::ALL {
+                let output = match (level, &row.sgcaps) {
+                    (ShiftLevel::Caps, Some(sgcaps)) => cell_at(sgcaps, ShiftLevel::Base),
+                    (ShiftLevel::Caps, None) if row.caps_lock => {
+                        cell_at(&row.cells, ShiftLevel::Shift)
+                    }
+                    (ShiftLevel::Caps, None) => None,
+                    _ => cell_at(&row.cells, level),
+                };
+                let stroke = KeyStroke::new(key, level);
+                match output {
+                    Some(Output::Text(text)) => layout_map.insert_level(stroke, text),
+                    Some(Output::Dead(dead)) => {
+                        layout_map.insert_dead_key(stroke, self.dead_key(dead))
+                    }
+                    None => {}
+                }
+            }
+        }
+        layout_map.layout.direction = layout_map.base_direction();
+        layout_map
+    }
+
+    /// The dead key `dead`. Its combination with Space is its accent, or
+    /// `dead` itself when the file does not list one.
+    fn dead_key(&self, dead: char) -> DeadKey {
+        let mut combinations = self.dead_keys.get(&dead).cloned().unwrap_or_default();
+        let accent = combinations
+            .remove(&' ')
+            .unwrap_or_else(|| dead.to_string());
+        DeadKey {
+            accent,
+            combinations,
+        }
+    }
+
+    fn keyboard_layout(&self) -> KeyboardLayout {
+        let description = if self.description.is_empty() {
+            self.name.clone()
+        } else {
+            self.description.clone()
+        };
+        let mut layout = KeyboardLayout {
+            id: LayoutId::new(&self.name),
+            lang_name: self
+                .language_name
+                .clone()
+                .unwrap_or_else(|| description.clone()),
+            description,
+            native_id: self.locale_id.clone().unwrap_or_else(|| self.name.clone()),
+            ..Default::default()
+        };
+        if let Some(locale_name) = &self.locale_name {
+            layout.set_language_tag(locale_name);
+        }
+        layout
+    }
+}
+
+enum Output {
+    Text(String),
+    Dead(char),
+}
+
+impl Output {
+    fn from_cell(cell: &Cell) -> Option<Output> {
+        match *cell {
+            Cell::Char(c) => Some(Output::Text(c.to_string())),
+            Cell::Dead(c) => Some(Output::Dead(c)),
+            Cell::Empty | Cell::Ligature => None,
+        }
+    }
+}
+
+fn parse_cell(token: &str) -> Result<Cell, String> {
+    match token {
+        "-1" => Ok(Cell::Empty),
+        "%%" => Ok(Cell::Ligature),
+        _ => match token.strip_suffix('@') {
+            Some(dead) if !dead.is_empty() => parse_char(dead).map(Cell::Dead),
+            _ => parse_char(token).map(Cell::Char),
+        },
+    }
+}
+
+/// A character written as itself (`a`) or as a hexadecimal code point of
+/// at least four digits (`05e9`).
+fn parse_char(token: &str) -> Result<char, String> {
+    let mut chars = token.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c);
+    }
+    u32::from_str_radix(token, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("invalid character `{token}`"))
+}
+
+/// `line` without its `//` or `;` comment. A `;` only starts a comment at
+/// the start of a word that goes on, so a lone `;` stays a character.
+fn strip_comment(line: &str) -> &str {
+    let line = line.split_once("//").map_or(line, |(code, _)| code);
+    let bytes = line.as_bytes();
+    for (index, _) in line.match_indices(';') {
+        let word_start = index == 0 || bytes[index - 1].is_ascii_whitespace();
+        let word_goes_on = bytes
+            .get(index + 1)
+            .is_some_and(|next| !next.is_ascii_whitespace());
+        if word_start && (index == 0 || word_goes_on) {
+            return &line[..index];
+        }
+    }
+    line
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Shift states of the columns [`LayoutMap::to_klc`] writes, with the
+/// level each one holds. MSKLC always has a Ctrl column.
+const COLUMNS: [(u8, Option<ShiftLevel>); 5] = [
+    (0, Some(ShiftLevel::Base)),
+    (1, Some(ShiftLevel::Shift)),
+    (2, None),
+    (6, Some(ShiftLevel::AltGr)),
+    (7, Some(ShiftLevel::ShiftAltGr)),
+];
+
+/// Key names MSKLC writes for every layout.
+const KEY_NAMES: &str = "KEYNAME
+
+01\tEsc
+0e\tBackspace
+0f\tTab
+1c\tEnter
+1d\tCtrl
+2a\tShift
+36\t\"Right Shift\"
+37\t\"Num *\"
+38\tAlt
+39\tSpace
+3a\t\"Caps Lock\"
+3b\tF1
+3c\tF2
+3d\tF3
+3e\tF4
+3f\tF5
+40\tF6
+41\tF7
+42\tF8
+43\tF9
+44\tF10
+45\tPause
+46\t\"Scroll Lock\"
+47\t\"Num 7\"
+48\t\"Num 8\"
+49\t\"Num 9\"
+4a\t\"Num -\"
+4b\t\"Num 4\"
+4c\t\"Num 5\"
+4d\t\"Num 6\"
+4e\t\"Num +\"
+4f\t\"Num 1\"
+50\t\"Num 2\"
+51\t\"Num 3\"
+52\t\"Num 0\"
+53\t\"Num Del\"
+54\t\"Sys Req\"
+57\tF11
+58\tF12
+
+KEYNAME_EXT
+
+1c\t\"Num Enter\"
+1d\t\"Right Ctrl\"
+35\t\"Num /\"
+37\t\"Prnt Scrn\"
+38\t\"Right Alt\"
+45\t\"Num Lock\"
+46\tBreak
+47\tHome
+48\tUp
+49\t\"Page Up\"
+4b\tLeft
+4d\tRight
+4f\tEnd
+50\tDown
+51\t\"Page Down\"
+52\tInsert
+53\tDelete
+5b\t\"Left Windows\"
+5c\t\"Right Windows\"
+5d\tApplication
+
+";
+
+/// Virtual key name of `key` in a `LAYOUT` row, for the keys a KLC file
+/// can define.
+fn vk_name(key: PhysicalKey) -> Option<&'static str> {
+    use PhysicalKey::*;
+    let code = key.code();
+    let name = match key {
+        Backquote => "OEM_3",
+        Minus => "OEM_MINUS",
+        Equal => "OEM_PLUS",
+        BracketLeft => "OEM_4",
+        BracketRight => "OEM_6",
+        Backslash | IntlYen => "OEM_5",
+        Semicolon => "OEM_1",
+        Quote => "OEM_7",
+        IntlBackslash => "OEM_102",
+        Comma => "OEM_COMMA",
+        Period => "OEM_PERIOD",
+        Slash => "OEM_2",
+        IntlRo => "ABNT_C1",
+        NumpadComma => "ABNT_C2",
+        NumpadDecimal => "DECIMAL",
+        Space => "SPACE",
+        _ => code
+            .strip_prefix("Key")
+            .or_else(|| code.strip_prefix("Digit"))?,
+    };
+    Some(name)
+}
+
+/// `text` for a quoted KLC string, which has no way to escape a quote.
+fn klc_string(text: &str) -> String {
+    text.replace('"', "")
+}
+
+/// A character in a `LAYOUT` cell: letters and digits as themselves, like
+/// MSKLC writes them, anything else as a code point.
+fn cell_char(c: char) -> String {
+    if c.is_ascii_alphanumeric() {
+        c.to_string()
+    } else {
+        hex(c)
+    }
+}
+
+fn hex(c: char) -> String {
+    format!("{:04x}", u32::from(c))
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Writes a [`LayoutMap`] as KLC source.
+struct KlcWriter<'a> {
+    layout_map: &'a LayoutMap,
+    /// Dead keys with the character naming their `DEADKEY` section: the
+    /// accent, or a private use character when that is taken or not a
+    /// single character.
+    dead_keys: Vec<(&'a DeadKey, char)>,
+    ligatures: Vec<String>,
+    out: String,
+}
+
+impl<'a> KlcWriter<'a> {
+    fn new(layout_map: &'a LayoutMap) -> Self {
+        let mut strokes: Vec<_> = layout_map.dead_keys.iter().collect();
+        strokes.sort_by_key(|(stroke, _)| **stroke);
+        let mut dead_keys: Vec<(&DeadKey, char)> = Vec::new();
+        let mut private_use = '\u{E000}'..='\u{F8FF}';
+        for (_, dead_key) in strokes {
+            if dead_keys.iter().any(|(known, _)| *known == dead_key) {
+                continue;
+            }
+            let taken = |c: char| dead_keys.iter().any(|(_, used)| *used == c);
+            let name = match single_char(&dead_key.accent) {
+                Some(accent) if !taken(accent) => accent,
+                _ => private_use
+                    .by_ref()
+                    .find(|&c| !taken(c))
+                    .unwrap_or('\u{F8FF}'),
+            };
+            dead_keys.push((dead_key, name));
+        }
+        KlcWriter {
+            layout_map,
+            dead_keys,
+            ligatures: Vec::new(),
+            out: String::new(),
+        }
+    }
+
+    fn write(mut self) -> String {
+        let layout = &self.layout_map.layout;
+        let mut name: String = layout
+            .id
+            .as_str()
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(8)
+            .collect();
+        if name.is_empty() {
+            name = "layout".to_string();
+        }
+        let description = if layout.description.is_empty() {
+            layout.id.as_str()
+        } else {
+            &layout.description
+        };
+        let language_name = if layout.lang_name.is_empty() {
+            description
+        } else {
+            &layout.lang_name
+        };
+        self.line(&format!("KBD\t{name}\t\"{}\"\n", klc_string(description)));
+        self.line("COPYRIGHT\t\"\"\n");
+        self.line("COMPANY\t\"\"\n");
+        if let Some(tag) = &layout.language_tag {
+            self.line(&format!("LOCALENAME\t\"{tag}\"\n"));
+        }
+        if layout.native_id.len() == 8 && layout.native_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            self.line(&format!("LOCALEID\t\"{}\"\n", layout.native_id));
+        }
+        self.line("VERSION\t1.0\n");
+        self.line("SHIFTSTATE\n");
+        self.line("0\t//Column 4");
+        self.line("1\t//Column 5 : Shft");
+        self.line("2\t//Column 6 :       Ctrl");
+        self.line("6\t//Column 7 :       Ctrl Alt");
+        self.line("7\t//Column 8 : Shft  Ctrl Alt\n");
+        self.line("LAYOUT\t\t;an extra '@' at the end is a dead key\n");
+        self.line("//SC\tVK_\t\tCap\t0\t1\t2\t6\t7");
+        self.line("//--\t----\t\t----\t----\t----\t----\t----\t----\n");
+        let keys = self.layout_map.keys();
+        for &key in &keys {
+            // Both are `OEM_5`; a file can only hold one of them.
+            if key == PhysicalKey::IntlYen && keys.contains(&PhysicalKey::Backslash) {
+                continue;
+            }
+            if let Some(vk) = vk_name(key) {
+                self.write_row(key, vk);
+            }
+        }
+        self.line("");
+        if !self.ligatures.is_empty() {
+            self.line("LIGATURE\n");
+            self.line("//VK_\tMod#\tChar0\tChar1\tChar2\tChar3");
+            self.line("//----\t\t----\t----\t----\t----\t----\n");
+            for ligature in std::mem::take(&mut self.ligatures) {
+                self.line(&ligature);
+            }
+            self.line("");
+        }
+        for (dead_key, name) in self.dead_keys.clone() {
+            self.line(&format!("DEADKEY\t{}\n", hex(name)));
+            for (base, composed) in &dead_key.combinations {
+                if let Some(composed) = single_char(composed).filter(|_| *base != ' ') {
+                    self.line(&format!("{}\t{}", hex(*base), hex(composed)));
+                }
+            }
+            // Space goes last, as MSKLC writes it, and only once.
+            let space = dead_key.combinations.get(&' ').unwrap_or(&dead_key.accent);
+            if let Some(accent) = single_char(space) {
+                self.line(&format!("0020\t{}", hex(accent)));
+            }
+            self.line("");
+        }
+        self.out.push_str(KEY_NAMES);
+        if !self.dead_keys.is_empty() {
+            self.line("KEYNAME_DEAD\n");
+            for (dead_key, name) in self.dead_keys.clone() {
+                self.line(&format!(
+                    "{}\t\"{}\"",
+                    hex(name),
+                    klc_string(&dead_key.accent)
+                ));
+            }
+            self.line("");
+        }
+        self.line("DESCRIPTIONS\n");
+        self.line(&format!("0409\t{description}\n"));
+        self.line("LANGUAGENAMES\n");
+        self.line(&format!("0409\t{language_name}\n"));
+        self.line("ENDKBD");
+        self.out
+    }
+
+    /// Writes the `LAYOUT` row of `key`, and the Caps Lock line after it
+    /// for `SGCap`.
+    fn write_row(&mut self, key: PhysicalKey, vk: &str) {
+        let exact = |level| self.layout_map.map.get(&KeyStroke::new(key, level));
+        let caps = exact(ShiftLevel::Caps).and_then(|caps| single_char(caps));
+        let shift = self
+            .layout_map
+            .resolve(KeyStroke::new(key, ShiftLevel::Shift))
+            .and_then(single_char);
+        let cap = match caps {
+            None => "0",
+            Some(caps) if Some(caps) == shift => "1",
+            Some(_) => "SGCap",
+        };
+        let cells: Vec<String> = COLUMNS
+            .iter()
+            .enumerate()
+            .map(|(column, (_, level))| match level {
+                Some(level) => self.cell(key, *level, vk, column),
+                None => "-1".to_string(),
+            })
+            .collect();
+        let scan_code = key.scan_code();
+        self.line(&format!(
+            "{scan_code:02x}\t{vk}\t\t{cap}\t{}",
+            cells.join("\t")
+        ));
+        if let (Some(caps), "SGCap") = (caps, cap) {
+            self.line(&format!("-1\t-1\t\t0\t{}\t-1", cell_char(caps)));
+        }
+    }
+
+    fn cell(&mut self, key: PhysicalKey, level: ShiftLevel, vk: &str, column: usize) -> String {
+        let stroke = KeyStroke::new(key, level);
+        if let Some(output) = self.layout_map.map.get(&stroke) {
+            return match single_char(output) {
+                Some(c) => cell_char(c),
+                None => {
+                    let chars: Vec<String> = output.chars().map(hex).collect();
+                    self.ligatures
+                        .push(format!("{vk}\t{column}\t{}", chars.join("\t")));
+                    "%%".to_string()
+                }
+            };
+        }
+        let dead = self.layout_map.dead_keys.get(&stroke).and_then(|dead_key| {
+            self.dead_keys
+                .iter()
+                .find(|(known, _)| *known == dead_key)
+                .map(|(_, name)| *name)
+        });
+        match dead {
+            Some(name) => format!("{}@", cell_char(name)),
+            None => "-1".to_string(),
+        }
+    }
+
+    /// Appends `text` and a line end.
+    fn line(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests;
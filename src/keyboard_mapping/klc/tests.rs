@@ -0,0 +1,400 @@
+use super::*;
+use crate::keyboard_mapping::shift_text_language;
+use crate::keyboard_mapping::temp_path;
+use crate::keyboard_mapping::types::KeyboardDirection;
+
+const SAMPLE: &str = "KBD\tkbdtest\t\"Test Layout\"
+
+COPYRIGHT\t\"(c) 2024 Example\"
+
+COMPANY\t\"Example\"
+
+LOCALENAME\t\"de-DE\"
+
+LOCALEID\t\"00000407\"
+
+VERSION\t1.0
+
+SHIFTSTATE
+
+0\t//Column 4
+1\t//Column 5 : Shft
+2\t//Column 6 :       Ctrl
+6\t//Column 7 :       Ctrl Alt
+7\t//Column 8 : Shft  Ctrl Alt
+
+LAYOUT\t\t;an extra '@' at the end is a dead key
+
+//SC\tVK_\t\tCap\t0\t1\t2\t6\t7
+//--\t----\t\t----\t----\t----\t----\t----\t----
+
+02\t1\t\t0\t1\t0021\t-1\t-1\t-1\t// DIGIT ONE, EXCLAMATION MARK
+10\tQ\t\t1\tq\tQ\t0011\t0040\t-1\t// LATIN SMALL LETTER Q, LATIN CAPITAL LETTER Q, <none>, COMMERCIAL AT
+12\tE\t\t1\te\tE\t0005\t20ac\t-1
+1e\tA\t\t1\ta\tA\t0001\t-1\t-1
+1a\tOEM_1\t\tSGCap\t00fc\t00dc\t-1\t-1\t-1
+-1\t-1\t\t0\t00dc\t00fc\t\t\t\t// SGCap: Caps Lock types the capital
+28\tOEM_7\t\t0\t%%\t00e4\t-1\t-1\t-1
+29\tOEM_3\t\t0\t005e@\t00b0\t-1\t-1\t-1
+0d\tOEM_6\t\t0\t00b4@\t0060@\t-1\t-1\t-1
+39\tSPACE\t\t0\t0020\t0020\t0020\t-1\t-1
+7e\tABNT_C2\t\t0\t002e\t002e\t-1\t-1\t-1
+
+LIGATURE
+
+//VK_\tMod#\tChar0\tChar1\tChar2\tChar3
+//----\t\t----\t----\t----\t----\t----
+
+OEM_7\t0\t0061\t0065\t// LATIN SMALL LETTER A + LATIN SMALL LETTER E
+
+DEADKEY\t005e
+
+0061\t00e2\t// a -> \u{e2}
+0065\t00ea\t// e -> \u{ea}
+0020\t005e\t//   -> ^
+
+DEADKEY\t00b4
+
+0061\t00e1\t// a -> \u{e1}
+0065\t00e9\t// e -> \u{e9}
+0020\t00b4
+
+DEADKEY\t0060
+
+0061\t00e0
+
+KEYNAME
+
+01\tEsc
+0e\tBackspace
+39\tSpace
+
+KEYNAME_DEAD
+
+005e\t\"CIRCUMFLEX\"
+00b4\t\"ACUTE\"
+
+DESCRIPTIONS
+
+0409\tTest Layout
+
+LANGUAGENAMES
+
+0407\tGerman (Germany)
+
+ENDKBD
+";
+
+const HEBREW: &str = "KBD\thebtest\t\"Hebrew Test\"
+LOCALENAME\t\"he-IL\"
+LOCALEID\t\"0000040d\"
+SHIFTSTATE
+0
+1
+LAYOUT
+1e\tA\t0\t05e9\tA
+10\tQ\t0\t/\tQ
+ENDKBD
+";
+
+/// Two dead keys on one key, as on the Spanish layout.
+const SPANISH: &str = "KBD\tdeadtest\t\"Spanish Test\"
+LOCALENAME\t\"es-ES\"
+SHIFTSTATE
+0
+1
+LAYOUT
+12\tE\t1\te\tE
+1e\tA\t1\ta\tA
+28\tOEM_7\t0\t00b4@\t00a8@
+39\tSPACE\t0\t0020\t0020
+DEADKEY\t00b4
+0061\t00e1
+0065\t00e9
+0020\t00b4
+DEADKEY\t00a8
+0061\t00e4
+0065\t00eb
+0020\t00a8
+ENDKBD
+";
+
+fn output(map: &LayoutMap, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+    map.get(key, level)
+}
+
+#[test]
+fn test_from_klc_reads_every_level() {
+    let map = LayoutMap::from_klc(SAMPLE).unwrap();
+    assert_eq!(output(&map, PhysicalKey::KeyQ, ShiftLevel::Base), Some("q"));
+    assert_eq!(
+        output(&map, PhysicalKey::KeyQ, ShiftLevel::Shift),
+        Some("Q")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyQ, ShiftLevel::AltGr),
+        Some("@")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::KeyE, ShiftLevel::AltGr),
+        Some("€")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Digit1, ShiftLevel::Shift),
+        Some("!")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Space, ShiftLevel::Base),
+        Some(" ")
+    );
+    // Ctrl columns are not levels.
+    assert!(!map.map.values().any(|output| output == "\u{11}"));
+}
+
+#[test]
+fn test_from_klc_caps_lock() {
+    let map = LayoutMap::from_klc(SAMPLE).unwrap();
+    assert_eq!(output(&map, PhysicalKey::KeyA, ShiftLevel::Caps), Some("A"));
+    assert_eq!(
+        output(&map, PhysicalKey::BracketLeft, ShiftLevel::Caps),
+        Some("Ü")
+    );
+    assert_eq!(output(&map, PhysicalKey::Digit1, ShiftLevel::Caps), None);
+}
+
+#[test]
+fn test_from_klc_ligatures() {
+    let map = LayoutMap::from_klc(SAMPLE).unwrap();
+    assert_eq!(
+        output(&map, PhysicalKey::Quote, ShiftLevel::Base),
+        Some("ae")
+    );
+    assert_eq!(
+        output(&map, PhysicalKey::Quote, ShiftLevel::Shift),
+        Some("ä")
+    );
+}
+
+#[test]
+fn test_from_klc_dead_keys() {
+    let map = LayoutMap::from_klc(SAMPLE).unwrap();
+    let circumflex = &map.dead_keys[&KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base)];
+    assert_eq!(circumflex.accent, "^");
+    assert_eq!(circumflex.combinations[&'e'], "ê");
+    assert!(!circumflex.combinations.contains_key(&' '));
+
+    // No Space entry: the dead character itself is the accent.
+    let grave = &map.dead_keys[&KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Shift)];
+    assert_eq!(grave.accent, "`");
+
+    let typed = map.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Equal, ShiftLevel::Base),
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("é"));
+    assert_eq!(
+        output(&map, PhysicalKey::Backquote, ShiftLevel::Shift),
+        Some("°")
+    );
+}
+
+#[test]
+fn test_from_klc_dead_key_replay() {
+    let spanish = LayoutMap::from_klc(SPANISH).unwrap();
+    let typed = spanish.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base),
+        KeyStroke::new(PhysicalKey::Space, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("´"));
+    assert_eq!(shift_text_language("´é", &spanish, &spanish), "´é");
+    assert_eq!(shift_text_language("¨ë a", &spanish, &spanish), "¨ë a");
+}
+
+#[test]
+fn test_from_klc_metadata() {
+    let layout = LayoutMap::from_klc(SAMPLE).unwrap().layout;
+    assert_eq!(layout.id, LayoutId::new("kbdtest"));
+    assert_eq!(layout.description, "Test Layout");
+    assert_eq!(layout.lang_name, "German (Germany)");
+    assert_eq!(layout.language_tag.as_deref(), Some("de-DE"));
+    assert_eq!(layout.languages, ["deu"]);
+    assert_eq!(layout.scripts, ["Latn"]);
+    assert_eq!(layout.short_label, "DE");
+    assert_eq!(layout.native_id, "00000407");
+    assert_eq!(layout.direction, KeyboardDirection::LTR);
+
+    let hebrew = LayoutMap::from_klc(HEBREW).unwrap();
+    assert_eq!(hebrew.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(hebrew.layout.lang_name, "Hebrew Test");
+    assert_eq!(hebrew.layout.native_id, "0000040D");
+    assert_eq!(
+        output(&hebrew, PhysicalKey::KeyQ, ShiftLevel::Base),
+        Some("/")
+    );
+}
+
+#[test]
+fn test_from_klc_bytes_decodes_utf16() {
+    let expected = LayoutMap::from_klc(SAMPLE).unwrap();
+    let mut little_endian = vec![0xFF, 0xFE];
+    let mut big_endian = vec![0xFE, 0xFF];
+    for unit in SAMPLE.encode_utf16() {
+        little_endian.extend(unit.to_le_bytes());
+        big_endian.extend(unit.to_be_bytes());
+    }
+    assert_eq!(LayoutMap::from_klc_bytes(&little_endian).unwrap(), expected);
+    assert_eq!(LayoutMap::from_klc_bytes(&big_endian).unwrap(), expected);
+    assert_eq!(
+        LayoutMap::from_klc_bytes(&little_endian[2..]).unwrap(),
+        expected
+    );
+    assert_eq!(
+        LayoutMap::from_klc_bytes(SAMPLE.as_bytes()).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_from_klc_file() {
+    let path = temp_path("test.klc");
+    let bytes: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(HEBREW.encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    fs::write(&path, bytes).unwrap();
+    let map = LayoutMap::from_klc_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(map.unwrap().layout.id, LayoutId::new("hebtest"));
+    assert!(matches!(
+        LayoutMap::from_klc_file(&path),
+        Err(LayoutError::FileAccess { .. })
+    ));
+}
+
+#[test]
+fn test_from_klc_errors() {
+    let error = LayoutMap::from_klc("KBD x \"x\"\nSHIFTSTATE\n0\n").unwrap_err();
+    assert_eq!(error.to_string(), "invalid KLC file: no LAYOUT section");
+
+    let error = LayoutMap::from_klc("SHIFTSTATE\n0\nLAYOUT\n1e\tA\t0\tzzzz\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid KLC file: line 4: invalid character `zzzz`"
+    );
+    assert!(LayoutMap::from_klc("SHIFTSTATE\n0\nLAYOUT\n-1\t-1\t0\ta\n").is_err());
+}
+
+#[test]
+fn test_strip_comment() {
+    assert_eq!(strip_comment("LAYOUT\t\t;an extra '@'"), "LAYOUT\t\t");
+    assert_eq!(strip_comment("02\t1\t0\t1\t// DIGIT ONE"), "02\t1\t0\t1\t");
+    assert_eq!(strip_comment("27\tOEM_1\t0\t;\t:"), "27\tOEM_1\t0\t;\t:");
+    assert_eq!(strip_comment("; whole line"), "");
+}
+
+#[test]
+fn test_to_klc_round_trip() {
+    for klc in [SAMPLE, HEBREW] {
+        let map = LayoutMap::from_klc(klc).unwrap();
+        assert_eq!(LayoutMap::from_klc(&map.to_klc()).unwrap(), map);
+        assert_eq!(LayoutMap::from_klc_bytes(&map.to_klc_bytes()).unwrap(), map);
+    }
+}
+
+#[test]
+fn test_to_klc_writes_msklc_sections() {
+    let map = LayoutMap::from_klc(SAMPLE).unwrap();
+    let klc = map.to_klc();
+    assert!(klc.starts_with("KBD\tkbdtest\t\"Test Layout\"\n"));
+    assert!(klc.contains("LOCALENAME\t\"de-DE\""));
+    assert!(klc.contains("1e\tA\t\t1\ta\tA\t-1\t-1\t-1\n"));
+    assert!(klc.contains("29\tOEM_3\t\t0\t005e@\t00b0\t-1\t-1\t-1\n"));
+    assert!(klc.contains("28\tOEM_7\t\t0\t%%\t00e4\t-1\t-1\t-1\n"));
+    assert!(klc.contains("OEM_7\t0\t0061\t0065\n"));
+    assert!(klc.contains("DEADKEY\t005e\n\n0061\t00e2\n0065\t00ea\n0020\t005e\n"));
+    assert!(klc.contains("KEYNAME_EXT"));
+    assert!(klc.ends_with("ENDKBD\n"));
+    let bytes = map.to_klc_bytes();
+    assert_eq!(bytes[..2], [0xFF, 0xFE]);
+    assert_eq!(bytes[2..6], [b'K', 0, b'B', 0]);
+}
+
+#[test]
+fn test_to_klc_writes_space_once() {
+    let mut map = LayoutMap::default();
+    map.insert_level(
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Base),
+        "e".to_string(),
+    );
+    map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base),
+        DeadKey {
+            accent: "´".to_string(),
+            combinations: BTreeMap::from([(' ', "´".to_string()), ('e', "é".to_string())]),
+        },
+    );
+    let klc = map.to_klc();
+    assert!(klc.contains("DEADKEY\t00b4\n\n0065\t00e9\n0020\t00b4\n\n"));
+    assert_eq!(klc.matches("0020\t00b4").count(), 1);
+    let read = LayoutMap::from_klc(&klc).unwrap();
+    let stroke = KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base);
+    assert_eq!(read.dead_keys[&stroke].combine(" "), "´");
+}
+
+#[test]
+fn test_to_klc_writes_each_virtual_key_once() {
+    let mut map = LayoutMap::default();
+    map.layout.description = "Say \"hi\"".to_string();
+    for (key, output) in [(PhysicalKey::Backslash, "\\"), (PhysicalKey::IntlYen, "¥")] {
+        map.insert_level(KeyStroke::new(key, ShiftLevel::Base), output.to_string());
+    }
+    let klc = map.to_klc();
+    assert_eq!(klc.matches("\tOEM_5\t").count(), 1);
+    assert!(klc.starts_with("KBD\tlayout\t\"Say hi\"\n"));
+
+    let read = LayoutMap::from_klc(&klc).unwrap();
+    assert_eq!(read.layout.description, "Say hi");
+    assert_eq!(
+        output(&read, PhysicalKey::Backslash, ShiftLevel::Base),
+        Some("\\")
+    );
+    assert_eq!(output(&read, PhysicalKey::IntlYen, ShiftLevel::Base), None);
+}
+
+#[test]
+fn test_to_klc_names_dead_keys_without_a_single_accent() {
+    let mut map = LayoutMap::default();
+    map.layout.id = LayoutId::new("custom-layout(test)");
+    let dead_key = |accent: &str| DeadKey {
+        accent: accent.to_string(),
+        combinations: BTreeMap::from([('a', "ä".to_string())]),
+    };
+    map.insert_level(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "a".to_string(),
+    );
+    map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base),
+        dead_key(""),
+    );
+    map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Shift),
+        dead_key("^^"),
+    );
+    // Numpad digits have no place in a KLC file.
+    map.insert_level(
+        KeyStroke::new(PhysicalKey::Numpad1, ShiftLevel::Base),
+        "1".to_string(),
+    );
+    let klc = map.to_klc();
+    assert!(klc.starts_with("KBD\tcustomla\t"));
+    assert!(klc.contains("28\tOEM_7\t\t0\te000@\te001@\t-1\t-1\t-1\n"));
+    assert!(!klc.contains("NUMPAD"));
+
+    let read = LayoutMap::from_klc(&klc).unwrap();
+    let typed = read.type_strokes(&[
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Shift),
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+    ]);
+    assert_eq!(typed.as_deref(), Some("ä"));
+}
@@ -0,0 +1,186 @@
+//! ISO 639 / BCP-47 language codes and the script each language is written in.
+
+struct Language {
+    /// Shortest BCP-47 primary subtag: ISO 639-1 when it exists, else 639-2.
+    tag: &'static str,
+    /// ISO 639-2/T (terminology) code.
+    iso639_2: &'static str,
+    /// ISO 639-2/B (bibliographic) code where it differs from 639-2/T.
+    iso639_2b: Option<&'static str>,
+    /// ISO 15924 script code.
+    script: &'static str,
+}
+
+const fn lang(
+    tag: &'static str,
+    iso639_2: &'static str,
+    iso639_2b: Option<&'static str>,
+    script: &'static str,
+) -> Language {
+    Language {
+        tag,
+        iso639_2,
+        iso639_2b,
+        script,
+    }
+}
+
+#[rustfmt::skip]
+const LANGUAGES: &[Language] = &[
+    lang("af", "afr", None, "Latn"),
+    lang("am", "amh", None, "Ethi"),
+    lang("ar", "ara", None, "Arab"),
+    lang("as", "asm", None, "Beng"),
+    lang("az", "aze", None, "Latn"),
+    lang("ba", "bak", None, "Cyrl"),
+    lang("be", "bel", None, "Cyrl"),
+    lang("bg", "bul", None, "Cyrl"),
+    lang("bn", "ben", None, "Beng"),
+    lang("bo", "bod", Some("tib"), "Tibt"),
+    lang("bs", "bos", None, "Latn"),
+    lang("ca", "cat", None, "Latn"),
+    lang("cs", "ces", Some("cze"), "Latn"),
+    lang("cv", "chv", None, "Cyrl"),
+    lang("cy", "cym", Some("wel"), "Latn"),
+    lang("da", "dan", None, "Latn"),
+    lang("de", "deu", Some("ger"), "Latn"),
+    lang("dv", "div", None, "Thaa"),
+    lang("dz", "dzo", None, "Tibt"),
+    lang("el", "ell", Some("gre"), "Grek"),
+    lang("en", "eng", None, "Latn"),
+    lang("eo", "epo", None, "Latn"),
+    lang("es", "spa", None, "Latn"),
+    lang("et", "est", None, "Latn"),
+    lang("eu", "eus", Some("baq"), "Latn"),
+    lang("fa", "fas", Some("per"), "Arab"),
+    lang("fi", "fin", None, "Latn"),
+    lang("fo", "fao", None, "Latn"),
+    lang("fr", "fra", Some("fre"), "Latn"),
+    lang("ga", "gle", None, "Latn"),
+    lang("gd", "gla", None, "Latn"),
+    lang("gl", "glg", None, "Latn"),
+    lang("gu", "guj", None, "Gujr"),
+    lang("ha", "hau", None, "Latn"),
+    lang("he", "heb", None, "Hebr"),
+    lang("hi", "hin", None, "Deva"),
+    lang("hr", "hrv", None, "Latn"),
+    lang("hu", "hun", None, "Latn"),
+    lang("hy", "hye", Some("arm"), "Armn"),
+    lang("id", "ind", None, "Latn"),
+    lang("ig", "ibo", None, "Latn"),
+    lang("is", "isl", Some("ice"), "Latn"),
+    lang("it", "ita", None, "Latn"),
+    lang("iu", "iku", None, "Cans"),
+    lang("ja", "jpn", None, "Jpan"),
+    lang("jv", "jav", None, "Latn"),
+    lang("ka", "kat", Some("geo"), "Geor"),
+    lang("kk", "kaz", None, "Cyrl"),
+    lang("km", "khm", None, "Khmr"),
+    lang("kn", "kan", None, "Knda"),
+    lang("ko", "kor", None, "Kore"),
+    lang("ku", "kur", None, "Latn"),
+    lang("kv", "kom", None, "Cyrl"),
+    lang("ky", "kir", None, "Cyrl"),
+    lang("lo", "lao", None, "Laoo"),
+    lang("lt", "lit", None, "Latn"),
+    lang("lv", "lav", None, "Latn"),
+    lang("mi", "mri", Some("mao"), "Latn"),
+    lang("mk", "mkd", Some("mac"), "Cyrl"),
+    lang("ml", "mal", None, "Mlym"),
+    lang("mn", "mon", None, "Cyrl"),
+    lang("mr", "mar", None, "Deva"),
+    lang("ms", "msa", Some("may"), "Latn"),
+    lang("mt", "mlt", None, "Latn"),
+    lang("my", "mya", Some("bur"), "Mymr"),
+    lang("nb", "nob", None, "Latn"),
+    lang("ne", "nep", None, "Deva"),
+    lang("nl", "nld", Some("dut"), "Latn"),
+    lang("nn", "nno", None, "Latn"),
+    lang("no", "nor", None, "Latn"),
+    lang("oc", "oci", None, "Latn"),
+    lang("or", "ori", None, "Orya"),
+    lang("os", "oss", None, "Cyrl"),
+    lang("pa", "pan", None, "Guru"),
+    lang("pl", "pol", None, "Latn"),
+    lang("ps", "pus", None, "Arab"),
+    lang("pt", "por", None, "Latn"),
+    lang("ro", "ron", Some("rum"), "Latn"),
+    lang("ru", "rus", None, "Cyrl"),
+    lang("sa", "san", None, "Deva"),
+    lang("sd", "snd", None, "Arab"),
+    lang("se", "sme", None, "Latn"),
+    lang("si", "sin", None, "Sinh"),
+    lang("sk", "slk", Some("slo"), "Latn"),
+    lang("sl", "slv", None, "Latn"),
+    lang("sq", "sqi", Some("alb"), "Latn"),
+    lang("sr", "srp", None, "Cyrl"),
+    lang("sv", "swe", None, "Latn"),
+    lang("sw", "swa", None, "Latn"),
+    lang("ta", "tam", None, "Taml"),
+    lang("te", "tel", None, "Telu"),
+    lang("tg", "tgk", None, "Cyrl"),
+    lang("th", "tha", None, "Thai"),
+    lang("tk", "tuk", None, "Latn"),
+    lang("tl", "tgl", None, "Latn"),
+    lang("tr", "tur", None, "Latn"),
+    lang("tt", "tat", None, "Cyrl"),
+    lang("ug", "uig", None, "Arab"),
+    lang("uk", "ukr", None, "Cyrl"),
+    lang("ur", "urd", None, "Arab"),
+    lang("uz", "uzb", None, "Latn"),
+    lang("vi", "vie", None, "Latn"),
+    lang("wo", "wol", None, "Latn"),
+    lang("yo", "yor", None, "Latn"),
+    lang("zh", "zho", Some("chi"), "Hani"),
+    lang("chr", "chr", None, "Cher"),
+    lang("crh", "crh", None, "Latn"),
+    lang("fil", "fil", None, "Latn"),
+    lang("nqo", "nqo", None, "Nkoo"),
+    lang("sah", "sah", None, "Cyrl"),
+    lang("syr", "syr", None, "Syrc"),
+    lang("udm", "udm", None, "Cyrl"),
+];
+
+fn find(code: &str) -> Option<&'static Language> {
+    let code = code.to_ascii_lowercase();
+    LANGUAGES.iter().find(|language| {
+        language.tag == code || language.iso639_2 == code || language.iso639_2b == Some(&code)
+    })
+}
+
+/// BCP-47 primary language subtag for an ISO 639-1, 639-2/T or 639-2/B
+/// code: `"heb"` and `"he"` both give `"he"`. Codes without a two-letter
+/// form are already valid subtags and are returned lowercased.
+pub fn language_subtag(iso639: &str) -> String {
+    match find(iso639) {
+        Some(language) => language.tag.to_string(),
+        None => iso639.to_ascii_lowercase(),
+    }
+}
+
+/// BCP-47 tag built from an ISO 639 language and an optional ISO 3166
+/// region, e.g. `("heb", Some("IL"))` gives `"he-IL"`.
+pub fn language_tag(iso639: &str, region: Option<&str>) -> String {
+    let subtag = language_subtag(iso639);
+    match region {
+        Some(region) => format!("{subtag}-{}", region.to_ascii_uppercase()),
+        None => subtag,
+    }
+}
+
+/// ISO 639-2/T code of `language`, given as an ISO 639 code or a BCP-47
+/// tag: `"he-IL"` gives `"heb"`. Only the primary subtag is considered.
+pub fn iso639_2(language: &str) -> Option<&'static str> {
+    let primary = language.split(['-', '_']).next()?;
+    find(primary).map(|language| language.iso639_2)
+}
+
+/// ISO 15924 code of the script `language` is usually written in. Accepts
+/// ISO 639 codes and BCP-47 tags; only the primary subtag is considered.
+pub fn default_script(language: &str) -> Option<&'static str> {
+    let primary = language.split(['-', '_']).next()?;
+    find(primary).map(|language| language.script)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn test_language_subtag_normalizes_iso639() {
+    assert_eq!(language_subtag("heb"), "he");
+    assert_eq!(language_subtag("he"), "he");
+    assert_eq!(language_subtag("ger"), "de");
+    assert_eq!(language_subtag("deu"), "de");
+    assert_eq!(language_subtag("ENG"), "en");
+    assert_eq!(language_subtag("haw"), "haw");
+}
+
+#[test]
+fn test_language_tag_adds_region() {
+    assert_eq!(language_tag("heb", Some("IL")), "he-IL");
+    assert_eq!(language_tag("eng", Some("us")), "en-US");
+    assert_eq!(language_tag("rus", None), "ru");
+}
+
+#[test]
+fn test_iso639_2() {
+    assert_eq!(iso639_2("he-IL"), Some("heb"));
+    assert_eq!(iso639_2("ger"), Some("deu"));
+    assert_eq!(iso639_2("en_US"), Some("eng"));
+    assert_eq!(iso639_2("xyz"), None);
+}
+
+#[test]
+fn test_default_script() {
+    assert_eq!(default_script("heb"), Some("Hebr"));
+    assert_eq!(default_script("he-IL"), Some("Hebr"));
+    assert_eq!(default_script("ru_RU"), Some("Cyrl"));
+    assert_eq!(default_script("fas"), Some("Arab"));
+    assert_eq!(default_script("xyz"), None);
+}
+
+#[test]
+fn test_table_codes_are_unique() {
+    for (i, a) in LANGUAGES.iter().enumerate() {
+        for b in &LANGUAGES[i + 1..] {
+            assert_ne!(a.tag, b.tag);
+            assert_ne!(a.iso639_2, b.iso639_2);
+        }
+    }
+}
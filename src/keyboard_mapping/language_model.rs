@@ -0,0 +1,210 @@
+//! Character n-gram language models that judge how plausible a text is in a
+//! given language, used to pick the layout a text was meant to be typed on.
+
+use std::collections::{HashMap, HashSet};
+
+use super::language::language_subtag;
+use super::types::KeyboardLayout;
+
+/// Length of the longest n-gram a model counts.
+const ORDER: usize = 3;
+
+/// Interpolation weights of the uniform, unigram, bigram and trigram
+/// estimates. Estimates whose context never occurred in training are left
+/// out and the remaining weights renormalised.
+const WEIGHTS: [f64; ORDER + 1] = [0.01, 0.09, 0.3, 0.6];
+
+/// Character trigram model of one language.
+///
+/// Text is lowercased and runs of whitespace are read as a single space, so
+/// word starts and ends are modelled too. Probabilities are interpolated down
+/// to a uniform estimate, so characters never seen in training are unlikely
+/// but not impossible.
+#[derive(Debug, Clone)]
+pub struct LanguageModel {
+    language: String,
+    counts: HashMap<String, u32>,
+    total: u32,
+    alphabet: usize,
+}
+
+impl LanguageModel {
+    /// Trains a model for `language` (a BCP-47 tag or ISO 639 code) on
+    /// `corpus`. A few kilobytes of ordinary prose are enough to tell
+    /// languages and keyboard gibberish apart.
+    pub fn train(language: &str, corpus: &str) -> Self {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        let mut alphabet = HashSet::new();
+        let chars = normalize(corpus);
+        for end in 1..chars.len() {
+            alphabet.insert(chars[end]);
+            for n in 1..=ORDER.min(end) {
+                let gram: String = chars[end + 1 - n..=end].iter().collect();
+                *counts.entry(gram).or_default() += 1;
+            }
+        }
+        LanguageModel {
+            language: primary_subtag(language),
+            total: (chars.len() - 1) as u32,
+            counts,
+            alphabet: alphabet.len(),
+        }
+    }
+
+    /// Primary BCP-47 subtag of the modelled language, e.g. `"he"`.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Natural log of the probability of `text`, summed over its characters
+    /// and the word boundary after it.
+    pub fn log_likelihood(&self, text: &str) -> f64 {
+        let chars = normalize(text);
+        (1..chars.len())
+            .map(|end| {
+                self.probability(&chars[end.saturating_sub(ORDER - 1)..=end])
+                    .ln()
+            })
+            .sum()
+    }
+
+    /// [`log_likelihood`](Self::log_likelihood) per character, comparable
+    /// between texts of different lengths.
+    pub fn average_log_likelihood(&self, text: &str) -> f64 {
+        match normalize(text).len() - 1 {
+            0 => 0.0,
+            scored => self.log_likelihood(text) / scored as f64,
+        }
+    }
+
+    /// Interpolated probability of the last character of `gram` after the
+    /// ones before it.
+    fn probability(&self, gram: &[char]) -> f64 {
+        let mut weight = WEIGHTS[0];
+        let mut probability = WEIGHTS[0] / (self.alphabet + 1) as f64;
+        for n in 1..=gram.len() {
+            let context = &gram[gram.len() - n..gram.len() - 1];
+            let context_count = if context.is_empty() {
+                self.total
+            } else {
+                self.count(context)
+            };
+            if context_count == 0 {
+                continue;
+            }
+            let count = self.count(&gram[gram.len() - n..]);
+            weight += WEIGHTS[n];
+            probability += WEIGHTS[n] * f64::from(count) / f64::from(context_count);
+        }
+        probability / weight
+    }
+
+    fn count(&self, gram: &[char]) -> u32 {
+        let gram: String = gram.iter().collect();
+        self.counts.get(&gram).copied().unwrap_or(0)
+    }
+}
+
+/// Lowercased characters of `text` between two word boundaries, with
+/// whitespace collapsed to single spaces.
+fn normalize(text: &str) -> Vec<char> {
+    let mut chars = vec![' '];
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let c = if c.is_whitespace() { ' ' } else { c };
+        if c != ' ' || chars.last() != Some(&' ') {
+            chars.push(c);
+        }
+    }
+    if chars.last() != Some(&' ') {
+        chars.push(' ');
+    }
+    chars
+}
+
+/// Primary subtag of a BCP-47 tag or ISO 639 code, as two letters when the
+/// language has an ISO 639-1 code.
+fn primary_subtag(language: &str) -> String {
+    let primary = language.split(['-', '_']).next().unwrap_or_default();
+    language_subtag(primary)
+}
+
+/// Set of [`LanguageModel`]s, at most one per language.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageModels {
+    models: Vec<LanguageModel>,
+}
+
+impl LanguageModels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `model`, replacing any model of the same language.
+    pub fn insert(&mut self, model: LanguageModel) {
+        self.models
+            .retain(|existing| existing.language != model.language);
+        self.models.push(model);
+    }
+
+    /// Model of `language`, given as a BCP-47 tag (`"he-IL"`) or an ISO 639
+    /// code (`"heb"`).
+    pub fn get(&self, language: &str) -> Option<&LanguageModel> {
+        let language = primary_subtag(language);
+        self.models.iter().find(|model| model.language == language)
+    }
+
+    /// Model of the language `layout` is for: its `language_tag` when set,
+    /// otherwise the first of its `languages` that has a model.
+    pub fn for_layout(&self, layout: &KeyboardLayout) -> Option<&LanguageModel> {
+        layout
+            .language_tag
+            .iter()
+            .chain(&layout.languages)
+            .find_map(|language| self.get(language))
+    }
+
+    /// Primary subtags of the modelled languages.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().map(LanguageModel::language)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Models trained on the small corpora shipped with the crate, for
+    /// English, Hebrew, Russian, Ukrainian, Arabic, Persian, German, French,
+    /// Spanish, Greek, Turkish and Thai. Trained on first use.
+    #[cfg(feature = "language-models")]
+    pub fn bundled() -> &'static LanguageModels {
+        use std::sync::OnceLock;
+
+        static BUNDLED: OnceLock<LanguageModels> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            let mut models = LanguageModels::new();
+            for (language, corpus) in BUNDLED_CORPORA {
+                models.insert(LanguageModel::train(language, corpus));
+            }
+            models
+        })
+    }
+}
+
+#[cfg(feature = "language-models")]
+const BUNDLED_CORPORA: &[(&str, &str)] = &[
+    ("en", include_str!("language_model/corpora/en.txt")),
+    ("he", include_str!("language_model/corpora/he.txt")),
+    ("ru", include_str!("language_model/corpora/ru.txt")),
+    ("uk", include_str!("language_model/corpora/uk.txt")),
+    ("ar", include_str!("language_model/corpora/ar.txt")),
+    ("fa", include_str!("language_model/corpora/fa.txt")),
+    ("de", include_str!("language_model/corpora/de.txt")),
+    ("fr", include_str!("language_model/corpora/fr.txt")),
+    ("es", include_str!("language_model/corpora/es.txt")),
+    ("el", include_str!("language_model/corpora/el.txt")),
+    ("tr", include_str!("language_model/corpora/tr.txt")),
+    ("th", include_str!("language_model/corpora/th.txt")),
+];
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,96 @@
+use super::*;
+use crate::keyboard_mapping::types::LayoutId;
+
+const ENGLISH: &str = "hello how are you today, the weather is nice and we are going home";
+const HEBREW: &str = "שלום מה שלומך היום, מזג האוויר יפה ואנחנו הולכים הביתה שלום";
+
+#[test]
+fn test_model_prefers_its_own_language() {
+    let english = LanguageModel::train("en", ENGLISH);
+    assert!(english.log_likelihood("hello") > english.log_likelihood("akuo"));
+    assert!(english.log_likelihood("hello") > english.log_likelihood("יקךךם"));
+}
+
+#[test]
+fn test_model_ignores_case_and_whitespace_runs() {
+    let english = LanguageModel::train("en", ENGLISH);
+    assert_eq!(
+        english.log_likelihood("Hello  World"),
+        english.log_likelihood("hello world")
+    );
+}
+
+#[test]
+fn test_unseen_characters_are_unlikely_but_possible() {
+    let hebrew = LanguageModel::train("he", HEBREW);
+    let score = hebrew.log_likelihood("xyz");
+    assert!(score.is_finite());
+    assert!(score < hebrew.log_likelihood("שלום"));
+}
+
+#[test]
+fn test_average_log_likelihood_of_empty_text() {
+    let english = LanguageModel::train("en", ENGLISH);
+    assert_eq!(english.average_log_likelihood(""), 0.0);
+    assert!(english.average_log_likelihood("hello") < 0.0);
+}
+
+#[test]
+fn test_language_is_normalized_to_primary_subtag() {
+    assert_eq!(LanguageModel::train("heb", HEBREW).language(), "he");
+    assert_eq!(LanguageModel::train("he-IL", HEBREW).language(), "he");
+    assert_eq!(LanguageModel::train("en_US", ENGLISH).language(), "en");
+}
+
+#[test]
+fn test_models_lookup_by_tag_or_iso639() {
+    let mut models = LanguageModels::new();
+    assert!(models.is_empty());
+    models.insert(LanguageModel::train("he", HEBREW));
+    models.insert(LanguageModel::train("en", ENGLISH));
+    assert_eq!(models.get("heb").unwrap().language(), "he");
+    assert_eq!(models.get("en-GB").unwrap().language(), "en");
+    assert!(models.get("ru").is_none());
+}
+
+#[test]
+fn test_models_insert_replaces_same_language() {
+    let mut models = LanguageModels::new();
+    models.insert(LanguageModel::train("en", "abc"));
+    models.insert(LanguageModel::train("eng", ENGLISH));
+    assert_eq!(models.languages().collect::<Vec<_>>(), ["en"]);
+    assert!(models.get("en").unwrap().log_likelihood("hello") > -20.0);
+}
+
+#[test]
+fn test_models_for_layout() {
+    let mut models = LanguageModels::new();
+    models.insert(LanguageModel::train("he", HEBREW));
+    let layout = KeyboardLayout {
+        id: LayoutId::new("il"),
+        languages: vec!["heb".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(models.for_layout(&layout).unwrap().language(), "he");
+
+    let tagged = KeyboardLayout {
+        language_tag: Some("he-IL".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(models.for_layout(&tagged).unwrap().language(), "he");
+    assert!(models.for_layout(&KeyboardLayout::default()).is_none());
+}
+
+#[cfg(feature = "language-models")]
+#[test]
+fn test_bundled_models() {
+    let models = LanguageModels::bundled();
+    for language in [
+        "en", "he", "ru", "uk", "ar", "fa", "de", "fr", "es", "el", "tr", "th",
+    ] {
+        assert!(models.get(language).is_some(), "{language}");
+    }
+    let english = models.get("en").unwrap();
+    let hebrew = models.get("he").unwrap();
+    assert!(hebrew.log_likelihood("שלום") > english.log_likelihood("akuo"));
+}
@@ -1,115 +1,98 @@
 #![cfg(target_os = "linux")]
-use xkbcommon::xkb::{CONTEXT_NO_FLAGS, Context, KEYMAP_COMPILE_NO_FLAGS, Keymap, State};
-
-use quick_xml::de::from_str;
-use serde::Deserialize;
-use std::process::Command;
+use xkbcommon::xkb::{
+    self, CONTEXT_NO_FLAGS, Context, KEYMAP_COMPILE_NO_FLAGS, Keymap, Keysym, ModMask, State,
+    compose,
+};
 
+use super::language;
+use super::physical_key::PhysicalKey;
 use super::remap::is_rtl_char;
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
-use std::collections::HashMap;
-use std::fs;
-
-const XML_PATH: &str = "/usr/share/X11/xkb/rules/evdev.xml";
-#[derive(Debug, Deserialize)]
-struct XkbConfigRegistry {
-    #[serde(rename = "layoutList")]
-    layout_list: LayoutList,
-}
-
-#[derive(Debug, Deserialize)]
-struct LayoutList {
-    #[serde(rename = "layout", default)]
-    layouts: Vec<Layout>,
-}
-
-#[derive(Debug, Deserialize)]
-struct Layout {
-    #[serde(rename = "configItem")]
-    config_item: ConfigItem,
-}
+use super::types::{
+    DeadKey, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutId, LayoutMap, ShiftLevel,
+};
+use super::xkb_registry::{XkbLayoutSpec, XkbRegistry};
+use crate::error::LayoutError;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::ffi::OsStr;
+use std::ops::RangeInclusive;
 
-#[derive(Debug, Deserialize)]
-struct ConfigItem {
-    name: String,
-}
-
-fn get_registry_from_xml() -> Result<XkbConfigRegistry, quick_xml::DeError> {
-    let xml_data = fs::read_to_string(XML_PATH).unwrap_or(String::from(""));
-
-    let registry: XkbConfigRegistry = from_str(&xml_data)?;
-    Ok(registry)
-}
-
-fn get_locale_layout_and_variant_strs(registry: XkbConfigRegistry) -> String {
-    let output = Command::new("locale")
-        .arg("-a")
-        .output()
-        .expect("Failed to run localectl");
-    let installed_locales_str: String = String::from_utf8_lossy(&output.stdout).to_string();
-    let installed_locales: Vec<String> = installed_locales_str
-        .split("\n")
-        .map(|s| s.to_string())
-        .collect();
-    let active_countries: Vec<String> = installed_locales
-        .iter()
-        .filter_map(|loc| {
-            if loc.starts_with("C") || loc.starts_with("POSIX") {
-                None
-            } else {
-                let parts: Vec<&str> = loc.split(&['_', '.'][..]).collect();
-                if parts.len() >= 2 {
-                    Some(parts[1].to_lowercase())
-                } else {
-                    None
-                }
-            }
-        })
-        .collect();
-    let filtered_layouts: Vec<&Layout> = registry
-        .layout_list
-        .layouts
-        .iter()
-        .filter(|layout| active_countries.contains(&layout.config_item.name.to_lowercase()))
-        .collect();
+mod active;
+mod keymap_file;
+mod sources;
+mod watch;
+mod x11;
 
-    let layout_string: String = filtered_layouts
-        .iter()
-        .map(|layout| layout.config_item.name.clone())
-        .collect::<Vec<_>>()
-        .join(", ");
-    layout_string
-}
+pub use keymap_file::layout_maps_from_xkb_keymap;
+pub use sources::{ConfiguredLayouts, LayoutSource, configured_layouts};
+pub use watch::watch_layout_changes;
 
-fn get_keymap() -> Result<Option<Keymap>, quick_xml::DeError> {
-    let registry: XkbConfigRegistry = get_registry_from_xml()?;
-    let layout_str = get_locale_layout_and_variant_strs(registry);
+/// Compiles `layouts` into one keymap, one group per layout.
+fn compile_keymap(layouts: &[XkbLayoutSpec]) -> Result<Keymap, LayoutError> {
+    let join =
+        |part: fn(&XkbLayoutSpec) -> &str| layouts.iter().map(part).collect::<Vec<_>>().join(",");
+    let layout_str = join(|spec| &spec.layout);
+    let variant_str = join(|spec| spec.variant.as_deref().unwrap_or(""));
     let context = Context::new(CONTEXT_NO_FLAGS);
-    let keymap = Keymap::new_from_names(
+    Keymap::new_from_names(
         &context,
         "",
         "",
         &layout_str,
-        "",
+        &variant_str,
         None::<String>,
         KEYMAP_COMPILE_NO_FLAGS,
-    );
-    Ok(keymap)
+    )
+    .ok_or_else(|| LayoutError::KeymapCompile {
+        layouts: layouts
+            .iter()
+            .map(XkbLayoutSpec::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    })
 }
-pub fn get_layout(index: u32) -> Option<KeyboardLayout> {
-    let keymap = get_keymap().expect("failed to get key map")?;
-    if index >= keymap.num_layouts() {
-        return None;
-    }
 
+/// Describes group `index` of `keymap`, compiled from `spec`. Names and
+/// languages come from `registry` when it knows the layout, otherwise from
+/// the group name baked into the keymap.
+fn layout_from_keymap(
+    keymap: &Keymap,
+    index: u32,
+    spec: &XkbLayoutSpec,
+    registry: &XkbRegistry,
+) -> KeyboardLayout {
     let name = keymap.layout_get_name(index).to_string();
     let lang_name = if name.is_empty() {
-        index.to_string()
+        spec.to_string()
     } else {
-        name.clone()
+        name
     };
+    let entry = registry.describe(spec);
+    let description = entry.as_ref().map_or_else(
+        || lang_name.clone(),
+        |entry| entry.description().to_string(),
+    );
+    let languages = entry
+        .as_ref()
+        .map(|entry| entry.languages().to_vec())
+        .unwrap_or_default();
+    let language_tag = languages.first().map(|language| {
+        let region = entry.as_ref().and_then(|entry| entry.countries().first());
+        language::language_tag(language, region.map(String::as_str))
+    });
+    let mut scripts: Vec<String> = Vec::new();
+    for script in languages.iter().filter_map(|l| language::default_script(l)) {
+        if !scripts.iter().any(|known| known == script) {
+            scripts.push(script.to_string());
+        }
+    }
+    let short_label = entry
+        .as_ref()
+        .and_then(|entry| entry.short_description())
+        .unwrap_or(&spec.layout)
+        .to_uppercase();
 
-    let mut state = State::new(&keymap);
+    let mut state = State::new(keymap);
     state.update_mask(0, 0, 0, index, 0, 0);
     let mut dir = KeyboardDirection::LTR;
     for keycode in 8u16..=255u16 {
@@ -121,64 +104,301 @@ pub fn get_layout(index: u32) -> Option<KeyboardLayout> {
             break;
         }
     }
-    Some(KeyboardLayout {
+    KeyboardLayout {
+        id: spec.id(),
         lang_name,
         direction: dir,
-    })
+        variant: spec.variant.clone(),
+        description,
+        languages,
+        language_tag,
+        scripts,
+        short_label,
+        native_id: spec.to_string(),
+    }
+}
+
+fn mod_mask(keymap: &Keymap, name: &str) -> ModMask {
+    let index = keymap.mod_get_index(name);
+    if index == xkb::MOD_INVALID {
+        0
+    } else {
+        1 << index
+    }
+}
+
+/// Depressed and locked modifier masks that select `level` in `keymap`.
+fn level_mods(keymap: &Keymap, level: ShiftLevel) -> (ModMask, ModMask) {
+    let shift = mod_mask(keymap, xkb::MOD_NAME_SHIFT);
+    let altgr = mod_mask(keymap, xkb::MOD_NAME_ISO_LEVEL3_SHIFT);
+    let caps = mod_mask(keymap, xkb::MOD_NAME_CAPS);
+    match level {
+        ShiftLevel::Base => (0, 0),
+        ShiftLevel::Shift => (shift, 0),
+        ShiftLevel::AltGr => (altgr, 0),
+        ShiftLevel::ShiftAltGr => (shift | altgr, 0),
+        ShiftLevel::Caps => (0, caps),
+    }
 }
 
-pub fn list_layouts() -> Vec<KeyboardLayout> {
-    let Some(keymap) = get_keymap().expect("failed to get key map") else {
-        return vec![KeyboardLayout {
-            lang_name: String::from("current"),
-            direction: KeyboardDirection::LTR,
-        }];
+/// Keysyms `XK_dead_grave` through `XK_dead_longsolidusoverlay`.
+const DEAD_KEYSYMS: RangeInclusive<u32> = 0xfe50..=0xfe93;
+
+/// Compose table of the user's locale, which defines what dead keys combine
+/// into. The C locale has almost no sequences, so it is replaced by
+/// `en_US.UTF-8`.
+fn compose_table() -> Option<compose::Table> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .filter(|locale| locale != "C" && locale != "POSIX")
+        .unwrap_or_else(|| "en_US.UTF-8".to_string());
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    compose::Table::new_from_locale(&context, OsStr::new(&locale), compose::COMPILE_NO_FLAGS).ok()
+}
+
+fn compose_pair(table: &compose::Table, dead: Keysym, next: Keysym) -> Option<String> {
+    let mut state = compose::State::new(table, compose::STATE_NO_FLAGS);
+    state.feed(dead);
+    state.feed(next);
+    if state.status() != compose::Status::Composed {
+        return None;
+    }
+    state.utf8().filter(|output| !output.is_empty())
+}
+
+/// Builds the [`DeadKey`] for `dead`, combining it with every character in
+/// `bases` that `table` has a sequence for.
+fn dead_key_from_keysym(
+    dead: Keysym,
+    table: Option<&compose::Table>,
+    bases: &BTreeSet<char>,
+) -> DeadKey {
+    let Some(table) = table else {
+        return DeadKey::default();
     };
-    let mut result = Vec::new();
-    for i in 0..keymap.num_layouts() {
-        if let Some(l) = get_layout(i) {
-            result.push(l);
+    let accent = compose_pair(table, dead, xkb::utf32_to_keysym(' ' as u32)).unwrap_or_default();
+    let combinations = bases
+        .iter()
+        .filter_map(|&base| {
+            let composed = compose_pair(table, dead, xkb::utf32_to_keysym(base as u32))?;
+            let mut chars = composed.chars();
+            let unchanged = chars.next() == Some(base) && chars.next().is_none();
+            (!unchanged).then_some((base, composed))
+        })
+        .collect();
+    DeadKey {
+        accent,
+        combinations,
+    }
+}
+
+fn layout_map_from_keymap(
+    keymap: &Keymap,
+    index: u32,
+    layout: KeyboardLayout,
+    compose: Option<&compose::Table>,
+) -> LayoutMap {
+    let mut state = State::new(keymap);
+    let mut layout_map = LayoutMap {
+        layout,
+        map: HashMap::new(),
+        dead_keys: HashMap::new(),
+    };
+    let mut dead_strokes = Vec::new();
+    for level in ShiftLevel::ALL {
+        let (depressed, locked) = level_mods(keymap, level);
+        state.update_mask(depressed, 0, locked, index, 0, 0);
+        for key in PhysicalKey::ALL {
+            let Some(keycode) = keymap.key_by_name(key.xkb_name()) else {
+                continue;
+            };
+            let stroke = KeyStroke::new(key, level);
+            let keysym = state.key_get_one_sym(keycode);
+            if DEAD_KEYSYMS.contains(&keysym.raw()) {
+                dead_strokes.push((stroke, keysym));
+                continue;
+            }
+            let s = state.key_get_utf8(keycode);
+            if !s.is_empty() {
+                layout_map.insert_level(stroke, s);
+            }
         }
     }
-    if result.is_empty() {
-        result.push(KeyboardLayout {
-            lang_name: String::from("current"),
-            direction: KeyboardDirection::LTR,
-        });
+
+    let bases: BTreeSet<char> = layout_map
+        .map
+        .values()
+        .filter_map(|output| {
+            let mut chars = output.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        })
+        .collect();
+    for (stroke, keysym) in dead_strokes {
+        let dead_key = dead_key_from_keysym(keysym, compose, &bases);
+        layout_map.insert_dead_key(stroke, dead_key);
     }
-    result
+    layout_map
 }
 
-pub fn vk_to_char_map_for_layout(layout_index: u32) -> LayoutMap {
-    let layout = get_layout(layout_index).unwrap_or(KeyboardLayout {
-        lang_name: layout_index.to_string(),
-        direction: KeyboardDirection::LTR,
-    });
-    let Some(keymap) = get_keymap().expect("failed to get key map") else {
-        return LayoutMap {
-            layout,
-            map: HashMap::new(),
+/// Handle over the configured layouts that compiles them once.
+///
+/// The layout configuration, the keymap and every [`LayoutMap`] are built on
+/// first use and reused by later calls until [`KeyboardContext::invalidate`]
+/// is called, e.g. after the user changed their layout configuration.
+#[derive(Default)]
+pub struct KeyboardContext {
+    configured: Option<ConfiguredLayouts>,
+    registry: Option<XkbRegistry>,
+    keymap: Option<Keymap>,
+    maps: Option<Vec<LayoutMap>>,
+}
+
+impl KeyboardContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops everything cached so the next call re-reads the configuration.
+    pub fn invalidate(&mut self) {
+        self.configured = None;
+        self.registry = None;
+        self.keymap = None;
+        self.maps = None;
+    }
+
+    /// The configured layouts and the source they were read from.
+    pub fn configured_layouts(&mut self) -> &ConfiguredLayouts {
+        self.configured.get_or_insert_with(configured_layouts)
+    }
+
+    /// The system XKB registry. An unreadable registry is treated as empty,
+    /// so layouts are then described by their keymap names only.
+    pub fn registry(&mut self) -> &XkbRegistry {
+        self.registry
+            .get_or_insert_with(|| XkbRegistry::load().unwrap_or_default())
+    }
+
+    fn keymap(&mut self) -> Result<Keymap, LayoutError> {
+        let keymap = match self.keymap.take() {
+            Some(keymap) => keymap,
+            None => compile_keymap(&self.configured_layouts().layouts)?,
         };
-    };
-    let mut state = State::new(&keymap);
-    state.update_mask(0, 0, 0, layout_index, 0, 0);
-    let mut map: HashMap<u16, String> = HashMap::new();
-    for keycode in 8u16..=255u16 {
-        let s = state.key_get_utf8(keycode.into());
-        if !s.is_empty() {
-            map.entry(keycode).or_insert(s);
+        Ok(self.keymap.insert(keymap).clone())
+    }
+
+    pub fn all_layout_vk_maps(&mut self) -> Result<&[LayoutMap], LayoutError> {
+        let maps = match self.maps.take() {
+            Some(maps) => maps,
+            None => {
+                let keymap = self.keymap()?;
+                let compose = compose_table();
+                let layouts = self.configured_layouts().layouts.clone();
+                let registry = self.registry();
+                layouts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, spec)| {
+                        let layout = layout_from_keymap(&keymap, index as u32, spec, registry);
+                        layout_map_from_keymap(&keymap, index as u32, layout, compose.as_ref())
+                    })
+                    .collect()
+            }
+        };
+        Ok(self.maps.insert(maps))
+    }
+
+    pub fn list_layouts(&mut self) -> Result<Vec<KeyboardLayout>, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        Ok(maps.iter().map(|map| map.layout.clone()).collect())
+    }
+
+    pub fn get_layout(&mut self, id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        Ok(maps
+            .iter()
+            .find(|map| &map.layout.id == id)
+            .map(|map| map.layout.clone()))
+    }
+
+    pub fn vk_to_char_map_for_layout(&mut self, id: &LayoutId) -> Result<&LayoutMap, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        maps.iter()
+            .find(|map| &map.layout.id == id)
+            .ok_or_else(|| LayoutError::LayoutNotFound { id: id.clone() })
+    }
+
+    /// Id of the layout that is active right now. The active layout is read
+    /// on every call; only the configured list it is looked up in is cached.
+    pub fn current_layout(&mut self) -> Result<LayoutId, LayoutError> {
+        let configured = &self.configured_layouts().layouts;
+        active::active_layout(configured)
+            .map(|spec| spec.id())
+            .ok_or(LayoutError::NoActiveLayout)
+    }
+
+    /// Makes `id` the active layout and returns the layout active before,
+    /// which can be passed back here to undo the switch. If the switch had to
+    /// reload the keymap, everything cached is dropped.
+    pub fn activate_layout(&mut self, id: &LayoutId) -> Result<LayoutId, LayoutError> {
+        let target = XkbLayoutSpec::parse(id.as_str())
+            .ok_or_else(|| LayoutError::LayoutNotFound { id: id.clone() })?;
+        let configured = self.configured_layouts().layouts.clone();
+        let (previous, activation) = active::activate(&target, &configured)?;
+        if activation == active::Activation::Reloaded {
+            self.invalidate();
         }
+        Ok(previous.id())
     }
-    LayoutMap { layout, map }
+
+    /// Map of the active layout, or of the first configured layout when the
+    /// active one cannot be told or is not among the configured layouts. The
+    /// first layout is the group a fresh keymap starts in.
+    pub fn vk_to_char_map_default(&mut self) -> Result<&LayoutMap, LayoutError> {
+        let current = match self.current_layout() {
+            Ok(current) => Some(current),
+            Err(LayoutError::NoActiveLayout) => None,
+            Err(err) => return Err(err),
+        };
+        let maps = self.all_layout_vk_maps()?;
+        maps.iter()
+            .find(|map| Some(&map.layout.id) == current.as_ref())
+            .or_else(|| maps.first())
+            .ok_or(LayoutError::NoActiveLayout)
+    }
+}
+
+pub fn activate_layout(id: &LayoutId) -> Result<LayoutId, LayoutError> {
+    KeyboardContext::new().activate_layout(id)
+}
+
+pub fn current_layout() -> Result<LayoutId, LayoutError> {
+    KeyboardContext::new().current_layout()
+}
+
+pub fn get_layout(id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+    KeyboardContext::new().get_layout(id)
+}
+
+pub fn list_layouts() -> Result<Vec<KeyboardLayout>, LayoutError> {
+    KeyboardContext::new().list_layouts()
+}
+
+pub fn vk_to_char_map_for_layout(id: &LayoutId) -> Result<LayoutMap, LayoutError> {
+    KeyboardContext::new()
+        .vk_to_char_map_for_layout(id)
+        .cloned()
 }
 
-pub fn vk_to_char_map_default() -> LayoutMap {
-    vk_to_char_map_for_layout(0)
+pub fn vk_to_char_map_default() -> Result<LayoutMap, LayoutError> {
+    KeyboardContext::new().vk_to_char_map_default().cloned()
 }
 
-pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
-    let total = list_layouts().len() as u32;
-    (0..total).map(vk_to_char_map_for_layout).collect()
+pub fn all_layout_vk_maps() -> Result<Vec<LayoutMap>, LayoutError> {
+    KeyboardContext::new()
+        .all_layout_vk_maps()
+        .map(<[LayoutMap]>::to_vec)
 }
 
 #[cfg(all(test, target_os = "linux"))]
@@ -0,0 +1,163 @@
+use std::env;
+use std::process::Command;
+
+use super::sources::{self, parse_gnome_sources, tool_stdout};
+use super::x11::XDisplay;
+use crate::error::LayoutError;
+use crate::keyboard_mapping::xkb_registry::XkbLayoutSpec;
+
+type ActiveReader = fn(&[XkbLayoutSpec]) -> Option<XkbLayoutSpec>;
+
+/// Works out which of `configured` is active right now.
+///
+/// X11 sessions report the XKB group of the core keyboard, which indexes the
+/// X server's own layout list. Wayland compositors expose no common
+/// protocol, so GNOME's most-recently-used input source and KDE's
+/// `org.kde.KeyboardLayouts` service are asked instead. Returns `None` when
+/// nothing answers.
+pub(crate) fn active_layout(configured: &[XkbLayoutSpec]) -> Option<XkbLayoutSpec> {
+    let readers: [ActiveReader; 3] = [from_x11, from_gnome, from_kde];
+    readers.iter().find_map(|read| read(configured))
+}
+
+fn from_x11(configured: &[XkbLayoutSpec]) -> Option<XkbLayoutSpec> {
+    if is_wayland() {
+        return None;
+    }
+    let group = XDisplay::open()?.group()? as usize;
+    let server_layouts = sources::from_setxkbmap().filter(|layouts| !layouts.is_empty());
+    server_layouts
+        .as_deref()
+        .unwrap_or(configured)
+        .get(group)
+        .cloned()
+}
+
+fn from_gnome(_: &[XkbLayoutSpec]) -> Option<XkbLayoutSpec> {
+    let out = tool_stdout(
+        "gsettings",
+        &["get", "org.gnome.desktop.input-sources", "mru-sources"],
+    )?;
+    parse_gnome_sources(&out).into_iter().next()
+}
+
+/// KDE reports an index into its own layout list, which need not match the
+/// configured order, so the layout is looked up in that list.
+fn from_kde(_: &[XkbLayoutSpec]) -> Option<XkbLayoutSpec> {
+    let kde_call = |method: &str| {
+        tool_stdout(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest",
+                "org.kde.keyboard",
+                "--object-path",
+                "/Layouts",
+                "--method",
+                method,
+            ],
+        )
+    };
+    let index = parse_gdbus_uint(&kde_call("org.kde.KeyboardLayouts.getLayout")?)?;
+    let layouts = parse_kde_layouts(&kde_call("org.kde.KeyboardLayouts.getLayoutsList")?);
+    layouts.into_iter().nth(index as usize)
+}
+
+/// How [`activate`] switched layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Activation {
+    /// The XKB group was locked; the layout list is unchanged.
+    GroupLocked,
+    /// The keymap was reloaded with the target first; the layout list was
+    /// reordered or extended.
+    Reloaded,
+}
+
+fn is_wayland() -> bool {
+    env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland")
+}
+
+/// Makes `target` the active layout and returns the one active before.
+///
+/// When the X server already has `target` in its layout list, its group is
+/// locked through the XKB extension. Otherwise, or when that fails,
+/// `setxkbmap` reloads the keymap with `target` as the first group. Wayland
+/// compositors accept neither, so sessions there are not supported.
+pub(crate) fn activate(
+    target: &XkbLayoutSpec,
+    configured: &[XkbLayoutSpec],
+) -> Result<(XkbLayoutSpec, Activation), LayoutError> {
+    if is_wayland() {
+        return Err(LayoutError::UnsupportedPlatform {
+            operation: "keyboard_mapping::activate_layout",
+        });
+    }
+    let previous = active_layout(configured).ok_or(LayoutError::NoActiveLayout)?;
+    let server_layouts = sources::from_setxkbmap().unwrap_or_else(|| configured.to_vec());
+    if let Some(group) = server_layouts.iter().position(|spec| spec == target)
+        && let Some(display) = XDisplay::open()
+        && display.lock_group(group as u32)
+    {
+        return Ok((previous, Activation::GroupLocked));
+    }
+
+    let out = Command::new("setxkbmap")
+        .args(setxkbmap_args(target, &server_layouts))
+        .output()
+        .map_err(|err| LayoutError::from_spawn("setxkbmap", err))?;
+    if !out.status.success() {
+        return Err(LayoutError::ToolFailed {
+            tool: "setxkbmap",
+            reason: String::from_utf8_lossy(&out.stderr).trim().to_string(),
+        });
+    }
+    Ok((previous, Activation::Reloaded))
+}
+
+/// `setxkbmap` arguments that load `target` as group 0 followed by the rest
+/// of `layouts`, so that switching back stays possible.
+pub(crate) fn setxkbmap_args(target: &XkbLayoutSpec, layouts: &[XkbLayoutSpec]) -> Vec<String> {
+    let order: Vec<&XkbLayoutSpec> = std::iter::once(target)
+        .chain(layouts.iter().filter(|spec| *spec != target))
+        .collect();
+    let join = |part: fn(&XkbLayoutSpec) -> &str| {
+        order
+            .iter()
+            .map(|spec| part(spec))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    vec![
+        "-layout".to_string(),
+        join(|spec| &spec.layout),
+        "-variant".to_string(),
+        join(|spec| spec.variant.as_deref().unwrap_or("")),
+    ]
+}
+
+/// Parses a single unsigned integer reply such as `(uint32 1,)`.
+pub(crate) fn parse_gdbus_uint(output: &str) -> Option<u32> {
+    let inner = output.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let value = inner.trim_end_matches(',').trim();
+    let value = value.split_once(' ').map_or(value, |(_, number)| number);
+    value.parse().ok()
+}
+
+/// Parses KDE's `getLayoutsList` reply, e.g.
+/// `([('us', '', 'English (US)'), ('il', 'phonetic', 'Hebrew (phonetic)')],)`.
+pub(crate) fn parse_kde_layouts(output: &str) -> Vec<XkbLayoutSpec> {
+    output
+        .split("('")
+        .skip(1)
+        .filter_map(|entry| {
+            let mut fields = entry.splitn(3, "', '");
+            let layout = fields.next()?;
+            let variant = fields.next()?;
+            Some(XkbLayoutSpec::new(layout, Some(variant.to_string())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
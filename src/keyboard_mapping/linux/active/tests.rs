@@ -0,0 +1,38 @@
+use super::*;
+
+#[test]
+fn test_parse_gdbus_uint() {
+    assert_eq!(parse_gdbus_uint("(uint32 1,)\n"), Some(1));
+    assert_eq!(parse_gdbus_uint("(0,)"), Some(0));
+    assert_eq!(parse_gdbus_uint("('us',)"), None);
+    assert_eq!(parse_gdbus_uint(""), None);
+}
+
+#[test]
+fn test_parse_kde_layouts() {
+    let output = "([('us', '', 'English (US)'), ('il', 'phonetic', 'Hebrew (phonetic)')],)\n";
+    assert_eq!(
+        parse_kde_layouts(output),
+        [
+            XkbLayoutSpec::new("us", None),
+            XkbLayoutSpec::parse("il(phonetic)").unwrap(),
+        ]
+    );
+    assert!(parse_kde_layouts("([],)").is_empty());
+}
+
+#[test]
+fn test_setxkbmap_args_put_target_first() {
+    let us = XkbLayoutSpec::new("us", None);
+    let phonetic = XkbLayoutSpec::parse("il(phonetic)").unwrap();
+    let ru = XkbLayoutSpec::new("ru", None);
+
+    assert_eq!(
+        setxkbmap_args(&phonetic, &[us.clone(), phonetic.clone(), ru.clone()]),
+        ["-layout", "il,us,ru", "-variant", "phonetic,,"]
+    );
+    assert_eq!(
+        setxkbmap_args(&ru, &[us]),
+        ["-layout", "ru,us", "-variant", ","]
+    );
+}
@@ -0,0 +1,201 @@
+//! Layout maps from XKB files that are not part of the system rules: full
+//! keymaps such as `xkbcomp -xkb` output, and custom symbols files such as
+//! `~/.config/xkb/symbols/*`.
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use xkbcommon::xkb::{
+    CONTEXT_NO_DEFAULT_INCLUDES, CONTEXT_NO_FLAGS, Context, KEYMAP_COMPILE_NO_FLAGS,
+    KEYMAP_FORMAT_TEXT_V1, Keymap,
+};
+
+use super::{compose_table, layout_from_keymap, layout_map_from_keymap};
+use crate::error::LayoutError;
+use crate::keyboard_mapping::types::LayoutMap;
+use crate::keyboard_mapping::xkb_registry::{XkbLayoutSpec, XkbRegistry};
+
+/// Maps of every group of a keymap in the XKB text format, one per layout.
+///
+/// Layouts are identified by the names in the keymap's `xkb_symbols`
+/// section, e.g. `us` and `il` for `"pc+us+il:2+inet(evdev)"` as written by
+/// `xkbcomp -xkb`. Groups without such a name use their group name instead.
+pub fn layout_maps_from_xkb_keymap(keymap: &str) -> Result<Vec<LayoutMap>, LayoutError> {
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    let compiled = Keymap::new_from_string(
+        &context,
+        keymap.to_string(),
+        KEYMAP_FORMAT_TEXT_V1,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or_else(|| LayoutError::InvalidData {
+        format: "XKB keymap",
+        reason: "xkbcommon could not compile the keymap".to_string(),
+    })?;
+    let specs = symbols_layouts(keymap);
+    Ok(layout_maps(&compiled, |index| {
+        specs.get(index as usize).cloned().flatten()
+    }))
+}
+
+impl LayoutMap {
+    /// Map of the first layout of a keymap in the XKB text format. See
+    /// [`layout_maps_from_xkb_keymap`] for keymaps with several layouts.
+    pub fn from_xkb_keymap(keymap: &str) -> Result<LayoutMap, LayoutError> {
+        layout_maps_from_xkb_keymap(keymap)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| LayoutError::InvalidData {
+                format: "XKB keymap",
+                reason: "the keymap has no layouts".to_string(),
+            })
+    }
+
+    /// Map of the first layout of the XKB keymap file at `path`, e.g. one
+    /// saved with `xkbcomp -xkb $DISPLAY keymap.xkb`.
+    pub fn from_xkb_keymap_file(path: impl AsRef<Path>) -> Result<LayoutMap, LayoutError> {
+        Self::from_xkb_keymap(&read(path.as_ref())?)
+    }
+
+    /// Map of a layout defined in a custom symbols file, e.g.
+    /// `~/.config/xkb/symbols/custom`, with its default section or the one
+    /// named `variant`.
+    ///
+    /// The file must sit in a directory called `symbols`, as XKB expects;
+    /// the directory above it is searched before the system XKB data, so the
+    /// file may include system layouts and override ones of the same name.
+    /// The layout is compiled on the `pc` model and gets the id
+    /// `name(variant)`, after the file name.
+    pub fn from_xkb_symbols_file(
+        path: impl AsRef<Path>,
+        variant: Option<&str>,
+    ) -> Result<LayoutMap, LayoutError> {
+        let path = path.as_ref();
+        let invalid = |reason: &str| LayoutError::InvalidData {
+            format: "XKB symbols file",
+            reason: format!("{}: {reason}", path.display()),
+        };
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| invalid("the file name is not valid UTF-8"))?;
+        let root = path
+            .parent()
+            .filter(|dir| dir.file_name().is_some_and(|dir| dir == "symbols"))
+            .and_then(Path::parent)
+            .ok_or_else(|| invalid("the file is not in a `symbols` directory"))?;
+        fs::metadata(path).map_err(|err| LayoutError::FileAccess {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+
+        let spec = XkbLayoutSpec::new(name, variant.map(str::to_string));
+        let mut context = Context::new(CONTEXT_NO_DEFAULT_INCLUDES);
+        if !context.include_path_append(root) || !context.include_path_append_default() {
+            return Err(invalid("the XKB include path could not be set up"));
+        }
+        let keymap = format!(
+            "xkb_keymap {{\n\
+             \txkb_keycodes {{ include \"evdev+aliases(qwerty)\" }};\n\
+             \txkb_types {{ include \"complete\" }};\n\
+             \txkb_compat {{ include \"complete\" }};\n\
+             \txkb_symbols {{ include \"pc+{spec}\" }};\n\
+             }};\n"
+        );
+        let compiled = Keymap::new_from_string(
+            &context,
+            keymap,
+            KEYMAP_FORMAT_TEXT_V1,
+            KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or_else(|| LayoutError::KeymapCompile {
+            layouts: spec.to_string(),
+        })?;
+        layout_maps(&compiled, |_| Some(spec.clone()))
+            .into_iter()
+            .next()
+            .ok_or_else(|| LayoutError::KeymapCompile {
+                layouts: spec.to_string(),
+            })
+    }
+}
+
+/// The system XKB registry, read on first use. These importers have no
+/// [`KeyboardContext`](super::KeyboardContext) to cache it in, and it does
+/// not change while the process runs.
+fn system_registry() -> &'static XkbRegistry {
+    static REGISTRY: OnceLock<XkbRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| XkbRegistry::load().unwrap_or_default())
+}
+
+/// Maps of every group of `keymap`, identified by `spec_of(group)` or, when
+/// that gives nothing, by the group's name.
+fn layout_maps(keymap: &Keymap, spec_of: impl Fn(u32) -> Option<XkbLayoutSpec>) -> Vec<LayoutMap> {
+    let registry = system_registry();
+    let compose = compose_table();
+    (0..keymap.num_layouts())
+        .map(|index| {
+            let spec = spec_of(index).unwrap_or_else(|| {
+                let name = keymap.layout_get_name(index);
+                let name = if name.is_empty() {
+                    format!("group{}", index + 1)
+                } else {
+                    name.to_string()
+                };
+                XkbLayoutSpec::new(name, None)
+            });
+            let layout = layout_from_keymap(keymap, index, &spec, registry);
+            layout_map_from_keymap(keymap, index, layout, compose.as_ref())
+        })
+        .collect()
+}
+
+/// Layouts named in the `xkb_symbols` section name of a keymap, by group:
+/// `"pc+us+il:2+inet(evdev)"` gives `us` for the first group and `il` for
+/// the second. A part without a group suffix is the first group only when
+/// it comes first after the `pc` model; otherwise it is an option.
+fn symbols_layouts(keymap: &str) -> Vec<Option<XkbLayoutSpec>> {
+    let Some(name) = keymap
+        .split_once("xkb_symbols")
+        .and_then(|(_, rest)| rest.split_once('{'))
+        .and_then(|(header, _)| header.trim().strip_prefix('"'))
+        .and_then(|header| header.strip_suffix('"'))
+    else {
+        return Vec::new();
+    };
+    let mut specs: Vec<Option<XkbLayoutSpec>> = Vec::new();
+    let mut first_seen = false;
+    for part in name.split('+') {
+        if part == "pc" || part.starts_with("pc(") {
+            continue;
+        }
+        let first = !first_seen;
+        first_seen = true;
+        let (part, group) = match part.split_once(':') {
+            Some((part, group)) => match group.parse::<usize>() {
+                Ok(group) if group > 0 => (part, group),
+                _ => continue,
+            },
+            None if first => (part, 1),
+            None => continue,
+        };
+        if let Some(spec) = XkbLayoutSpec::parse(part) {
+            if specs.len() < group {
+                specs.resize(group, None);
+            }
+            specs[group - 1] = Some(spec);
+        }
+    }
+    specs
+}
+
+fn read(path: &Path) -> Result<String, LayoutError> {
+    fs::read_to_string(path).map_err(|err| LayoutError::FileAccess {
+        path: path.to_path_buf(),
+        reason: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,163 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::temp_path;
+use crate::keyboard_mapping::types::{KeyStroke, KeyboardDirection, LayoutId, ShiftLevel};
+
+/// A keymap as `xkbcomp -xkb` names it, with the sections pulled in from the
+/// system XKB data.
+const US_IL_KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "evdev+aliases(qwerty)" { include "evdev+aliases(qwerty)" };
+    xkb_types "complete" { include "complete" };
+    xkb_compatibility "complete" { include "complete" };
+    xkb_symbols "pc+us+il:2+inet(evdev)" { include "pc+us+il:2+inet(evdev)" };
+};
+"#;
+
+const CUSTOM_SYMBOLS: &str = r#"default partial alphanumeric_keys
+xkb_symbols "basic" {
+    include "us(basic)"
+    name[Group1] = "Custom";
+    key <AC01> { [ ssharp, section ] };
+};
+
+partial alphanumeric_keys
+xkb_symbols "hebrew" {
+    include "il(basic)"
+    name[Group1] = "Custom Hebrew";
+    key <AC01> { [ hebrew_aleph ] };
+};
+"#;
+
+fn output(map: &LayoutMap, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+    map.map.get(&KeyStroke::new(key, level)).map(String::as_str)
+}
+
+#[test]
+fn test_layout_maps_from_xkb_keymap() {
+    let maps = layout_maps_from_xkb_keymap(US_IL_KEYMAP).unwrap();
+    assert_eq!(maps.len(), 2);
+    assert_eq!(maps[0].layout.id, LayoutId::new("us"));
+    assert_eq!(maps[1].layout.id, LayoutId::new("il"));
+    assert_eq!(
+        output(&maps[0], PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("a")
+    );
+    assert_eq!(
+        output(&maps[0], PhysicalKey::KeyA, ShiftLevel::Shift),
+        Some("A")
+    );
+    assert_eq!(
+        output(&maps[1], PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+    assert_eq!(maps[1].layout.direction, KeyboardDirection::RTL);
+}
+
+#[test]
+fn test_from_xkb_keymap_without_layout_names() {
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    let keymap = Keymap::new_from_names(
+        &context,
+        "",
+        "",
+        "us",
+        "",
+        None::<String>,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .unwrap();
+    let map = LayoutMap::from_xkb_keymap(&keymap.get_as_string(KEYMAP_FORMAT_TEXT_V1)).unwrap();
+    assert_eq!(map.layout.id, LayoutId::new("English (US)"));
+    assert_eq!(output(&map, PhysicalKey::KeyQ, ShiftLevel::Base), Some("q"));
+}
+
+#[test]
+fn test_from_xkb_keymap_file() {
+    let path = temp_path("xkb-keymap.xkb");
+    fs::write(&path, US_IL_KEYMAP).unwrap();
+    let map = LayoutMap::from_xkb_keymap_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(map.layout.id, LayoutId::new("us"));
+
+    assert!(matches!(
+        LayoutMap::from_xkb_keymap_file(&path),
+        Err(LayoutError::FileAccess { .. })
+    ));
+}
+
+#[test]
+fn test_from_xkb_keymap_rejects_invalid_text() {
+    assert!(matches!(
+        LayoutMap::from_xkb_keymap("xkb_keymap { nonsense"),
+        Err(LayoutError::InvalidData { .. })
+    ));
+}
+
+#[test]
+fn test_from_xkb_symbols_file() {
+    let root = temp_path("xkb-config");
+    let path = root.join("symbols").join("custom");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, CUSTOM_SYMBOLS).unwrap();
+
+    let basic = LayoutMap::from_xkb_symbols_file(&path, None);
+    let hebrew = LayoutMap::from_xkb_symbols_file(&path, Some("hebrew"));
+    fs::remove_dir_all(&root).unwrap();
+
+    let basic = basic.unwrap();
+    assert_eq!(basic.layout.id, LayoutId::new("custom"));
+    assert_eq!(basic.layout.lang_name, "Custom");
+    assert_eq!(
+        output(&basic, PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ß")
+    );
+    assert_eq!(
+        output(&basic, PhysicalKey::KeyA, ShiftLevel::Shift),
+        Some("§")
+    );
+    assert_eq!(
+        output(&basic, PhysicalKey::KeyS, ShiftLevel::Base),
+        Some("s")
+    );
+
+    let hebrew = hebrew.unwrap();
+    assert_eq!(hebrew.layout.id, LayoutId::new("custom(hebrew)"));
+    assert_eq!(
+        output(&hebrew, PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("א")
+    );
+    assert_eq!(hebrew.layout.direction, KeyboardDirection::RTL);
+}
+
+#[test]
+fn test_from_xkb_symbols_file_errors() {
+    assert!(matches!(
+        LayoutMap::from_xkb_symbols_file(temp_path("xkb-custom"), None),
+        Err(LayoutError::InvalidData { .. })
+    ));
+    let missing = temp_path("xkb-missing").join("symbols").join("custom");
+    assert!(matches!(
+        LayoutMap::from_xkb_symbols_file(&missing, None),
+        Err(LayoutError::FileAccess { .. })
+    ));
+}
+
+#[test]
+fn test_symbols_layouts() {
+    let layouts = |name: &str| {
+        symbols_layouts(&format!("xkb_symbols \"{name}\" {{ }};"))
+            .into_iter()
+            .map(|spec| spec.map(|spec| spec.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        layouts("pc+us+il(phonetic):2+inet(evdev)+group(alt_shift_toggle)"),
+        [Some("us".to_string()), Some("il(phonetic)".to_string())]
+    );
+    assert_eq!(
+        layouts("pc+ru:3+inet(evdev)"),
+        [None, None, Some("ru".to_string())]
+    );
+    assert!(layouts("(unnamed)").is_empty());
+    assert!(symbols_layouts("xkb_symbols { };").is_empty());
+}
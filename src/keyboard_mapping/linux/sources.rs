@@ -0,0 +1,221 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::keyboard_mapping::xkb_registry::XkbLayoutSpec;
+
+pub(super) const DEFAULT_KEYBOARD_PATH: &str = "/etc/default/keyboard";
+const DEFAULT_LAYOUT: &str = "us";
+
+/// Where the configured layout list was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutSource {
+    /// `XKB_DEFAULT_LAYOUT` / `XKB_DEFAULT_VARIANT`.
+    Environment,
+    /// `setxkbmap -query` against the running X server.
+    Setxkbmap,
+    /// `/etc/default/keyboard` (Debian and derivatives).
+    DefaultKeyboard,
+    /// `localectl status`, i.e. org.freedesktop.locale1.
+    Localectl,
+    /// GNOME `org.gnome.desktop.input-sources sources`.
+    Gnome,
+    /// KDE `kxkbrc`.
+    Kde,
+    /// Nothing was configured; the XKB default layout is used.
+    Default,
+}
+
+/// The user's configured layouts, in group order, and the source they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfiguredLayouts {
+    pub source: LayoutSource,
+    pub layouts: Vec<XkbLayoutSpec>,
+}
+
+/// Reads the configured layouts, trying each [`LayoutSource`] in turn and
+/// returning the first one that yields at least one layout.
+///
+/// In X11 sessions the sources are tried in declaration order. Wayland
+/// sessions keep their layouts in the desktop settings, so GNOME and KDE are
+/// tried right after the environment there, ahead of the system-wide files,
+/// and `setxkbmap` is skipped: it only reports the Xwayland keymap rather
+/// than the compositor's.
+pub fn configured_layouts() -> ConfiguredLayouts {
+    let wayland = env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland");
+    for &source in source_order(wayland) {
+        if let Some(layouts) = read_source(source).filter(|layouts| !layouts.is_empty()) {
+            return ConfiguredLayouts { source, layouts };
+        }
+    }
+    ConfiguredLayouts {
+        source: LayoutSource::Default,
+        layouts: vec![XkbLayoutSpec::new(DEFAULT_LAYOUT, None)],
+    }
+}
+
+/// Sources [`configured_layouts`] tries, in order.
+fn source_order(wayland: bool) -> &'static [LayoutSource] {
+    use LayoutSource::*;
+    if wayland {
+        &[Environment, Gnome, Kde, DefaultKeyboard, Localectl]
+    } else {
+        &[
+            Environment,
+            Setxkbmap,
+            DefaultKeyboard,
+            Localectl,
+            Gnome,
+            Kde,
+        ]
+    }
+}
+
+fn read_source(source: LayoutSource) -> Option<Vec<XkbLayoutSpec>> {
+    match source {
+        LayoutSource::Environment => from_environment(),
+        LayoutSource::Setxkbmap => from_setxkbmap(),
+        LayoutSource::DefaultKeyboard => from_default_keyboard(),
+        LayoutSource::Localectl => from_localectl(),
+        LayoutSource::Gnome => from_gnome(),
+        LayoutSource::Kde => from_kde(),
+        LayoutSource::Default => None,
+    }
+}
+
+pub(super) fn tool_stdout(tool: &str, args: &[&str]) -> Option<String> {
+    let out = Command::new(tool).args(args).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+fn from_environment() -> Option<Vec<XkbLayoutSpec>> {
+    let layouts = env::var("XKB_DEFAULT_LAYOUT").ok()?;
+    let variants = env::var("XKB_DEFAULT_VARIANT").unwrap_or_default();
+    Some(parse_layout_lists(&layouts, &variants))
+}
+
+pub(super) fn from_setxkbmap() -> Option<Vec<XkbLayoutSpec>> {
+    if env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland") {
+        return None;
+    }
+    parse_setxkbmap_query(&tool_stdout("setxkbmap", &["-query"])?)
+}
+
+fn from_default_keyboard() -> Option<Vec<XkbLayoutSpec>> {
+    parse_default_keyboard(&fs::read_to_string(DEFAULT_KEYBOARD_PATH).ok()?)
+}
+
+fn from_localectl() -> Option<Vec<XkbLayoutSpec>> {
+    parse_localectl_status(&tool_stdout("localectl", &["status"])?)
+}
+
+fn from_gnome() -> Option<Vec<XkbLayoutSpec>> {
+    let out = tool_stdout(
+        "gsettings",
+        &["get", "org.gnome.desktop.input-sources", "sources"],
+    )?;
+    Some(parse_gnome_sources(&out))
+}
+
+fn from_kde() -> Option<Vec<XkbLayoutSpec>> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    parse_kxkbrc(&fs::read_to_string(config_home.join("kxkbrc")).ok()?)
+}
+
+/// Pairs comma-separated layout and variant lists, as used by every source.
+pub(crate) fn parse_layout_lists(layouts: &str, variants: &str) -> Vec<XkbLayoutSpec> {
+    let mut variants = variants.split(',').map(str::trim);
+    layouts
+        .split(',')
+        .map(str::trim)
+        .map(|layout| (layout, variants.next()))
+        .filter(|(layout, _)| !layout.is_empty())
+        .map(|(layout, variant)| XkbLayoutSpec::new(layout, variant.map(str::to_string)))
+        .collect()
+}
+
+/// Value of the first `key<sep>value` line, with surrounding quotes removed.
+fn find_value<'a>(text: &'a str, key: &str, sep: char) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(sep)?;
+        (name.trim() == key).then(|| value.trim().trim_matches('"'))
+    })
+}
+
+pub(crate) fn parse_setxkbmap_query(output: &str) -> Option<Vec<XkbLayoutSpec>> {
+    let layouts = find_value(output, "layout", ':')?;
+    let variants = find_value(output, "variant", ':').unwrap_or("");
+    Some(parse_layout_lists(layouts, variants))
+}
+
+pub(crate) fn parse_default_keyboard(contents: &str) -> Option<Vec<XkbLayoutSpec>> {
+    let layouts = find_value(contents, "XKBLAYOUT", '=')?;
+    let variants = find_value(contents, "XKBVARIANT", '=').unwrap_or("");
+    Some(parse_layout_lists(layouts, variants))
+}
+
+pub(crate) fn parse_localectl_status(output: &str) -> Option<Vec<XkbLayoutSpec>> {
+    let layouts = find_value(output, "X11 Layout", ':')?;
+    let variants = find_value(output, "X11 Variant", ':').unwrap_or("");
+    Some(parse_layout_lists(layouts, variants))
+}
+
+/// Parses the GVariant text of `input-sources sources`, e.g.
+/// `[('xkb', 'us'), ('xkb', 'il+phonetic'), ('ibus', 'mozc-jp')]`.
+/// Non-XKB input methods are skipped.
+pub(crate) fn parse_gnome_sources(output: &str) -> Vec<XkbLayoutSpec> {
+    output
+        .split('(')
+        .skip(1)
+        .filter_map(|entry| {
+            let entry = entry.split(')').next()?;
+            let (kind, value) = entry.split_once(',')?;
+            if kind.trim().trim_matches('\'') != "xkb" {
+                return None;
+            }
+            let value = value.trim().trim_matches('\'');
+            let (layout, variant) = match value.split_once('+') {
+                Some((layout, variant)) => (layout, Some(variant.to_string())),
+                None => (value, None),
+            };
+            Some(XkbLayoutSpec::new(layout, variant))
+        })
+        .collect()
+}
+
+/// Parses the `[Layout]` group of KDE's `kxkbrc`. Returns `None` when KDE is
+/// not managing layouts (`Use=false`).
+pub(crate) fn parse_kxkbrc(contents: &str) -> Option<Vec<XkbLayoutSpec>> {
+    let mut in_layout_group = false;
+    let mut layouts = None;
+    let mut variants = "";
+    let mut in_use = true;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_layout_group = line == "[Layout]";
+            continue;
+        }
+        if !in_layout_group {
+            continue;
+        }
+        match line.split_once('=') {
+            Some(("LayoutList", value)) => layouts = Some(value),
+            Some(("VariantList", value)) => variants = value,
+            Some(("Use", value)) => in_use = value != "false",
+            _ => {}
+        }
+    }
+    if !in_use {
+        return None;
+    }
+    Some(parse_layout_lists(layouts?, variants))
+}
+
+#[cfg(test)]
+mod tests;
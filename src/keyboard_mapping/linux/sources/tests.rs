@@ -0,0 +1,101 @@
+use super::*;
+
+fn spec(layout: &str, variant: Option<&str>) -> XkbLayoutSpec {
+    XkbLayoutSpec::new(layout, variant.map(str::to_string))
+}
+
+#[test]
+fn test_parse_layout_lists_pairs_variants() {
+    assert_eq!(
+        parse_layout_lists("us,il", ",phonetic"),
+        vec![spec("us", None), spec("il", Some("phonetic"))]
+    );
+    assert_eq!(
+        parse_layout_lists("us, ru", ""),
+        vec![spec("us", None), spec("ru", None)]
+    );
+    assert!(parse_layout_lists("", "").is_empty());
+}
+
+#[test]
+fn test_parse_setxkbmap_query() {
+    let output = "rules:      evdev\nmodel:      pc105\nlayout:     us,il\nvariant:    ,phonetic\noptions:    grp:alt_shift_toggle\n";
+    assert_eq!(
+        parse_setxkbmap_query(output),
+        Some(vec![spec("us", None), spec("il", Some("phonetic"))])
+    );
+    assert_eq!(parse_setxkbmap_query("rules: evdev\n"), None);
+}
+
+#[test]
+fn test_parse_default_keyboard() {
+    let contents = "# KEYBOARD CONFIGURATION FILE\nXKBMODEL=\"pc105\"\nXKBLAYOUT=\"us,il\"\nXKBVARIANT=\",\"\nXKBOPTIONS=\"grp:alt_shift_toggle\"\n";
+    assert_eq!(
+        parse_default_keyboard(contents),
+        Some(vec![spec("us", None), spec("il", None)])
+    );
+}
+
+#[test]
+fn test_parse_localectl_status() {
+    let output = "   System Locale: LANG=en_US.UTF-8\n       VC Keymap: us\n      X11 Layout: us,ru\n       X11 Model: pc105\n     X11 Variant: dvorak,\n";
+    assert_eq!(
+        parse_localectl_status(output),
+        Some(vec![spec("us", Some("dvorak")), spec("ru", None)])
+    );
+    assert_eq!(
+        parse_localectl_status("   System Locale: LANG=C\n       VC Keymap: n/a\n"),
+        None
+    );
+}
+
+#[test]
+fn test_parse_gnome_sources() {
+    let output = "[('xkb', 'us'), ('xkb', 'il+phonetic'), ('ibus', 'mozc-jp')]\n";
+    assert_eq!(
+        parse_gnome_sources(output),
+        vec![spec("us", None), spec("il", Some("phonetic"))]
+    );
+    assert!(parse_gnome_sources("@a(ss) []\n").is_empty());
+}
+
+#[test]
+fn test_parse_kxkbrc() {
+    let contents = "[$Version]\nupdate_info=kxkb.upd:remove-empty-lists\n\n[Layout]\nDisplayNames=,\nLayoutList=us,il\nUse=true\nVariantList=,phonetic\n";
+    assert_eq!(
+        parse_kxkbrc(contents),
+        Some(vec![spec("us", None), spec("il", Some("phonetic"))])
+    );
+}
+
+#[test]
+fn test_parse_kxkbrc_not_in_use() {
+    let contents = "[Layout]\nLayoutList=us,il\nUse=false\n";
+    assert_eq!(parse_kxkbrc(contents), None);
+}
+
+#[test]
+fn test_source_order_prefers_desktop_settings_on_wayland() {
+    use LayoutSource::*;
+    assert_eq!(
+        source_order(false),
+        [
+            Environment,
+            Setxkbmap,
+            DefaultKeyboard,
+            Localectl,
+            Gnome,
+            Kde
+        ]
+    );
+    assert_eq!(
+        source_order(true),
+        [Environment, Gnome, Kde, DefaultKeyboard, Localectl]
+    );
+}
+
+#[test]
+fn test_configured_layouts_never_empty() {
+    let configured = configured_layouts();
+    assert!(!configured.layouts.is_empty());
+}
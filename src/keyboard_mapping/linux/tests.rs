@@ -1,8 +1,10 @@
 use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::ShiftLevel;
 
 #[test]
 fn test_list_layouts_returns_non_empty() {
-    let layouts = list_layouts();
+    let layouts = list_layouts().unwrap();
     assert!(
         !layouts.is_empty(),
         "Linux should have at least one keyboard layout"
@@ -10,13 +12,13 @@ fn test_list_layouts_returns_non_empty() {
 }
 
 #[test]
-fn test_get_layout_valid_index() {
-    let layouts = list_layouts();
-    if !layouts.is_empty() {
-        let layout = get_layout(0);
+fn test_get_layout_listed_id() {
+    let layouts = list_layouts().unwrap();
+    if let Some(first) = layouts.first() {
+        let layout = get_layout(&first.id).unwrap();
         assert!(
             layout.is_some(),
-            "get_layout(0) should return Some for valid index"
+            "get_layout should return Some for a listed id"
         );
 
         let layout = layout.unwrap();
@@ -28,19 +30,17 @@ fn test_get_layout_valid_index() {
 }
 
 #[test]
-fn test_get_layout_invalid_index() {
-    let layouts = list_layouts();
-    let invalid_index = layouts.len() + 100;
-    let layout = get_layout(invalid_index as u32);
+fn test_get_layout_unknown_id() {
+    let layout = get_layout(&LayoutId::new("no-such-layout")).unwrap();
     assert!(
         layout.is_none(),
-        "get_layout with invalid index should return None"
+        "get_layout with an unknown id should return None"
     );
 }
 
 #[test]
 fn test_vk_to_char_map_default_structure() {
-    let layout_map = vk_to_char_map_default();
+    let layout_map = vk_to_char_map_default().unwrap();
 
     assert!(
         !layout_map.layout.lang_name.is_empty(),
@@ -54,33 +54,28 @@ fn test_vk_to_char_map_default_structure() {
 
 #[test]
 fn test_vk_to_char_map_default_contains_common_keys() {
-    let layout_map = vk_to_char_map_default();
+    let layout_map = vk_to_char_map_default().unwrap();
 
     let common_keys = vec![
-        0x26, // KEY_A
-        0x38, // KEY_B
-        0x36, // KEY_C
-        0x39, // KEY_SPACE
-        0x1C, // KEY_ENTER
+        PhysicalKey::KeyA,
+        PhysicalKey::KeyB,
+        PhysicalKey::KeyC,
+        PhysicalKey::Space,
+        PhysicalKey::Enter,
     ];
 
     for key in common_keys {
-        if layout_map.map.contains_key(&key) {
-            let output = &layout_map.map[&key];
-            assert!(
-                !output.is_empty(),
-                "KEY_{:02X} should have non-empty output",
-                key
-            );
+        if let Some(output) = layout_map.get(key, ShiftLevel::Base) {
+            assert!(!output.is_empty(), "{} should have non-empty output", key);
         }
     }
 }
 
 #[test]
 fn test_vk_to_char_map_for_layout_structure() {
-    let layouts = list_layouts();
-    if !layouts.is_empty() {
-        let layout_map = vk_to_char_map_for_layout(0);
+    let layouts = list_layouts().unwrap();
+    if let Some(first) = layouts.first() {
+        let layout_map = vk_to_char_map_for_layout(&first.id).unwrap();
 
         assert!(
             !layout_map.layout.lang_name.is_empty(),
@@ -95,7 +90,7 @@ fn test_vk_to_char_map_for_layout_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_structure() {
-    let layout_maps = all_layout_vk_maps();
+    let layout_maps = all_layout_vk_maps().unwrap();
 
     assert!(
         !layout_maps.is_empty(),
@@ -116,8 +111,8 @@ fn test_all_layout_vk_maps_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_consistency() {
-    let layouts = list_layouts();
-    let layout_maps = all_layout_vk_maps();
+    let layouts = list_layouts().unwrap();
+    let layout_maps = all_layout_vk_maps().unwrap();
 
     assert_eq!(
         layouts.len(),
@@ -125,8 +120,8 @@ fn test_all_layout_vk_maps_consistency() {
         "Number of layouts should match number of layout maps"
     );
 
-    for (i, layout_map) in layout_maps.iter().enumerate() {
-        if let Some(layout) = get_layout(i as u32) {
+    for layout_map in &layout_maps {
+        if let Some(layout) = get_layout(&layout_map.layout.id).unwrap() {
             assert_eq!(
                 layout_map.layout.lang_name, layout.lang_name,
                 "Layout names should match"
@@ -138,3 +133,228 @@ fn test_all_layout_vk_maps_consistency() {
         }
     }
 }
+
+#[test]
+fn test_vk_to_char_map_default_captures_shift_level() {
+    let layout_map = vk_to_char_map_default().unwrap();
+    let key_a = PhysicalKey::KeyA;
+
+    if let (Some(base), Some(shifted)) = (
+        layout_map.get(key_a, ShiftLevel::Base),
+        layout_map.get(key_a, ShiftLevel::Shift),
+    ) {
+        assert_ne!(
+            base, shifted,
+            "Shift level should differ from base for KEY_A"
+        );
+    }
+}
+
+#[test]
+fn test_vk_to_char_map_for_unknown_id() {
+    let id = LayoutId::new("no-such-layout");
+    assert_eq!(
+        vk_to_char_map_for_layout(&id),
+        Err(LayoutError::LayoutNotFound { id })
+    );
+}
+
+#[test]
+fn test_layout_ids_round_trip() {
+    for layout in list_layouts().unwrap() {
+        let restored = LayoutId::new(layout.id.to_string());
+        let map = vk_to_char_map_for_layout(&restored).unwrap();
+        assert_eq!(map.layout.id, layout.id);
+    }
+}
+
+#[test]
+fn test_context_matches_free_functions() {
+    let mut context = KeyboardContext::new();
+    assert_eq!(context.list_layouts().unwrap(), list_layouts().unwrap());
+    assert_eq!(
+        context.all_layout_vk_maps().unwrap(),
+        all_layout_vk_maps().unwrap().as_slice()
+    );
+    assert_eq!(
+        context.vk_to_char_map_default().unwrap(),
+        &vk_to_char_map_default().unwrap()
+    );
+}
+
+#[test]
+fn test_context_caches_until_invalidated() {
+    let mut context = KeyboardContext::new();
+    let first = context.all_layout_vk_maps().unwrap().as_ptr();
+    let second = context.all_layout_vk_maps().unwrap().as_ptr();
+    assert_eq!(first, second, "maps should be built once");
+
+    context.invalidate();
+    assert!(context.maps.is_none());
+    assert!(context.keymap.is_none());
+    assert!(!context.all_layout_vk_maps().unwrap().is_empty());
+}
+
+#[test]
+fn test_context_unknown_id() {
+    let mut context = KeyboardContext::new();
+    let id = LayoutId::new("no-such-layout");
+    assert_eq!(context.get_layout(&id).unwrap(), None);
+    assert_eq!(
+        context.vk_to_char_map_for_layout(&id),
+        Err(LayoutError::LayoutNotFound { id })
+    );
+}
+
+#[test]
+fn test_list_layouts_follows_configured_layouts() {
+    let mut context = KeyboardContext::new();
+    let expected: Vec<LayoutId> = context
+        .configured_layouts()
+        .layouts
+        .iter()
+        .map(XkbLayoutSpec::id)
+        .collect();
+    let ids: Vec<LayoutId> = context
+        .list_layouts()
+        .unwrap()
+        .into_iter()
+        .map(|layout| layout.id)
+        .collect();
+    assert_eq!(ids, expected);
+}
+
+#[test]
+fn test_layout_from_keymap_uses_registry() {
+    let spec = XkbLayoutSpec::parse("il(phonetic)").unwrap();
+    let keymap = compile_keymap(std::slice::from_ref(&spec)).unwrap();
+    let registry = XkbRegistry::from_xml(
+        r#"<xkbConfigRegistry><layoutList><layout>
+            <configItem><name>il</name><shortDescription>he</shortDescription>
+              <description>Hebrew</description>
+              <countryList><iso3166Id>IL</iso3166Id></countryList>
+              <languageList><iso639Id>heb</iso639Id></languageList></configItem>
+            <variantList><variant><configItem><name>phonetic</name>
+              <description>Hebrew (phonetic)</description></configItem></variant></variantList>
+          </layout></layoutList></xkbConfigRegistry>"#,
+    )
+    .unwrap();
+
+    let layout = layout_from_keymap(&keymap, 0, &spec, &registry);
+    assert_eq!(layout.id, LayoutId::new("il(phonetic)"));
+    assert_eq!(layout.variant.as_deref(), Some("phonetic"));
+    assert_eq!(layout.description, "Hebrew (phonetic)");
+    assert_eq!(layout.languages, ["heb"]);
+    assert_eq!(layout.direction, KeyboardDirection::RTL);
+    assert_eq!(layout.language_tag.as_deref(), Some("he-IL"));
+    assert_eq!(layout.scripts, ["Hebr"]);
+    assert_eq!(layout.short_label, "HE");
+    assert_eq!(layout.native_id, "il(phonetic)");
+
+    let unknown = layout_from_keymap(&keymap, 0, &spec, &XkbRegistry::default());
+    assert_eq!(unknown.description, unknown.lang_name);
+    assert!(unknown.languages.is_empty());
+    assert_eq!(unknown.language_tag, None);
+    assert_eq!(unknown.short_label, "IL");
+}
+
+/// Exercises the X11 path when a display is available, e.g. under
+/// `xvfb-run cargo test`.
+#[test]
+fn test_current_layout_follows_x_server_group() {
+    if std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland") {
+        return;
+    }
+    let Some(group) = x11::XDisplay::open().and_then(|display| display.group()) else {
+        return;
+    };
+    let Some(server_layouts) = sources::from_setxkbmap() else {
+        return;
+    };
+    assert_eq!(
+        current_layout().unwrap(),
+        server_layouts[group as usize].id()
+    );
+}
+
+#[test]
+fn test_default_map_is_active_or_first_configured() {
+    let mut context = KeyboardContext::new();
+    let default = context.vk_to_char_map_default().unwrap().layout.id.clone();
+    let first = context.all_layout_vk_maps().unwrap()[0].layout.id.clone();
+    match context.current_layout() {
+        Ok(current) => {
+            let listed = context.get_layout(&current).unwrap().is_some();
+            assert_eq!(default, if listed { current } else { first });
+        }
+        Err(err) => {
+            assert!(matches!(err, LayoutError::NoActiveLayout));
+            assert_eq!(default, first);
+        }
+    }
+}
+
+/// Switches the X server's group and back, so it is ignored by default
+/// rather than risk leaving the developer's own session switched. Run it
+/// against a throwaway server, e.g. `xvfb-run sh -c 'setxkbmap us,il &&
+/// cargo test -- --ignored test_activate_layout_locks_x_server_group'`.
+/// Skipped without a display or a second layout.
+#[test]
+#[ignore = "switches the layout of the X session it runs in"]
+fn test_activate_layout_locks_x_server_group() {
+    if std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland") {
+        return;
+    }
+    if x11::XDisplay::open().is_none() {
+        return;
+    }
+    let Some(server_layouts) = sources::from_setxkbmap().filter(|layouts| layouts.len() > 1) else {
+        return;
+    };
+    let mut context = KeyboardContext::new();
+    let original = context.current_layout().unwrap();
+    let target = server_layouts
+        .iter()
+        .map(XkbLayoutSpec::id)
+        .find(|id| *id != original)
+        .unwrap();
+
+    assert_eq!(context.activate_layout(&target).unwrap(), original);
+    assert_eq!(context.current_layout().unwrap(), target);
+    assert_eq!(context.activate_layout(&original).unwrap(), target);
+}
+
+#[test]
+fn test_activate_layout_rejects_malformed_id() {
+    let id = LayoutId::new("us(dvorak");
+    assert_eq!(
+        activate_layout(&id),
+        Err(LayoutError::LayoutNotFound { id })
+    );
+}
+
+#[test]
+fn test_layout_map_records_dead_keys() {
+    let spec = XkbLayoutSpec::new("es", None);
+    let keymap = compile_keymap(std::slice::from_ref(&spec)).unwrap();
+    let layout = layout_from_keymap(&keymap, 0, &spec, &XkbRegistry::default());
+    let compose = compose_table();
+    let map = layout_map_from_keymap(&keymap, 0, layout, compose.as_ref());
+
+    // The key right of Ñ is dead_acute on the Spanish layout.
+    let acute = KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base);
+    assert!(map.dead_keys.contains_key(&acute));
+    assert!(!map.map.contains_key(&acute));
+
+    if compose.is_some() {
+        let e = KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Base);
+        assert_eq!(map.type_strokes(&[acute, e]).as_deref(), Some("é"));
+        assert_eq!(
+            map.dead_keys[&acute]
+                .combinations
+                .get(&'a')
+                .map(String::as_str),
+            Some("á")
+        );
+    }
+}
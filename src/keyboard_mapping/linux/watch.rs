@@ -0,0 +1,198 @@
+use libc::{
+    IN_CLOEXEC, IN_CLOSE_WRITE, IN_CREATE, IN_DELETE, IN_MOVED_TO, IN_NONBLOCK, POLLIN, c_int,
+    inotify_event, pollfd,
+};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsString};
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::active::active_layout;
+use super::sources::{self, configured_layouts};
+use super::x11::{XDisplay, XkbEvent};
+use crate::error::LayoutError;
+use crate::keyboard_mapping::watch::{EventSink, LayoutEvent, LayoutWatcher, POLL_INTERVAL};
+use crate::keyboard_mapping::xkb_registry::XkbLayoutSpec;
+
+/// Files whose modification can change the configured or active layouts.
+fn config_files() -> Vec<PathBuf> {
+    let mut files = vec![
+        PathBuf::from(sources::DEFAULT_KEYBOARD_PATH),
+        PathBuf::from("/etc/X11/xorg.conf.d/00-keyboard.conf"),
+        PathBuf::from("/etc/vconsole.conf"),
+    ];
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(config_home) = config_home {
+        files.push(config_home.join("kxkbrc"));
+        // GNOME input sources, including the most recently used one.
+        files.push(config_home.join("dconf").join("user"));
+    }
+    files
+}
+
+/// inotify watch on a set of files. The parent directories are watched so
+/// that files replaced by rename, as most editors and dconf do, are noticed.
+pub(crate) struct FileWatcher {
+    fd: c_int,
+    names: HashMap<c_int, Vec<OsString>>,
+}
+
+impl FileWatcher {
+    pub(crate) fn new(files: &[PathBuf]) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut watcher = FileWatcher {
+            fd,
+            names: HashMap::new(),
+        };
+        for file in files {
+            let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+                continue;
+            };
+            if let Some(wd) = watcher.watch_dir(dir) {
+                watcher.names.entry(wd).or_default().push(name.to_owned());
+            }
+        }
+        Ok(watcher)
+    }
+
+    fn watch_dir(&self, dir: &Path) -> Option<c_int> {
+        let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+        let mask = IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE | IN_DELETE;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, dir.as_ptr(), mask) };
+        (wd >= 0).then_some(wd)
+    }
+
+    /// Waits up to `timeout` and reports whether any watched file changed.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let mut fd = pollfd {
+            fd: self.fd,
+            events: POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as c_int) } <= 0 {
+            return false;
+        }
+        // u64 elements keep the buffer aligned for `inotify_event`.
+        let mut buf = [0u64; 512];
+        let mut changed = false;
+        loop {
+            let len =
+                unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), mem::size_of_val(&buf)) };
+            if len <= 0 {
+                return changed;
+            }
+            let bytes =
+                unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len as usize) };
+            changed |= self.matches_any(bytes);
+        }
+    }
+
+    fn matches_any(&self, mut bytes: &[u8]) -> bool {
+        let header = mem::size_of::<inotify_event>();
+        let mut matched = false;
+        while bytes.len() >= header {
+            let event = unsafe { &*bytes.as_ptr().cast::<inotify_event>() };
+            let end = header + event.len as usize;
+            let name = &bytes[header..end.min(bytes.len())];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            matched |= self
+                .names
+                .get(&event.wd)
+                .is_some_and(|names| names.iter().any(|known| known.as_bytes() == name));
+            bytes = &bytes[end.min(bytes.len())..];
+        }
+        matched
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Re-reads the configuration after a watched file changed and reports what
+/// differs from the previous reading.
+fn watch_config_files(files: FileWatcher, sink: EventSink) {
+    let mut configured = configured_layouts().layouts;
+    while !sink.stopped() {
+        if !files.wait(POLL_INTERVAL) {
+            continue;
+        }
+        let current = configured_layouts().layouts;
+        if current != configured {
+            configured = current;
+            if !sink.send(LayoutEvent::LayoutsChanged) {
+                return;
+            }
+        }
+        if let Some(active) = active_layout(&configured)
+            && !sink.send(LayoutEvent::ActiveChanged(active.id()))
+        {
+            return;
+        }
+    }
+}
+
+/// Follows XKB group changes and keymap reloads on the X server.
+fn watch_x11(sink: EventSink) {
+    let Some(mut display) = XDisplay::open() else {
+        return;
+    };
+    if !display.select_layout_events() {
+        return;
+    }
+    let server_layouts = || sources::from_setxkbmap().unwrap_or_default();
+    let mut layouts: Vec<XkbLayoutSpec> = server_layouts();
+    while !sink.stopped() {
+        let event = match display.wait_event(POLL_INTERVAL) {
+            Some(XkbEvent::NewKeyboard) => {
+                layouts = server_layouts();
+                LayoutEvent::LayoutsChanged
+            }
+            Some(XkbEvent::GroupChanged) => {
+                let group = display.group().unwrap_or(0) as usize;
+                let Some(spec) = layouts.get(group) else {
+                    continue;
+                };
+                LayoutEvent::ActiveChanged(spec.id())
+            }
+            None => continue,
+        };
+        if !sink.send(event) {
+            return;
+        }
+    }
+}
+
+/// Starts watching for layout switches and configuration changes.
+///
+/// Under X11 the server's `XkbStateNotify` and `XkbNewKeyboardNotify` events
+/// are followed; in every session the configuration files behind
+/// [`configured_layouts`](super::configured_layouts) are watched with inotify,
+/// which also catches GNOME input source switches stored in dconf.
+pub fn watch_layout_changes() -> Result<LayoutWatcher, LayoutError> {
+    let files = FileWatcher::new(&config_files()).map_err(|err| LayoutError::ToolFailed {
+        tool: "inotify",
+        reason: err.to_string(),
+    })?;
+    let mut watcher = LayoutWatcher::new();
+    watcher.assume_active(active_layout(&configured_layouts().layouts).map(|spec| spec.id()));
+    watcher.spawn(move |sink| watch_config_files(files, sink));
+    if !env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland") {
+        watcher.spawn(watch_x11);
+    }
+    Ok(watcher.start())
+}
+
+#[cfg(test)]
+mod tests;
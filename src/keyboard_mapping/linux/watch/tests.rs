@@ -0,0 +1,60 @@
+use super::*;
+use std::fs;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = crate::keyboard_mapping::temp_path(&format!("watch-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_file_watcher_reports_watched_file() {
+    let dir = temp_dir("watched");
+    let file = dir.join("kxkbrc");
+    let watcher = FileWatcher::new(std::slice::from_ref(&file)).unwrap();
+
+    fs::write(&file, "[Layout]\nLayoutList=us,il\n").unwrap();
+    assert!(watcher.wait(Duration::from_secs(2)));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_file_watcher_reports_replaced_file() {
+    let dir = temp_dir("replaced");
+    let file = dir.join("user");
+    fs::write(&file, "old").unwrap();
+    let watcher = FileWatcher::new(std::slice::from_ref(&file)).unwrap();
+
+    let staged = dir.join("user.tmp");
+    fs::write(&staged, "new").unwrap();
+    fs::rename(&staged, &file).unwrap();
+    assert!(watcher.wait(Duration::from_secs(2)));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_file_watcher_ignores_other_files() {
+    let dir = temp_dir("ignored");
+    let watcher = FileWatcher::new(&[dir.join("keyboard")]).unwrap();
+
+    fs::write(dir.join("unrelated"), "x").unwrap();
+    assert!(!watcher.wait(Duration::from_millis(100)));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_file_watcher_skips_missing_directories() {
+    let watcher = FileWatcher::new(&[PathBuf::from("/nonexistent/dir/kxkbrc")]).unwrap();
+    assert!(!watcher.wait(Duration::from_millis(10)));
+}
+
+#[test]
+fn test_watch_layout_changes_starts_and_stops() {
+    let mut watcher = watch_layout_changes().unwrap();
+    let _ = watcher.next_timeout(Duration::from_millis(10));
+    drop(watcher);
+}
@@ -0,0 +1,247 @@
+//! Minimal Xlib XKB client, loaded with `dlopen` so that libX11 stays an
+//! optional runtime dependency for Wayland-only systems.
+
+use libc::{POLLIN, RTLD_LOCAL, RTLD_NOW, c_char, c_int, c_long, c_uint, c_ulong, c_void, pollfd};
+use std::ffi::CStr;
+use std::ptr;
+use std::time::Duration;
+
+const LIBX11: &CStr = c"libX11.so.6";
+const XKB_USE_CORE_KBD: c_uint = 0x0100;
+const SUCCESS: c_int = 0;
+
+const XKB_NEW_KEYBOARD_NOTIFY: c_int = 0;
+const XKB_STATE_NOTIFY: c_int = 2;
+const XKB_NEW_KEYBOARD_NOTIFY_MASK: c_uint = 1 << 0;
+const XKB_STATE_NOTIFY_MASK: c_uint = 1 << 2;
+const XKB_GROUP_STATE_MASK: c_ulong = 1 << 4;
+
+/// `XkbStateRec` from `<X11/XKBlib.h>`.
+#[repr(C)]
+#[derive(Default)]
+struct XkbStateRec {
+    group: u8,
+    locked_group: u8,
+    base_group: u16,
+    latched_group: u16,
+    mods: u8,
+    base_mods: u8,
+    latched_mods: u8,
+    locked_mods: u8,
+    compat_state: u8,
+    grab_mods: u8,
+    compat_grab_mods: u8,
+    lookup_mods: u8,
+    compat_lookup_mods: u8,
+    ptr_buttons: u16,
+}
+
+/// Common prefix of every XKB event (`XkbAnyEvent`).
+#[repr(C)]
+struct XkbAnyEvent {
+    event_type: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut c_void,
+    time: c_ulong,
+    xkb_type: c_int,
+    device: c_uint,
+}
+
+/// Storage for an `XEvent` union, which is 24 longs.
+type XEvent = [c_long; 24];
+
+type XOpenDisplayFn = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type XCloseDisplayFn = unsafe extern "C" fn(*mut c_void) -> c_int;
+type XConnectionNumberFn = unsafe extern "C" fn(*mut c_void) -> c_int;
+type XPendingFn = unsafe extern "C" fn(*mut c_void) -> c_int;
+type XNextEventFn = unsafe extern "C" fn(*mut c_void, *mut XEvent) -> c_int;
+type XkbGetStateFn = unsafe extern "C" fn(*mut c_void, c_uint, *mut XkbStateRec) -> c_int;
+type XkbQueryExtensionFn = unsafe extern "C" fn(
+    *mut c_void,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+) -> c_int;
+type XSyncFn = unsafe extern "C" fn(*mut c_void, c_int) -> c_int;
+type XkbLockGroupFn = unsafe extern "C" fn(*mut c_void, c_uint, c_uint) -> c_int;
+type XkbSelectEventsFn = unsafe extern "C" fn(*mut c_void, c_uint, c_uint, c_uint) -> c_int;
+type XkbSelectEventDetailsFn =
+    unsafe extern "C" fn(*mut c_void, c_uint, c_uint, c_ulong, c_ulong) -> c_int;
+
+/// The libX11 entry points this module uses.
+struct Xlib {
+    close: XCloseDisplayFn,
+    connection_number: XConnectionNumberFn,
+    pending: XPendingFn,
+    next_event: XNextEventFn,
+    sync: XSyncFn,
+    get_state: XkbGetStateFn,
+    lock_group: XkbLockGroupFn,
+    query_extension: XkbQueryExtensionFn,
+    select_events: XkbSelectEventsFn,
+    select_event_details: XkbSelectEventDetailsFn,
+}
+
+/// XKB notification relevant to layout tracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum XkbEvent {
+    /// The effective group, i.e. the active layout, changed.
+    GroupChanged,
+    /// A new keymap was loaded, e.g. by `setxkbmap`.
+    NewKeyboard,
+}
+
+/// Connection to the X server named by `DISPLAY`.
+pub(crate) struct XDisplay {
+    library: *mut c_void,
+    display: *mut c_void,
+    xlib: Xlib,
+    event_base: Option<c_int>,
+}
+
+unsafe fn symbol<F>(library: *mut c_void, name: &CStr) -> Option<F> {
+    let address = unsafe { libc::dlsym(library, name.as_ptr()) };
+    // SAFETY: the caller names a function whose C signature matches `F`.
+    (!address.is_null()).then(|| unsafe { std::mem::transmute_copy(&address) })
+}
+
+unsafe fn load(library: *mut c_void) -> Option<(XOpenDisplayFn, Xlib)> {
+    unsafe {
+        let open = symbol(library, c"XOpenDisplay")?;
+        let xlib = Xlib {
+            close: symbol(library, c"XCloseDisplay")?,
+            connection_number: symbol(library, c"XConnectionNumber")?,
+            pending: symbol(library, c"XPending")?,
+            next_event: symbol(library, c"XNextEvent")?,
+            sync: symbol(library, c"XSync")?,
+            get_state: symbol(library, c"XkbGetState")?,
+            lock_group: symbol(library, c"XkbLockGroup")?,
+            query_extension: symbol(library, c"XkbQueryExtension")?,
+            select_events: symbol(library, c"XkbSelectEvents")?,
+            select_event_details: symbol(library, c"XkbSelectEventDetails")?,
+        };
+        Some((open, xlib))
+    }
+}
+
+impl XDisplay {
+    /// Returns `None` when libX11 is not installed or no X server is reachable.
+    pub(crate) fn open() -> Option<Self> {
+        unsafe {
+            let library = libc::dlopen(LIBX11.as_ptr(), RTLD_NOW | RTLD_LOCAL);
+            if library.is_null() {
+                return None;
+            }
+            let loaded = load(library).and_then(|(open, xlib)| {
+                let display = open(ptr::null());
+                (!display.is_null()).then_some((display, xlib))
+            });
+            let Some((display, xlib)) = loaded else {
+                libc::dlclose(library);
+                return None;
+            };
+            Some(XDisplay {
+                library,
+                display,
+                xlib,
+                event_base: None,
+            })
+        }
+    }
+
+    fn state(&self) -> Option<XkbStateRec> {
+        let mut state = XkbStateRec::default();
+        let status = unsafe { (self.xlib.get_state)(self.display, XKB_USE_CORE_KBD, &mut state) };
+        (status == SUCCESS).then_some(state)
+    }
+
+    /// Effective XKB group of the core keyboard, i.e. the index of the
+    /// active layout in the server's layout list.
+    pub(crate) fn group(&self) -> Option<u32> {
+        self.state().map(|state| u32::from(state.group))
+    }
+
+    /// Locks the core keyboard to `group`, making it the active layout, and
+    /// waits until the server has processed the request.
+    pub(crate) fn lock_group(&self, group: u32) -> bool {
+        unsafe {
+            let sent = (self.xlib.lock_group)(self.display, XKB_USE_CORE_KBD, group) != 0;
+            (self.xlib.sync)(self.display, 0);
+            sent
+        }
+    }
+
+    /// Subscribes to group changes and keymap reloads on the core keyboard.
+    pub(crate) fn select_layout_events(&mut self) -> bool {
+        let (mut opcode, mut event_base, mut error_base) = (0, 0, 0);
+        let (mut major, mut minor) = (1, 0);
+        unsafe {
+            let has_xkb = (self.xlib.query_extension)(
+                self.display,
+                &mut opcode,
+                &mut event_base,
+                &mut error_base,
+                &mut major,
+                &mut minor,
+            );
+            if has_xkb == 0 {
+                return false;
+            }
+            let mask = XKB_NEW_KEYBOARD_NOTIFY_MASK | XKB_STATE_NOTIFY_MASK;
+            (self.xlib.select_events)(self.display, XKB_USE_CORE_KBD, mask, mask);
+            (self.xlib.select_event_details)(
+                self.display,
+                XKB_USE_CORE_KBD,
+                XKB_STATE_NOTIFY as c_uint,
+                XKB_GROUP_STATE_MASK,
+                XKB_GROUP_STATE_MASK,
+            );
+        }
+        self.event_base = Some(event_base);
+        true
+    }
+
+    /// Waits up to `timeout` for the next event selected by
+    /// [`XDisplay::select_layout_events`].
+    pub(crate) fn wait_event(&self, timeout: Duration) -> Option<XkbEvent> {
+        let event_base = self.event_base?;
+        unsafe {
+            if (self.xlib.pending)(self.display) == 0 {
+                let mut fd = pollfd {
+                    fd: (self.xlib.connection_number)(self.display),
+                    events: POLLIN,
+                    revents: 0,
+                };
+                if libc::poll(&mut fd, 1, timeout.as_millis() as c_int) <= 0 {
+                    return None;
+                }
+            }
+            while (self.xlib.pending)(self.display) > 0 {
+                let mut event: XEvent = [0; 24];
+                (self.xlib.next_event)(self.display, &mut event);
+                let any = &*(event.as_ptr() as *const XkbAnyEvent);
+                if any.event_type != event_base {
+                    continue;
+                }
+                match any.xkb_type {
+                    XKB_STATE_NOTIFY => return Some(XkbEvent::GroupChanged),
+                    XKB_NEW_KEYBOARD_NOTIFY => return Some(XkbEvent::NewKeyboard),
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Drop for XDisplay {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.close)(self.display);
+            libc::dlclose(self.library);
+        }
+    }
+}
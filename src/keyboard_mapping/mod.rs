@@ -1,24 +1,72 @@
 #![allow(unused)]
+pub mod cldr;
+#[cfg(feature = "embedded-layouts")]
+pub mod embedded;
 pub mod fallback;
+pub mod inference;
+pub mod keylayout;
+pub mod klc;
+pub mod language;
+pub mod language_model;
 pub mod linux;
+pub mod physical_key;
+pub mod profile;
 pub mod remap;
 pub mod types;
+pub mod watch;
 pub mod windows;
-pub use types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+pub mod xkb_registry;
+pub mod xkb_symbols;
+pub use cldr::load_cldr_keyboards;
+#[cfg(feature = "embedded-layouts")]
+pub use embedded::{embedded_layout, embedded_layouts, embedded_layouts_for_language};
+pub use inference::{
+    Correction, LayoutGuess, TextSegment, correct_mixed_text_with, detect_mistyped_with,
+    infer_layout, mistyped_segments_with,
+};
+#[cfg(feature = "language-models")]
+pub use inference::{correct_mixed_text, detect_mistyped};
+pub use language_model::{LanguageModel, LanguageModels};
+pub use physical_key::{KeyBlock, PhysicalKey};
+pub use profile::{KeyboardProfile, ProfileFormat};
+pub use types::{
+    DeadKey, KeyAction, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutId, LayoutMap,
+    ShiftLevel,
+};
+pub use watch::{LayoutEvent, LayoutWatcher};
+pub use xkb_registry::{
+    XkbConfigItem, XkbLayoutDescription, XkbLayoutInfo, XkbLayoutSpec, XkbOptionGroup, XkbRegistry,
+};
 
 #[cfg(target_os = "windows")]
 pub use windows::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    KeyboardContext, activate_layout, all_layout_vk_maps, current_layout, get_layout, list_layouts,
+    vk_to_char_map_default, vk_to_char_map_for_layout, watch_layout_changes,
 };
 
 #[cfg(target_os = "linux")]
 pub use linux::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    ConfiguredLayouts, KeyboardContext, LayoutSource, activate_layout, all_layout_vk_maps,
+    configured_layouts, current_layout, get_layout, layout_maps_from_xkb_keymap, list_layouts,
+    vk_to_char_map_default, vk_to_char_map_for_layout, watch_layout_changes,
 };
 
 #[cfg(not(any(target_os = "windows", target_os = "linux")))]
 pub use fallback::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    KeyboardContext, activate_layout, all_layout_vk_maps, current_layout, get_layout, list_layouts,
+    vk_to_char_map_default, vk_to_char_map_for_layout, watch_layout_changes,
+};
+
+#[allow(deprecated)]
+pub use remap::get_text_leyaout_map;
+pub use remap::{
+    KeyRanking, LayoutCandidate, get_text_layout_map, rank_layouts, shift_text_language,
+    shift_text_language_with,
 };
 
-pub use remap::{get_text_leyaout_map, shift_text_language};
+/// Path named `name` in the temp directory, unique to this test process,
+/// for tests that write files. Callers prefix `name` with their module.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("layout-{}-{name}", std::process::id()))
+}
@@ -0,0 +1,380 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Physical key position, named after the W3C `KeyboardEvent.code` values
+/// (which follow the USB HID usage table). The identity is independent of the
+/// active layout: `KeyQ` is the key right of Tab even on an AZERTY keyboard.
+///
+/// Serializes as its code name, e.g. `"KeyA"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PhysicalKey {
+    Backquote,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Digit0,
+    Minus,
+    Equal,
+    IntlYen,
+    KeyQ,
+    KeyW,
+    KeyE,
+    KeyR,
+    KeyT,
+    KeyY,
+    KeyU,
+    KeyI,
+    KeyO,
+    KeyP,
+    BracketLeft,
+    BracketRight,
+    Backslash,
+    KeyA,
+    KeyS,
+    KeyD,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyJ,
+    KeyK,
+    KeyL,
+    Semicolon,
+    Quote,
+    IntlBackslash,
+    KeyZ,
+    KeyX,
+    KeyC,
+    KeyV,
+    KeyB,
+    KeyN,
+    KeyM,
+    Comma,
+    Period,
+    Slash,
+    IntlRo,
+    Space,
+    Escape,
+    Tab,
+    Enter,
+    Backspace,
+    Delete,
+    NumpadDivide,
+    NumpadMultiply,
+    NumpadSubtract,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad0,
+    NumpadDecimal,
+    NumpadEnter,
+    NumpadEqual,
+    NumpadComma,
+}
+
+struct KeyCodes {
+    key: PhysicalKey,
+    code: &'static str,
+    evdev: u16,
+    xkb_name: &'static str,
+    scan_code: u16,
+    windows_vk: Option<u16>,
+    mac_key_code: u16,
+}
+
+const fn codes(
+    key: PhysicalKey,
+    code: &'static str,
+    evdev: u16,
+    xkb_name: &'static str,
+    scan_code: u16,
+    windows_vk: Option<u16>,
+    mac_key_code: u16,
+) -> KeyCodes {
+    KeyCodes {
+        key,
+        code,
+        evdev,
+        xkb_name,
+        scan_code,
+        windows_vk,
+        mac_key_code,
+    }
+}
+
+use PhysicalKey as K;
+
+/// Conversion table, one row per key and in declaration order.
+///
+/// `evdev` is the Linux input event code (XKB keycodes are `evdev + 8`),
+/// `scan_code` is the PC/AT set 1 make code with extended keys prefixed by
+/// `0xE0`, and `windows_vk` is the virtual key a US layout assigns to the
+/// position. Keys whose VK collides with another position have none.
+/// `mac_key_code` is the macOS virtual key code (`kVK_*` in `Events.h`);
+/// the ISO key left of Z is `kVK_ISO_Section`.
+#[rustfmt::skip]
+const TABLE: &[KeyCodes] = &[
+    codes(K::Backquote, "Backquote", 41, "TLDE", 0x29, Some(0xC0), 0x32),
+    codes(K::Digit1, "Digit1", 2, "AE01", 0x02, Some(0x31), 0x12),
+    codes(K::Digit2, "Digit2", 3, "AE02", 0x03, Some(0x32), 0x13),
+    codes(K::Digit3, "Digit3", 4, "AE03", 0x04, Some(0x33), 0x14),
+    codes(K::Digit4, "Digit4", 5, "AE04", 0x05, Some(0x34), 0x15),
+    codes(K::Digit5, "Digit5", 6, "AE05", 0x06, Some(0x35), 0x17),
+    codes(K::Digit6, "Digit6", 7, "AE06", 0x07, Some(0x36), 0x16),
+    codes(K::Digit7, "Digit7", 8, "AE07", 0x08, Some(0x37), 0x1A),
+    codes(K::Digit8, "Digit8", 9, "AE08", 0x09, Some(0x38), 0x1C),
+    codes(K::Digit9, "Digit9", 10, "AE09", 0x0A, Some(0x39), 0x19),
+    codes(K::Digit0, "Digit0", 11, "AE10", 0x0B, Some(0x30), 0x1D),
+    codes(K::Minus, "Minus", 12, "AE11", 0x0C, Some(0xBD), 0x1B),
+    codes(K::Equal, "Equal", 13, "AE12", 0x0D, Some(0xBB), 0x18),
+    codes(K::IntlYen, "IntlYen", 124, "AE13", 0x7D, None, 0x5D),
+    codes(K::KeyQ, "KeyQ", 16, "AD01", 0x10, Some(0x51), 0x0C),
+    codes(K::KeyW, "KeyW", 17, "AD02", 0x11, Some(0x57), 0x0D),
+    codes(K::KeyE, "KeyE", 18, "AD03", 0x12, Some(0x45), 0x0E),
+    codes(K::KeyR, "KeyR", 19, "AD04", 0x13, Some(0x52), 0x0F),
+    codes(K::KeyT, "KeyT", 20, "AD05", 0x14, Some(0x54), 0x11),
+    codes(K::KeyY, "KeyY", 21, "AD06", 0x15, Some(0x59), 0x10),
+    codes(K::KeyU, "KeyU", 22, "AD07", 0x16, Some(0x55), 0x20),
+    codes(K::KeyI, "KeyI", 23, "AD08", 0x17, Some(0x49), 0x22),
+    codes(K::KeyO, "KeyO", 24, "AD09", 0x18, Some(0x4F), 0x1F),
+    codes(K::KeyP, "KeyP", 25, "AD10", 0x19, Some(0x50), 0x23),
+    codes(K::BracketLeft, "BracketLeft", 26, "AD11", 0x1A, Some(0xDB), 0x21),
+    codes(K::BracketRight, "BracketRight", 27, "AD12", 0x1B, Some(0xDD), 0x1E),
+    codes(K::Backslash, "Backslash", 43, "BKSL", 0x2B, Some(0xDC), 0x2A),
+    codes(K::KeyA, "KeyA", 30, "AC01", 0x1E, Some(0x41), 0x00),
+    codes(K::KeyS, "KeyS", 31, "AC02", 0x1F, Some(0x53), 0x01),
+    codes(K::KeyD, "KeyD", 32, "AC03", 0x20, Some(0x44), 0x02),
+    codes(K::KeyF, "KeyF", 33, "AC04", 0x21, Some(0x46), 0x03),
+    codes(K::KeyG, "KeyG", 34, "AC05", 0x22, Some(0x47), 0x05),
+    codes(K::KeyH, "KeyH", 35, "AC06", 0x23, Some(0x48), 0x04),
+    codes(K::KeyJ, "KeyJ", 36, "AC07", 0x24, Some(0x4A), 0x26),
+    codes(K::KeyK, "KeyK", 37, "AC08", 0x25, Some(0x4B), 0x28),
+    codes(K::KeyL, "KeyL", 38, "AC09", 0x26, Some(0x4C), 0x25),
+    codes(K::Semicolon, "Semicolon", 39, "AC10", 0x27, Some(0xBA), 0x29),
+    codes(K::Quote, "Quote", 40, "AC11", 0x28, Some(0xDE), 0x27),
+    codes(K::IntlBackslash, "IntlBackslash", 86, "LSGT", 0x56, Some(0xE2), 0x0A),
+    codes(K::KeyZ, "KeyZ", 44, "AB01", 0x2C, Some(0x5A), 0x06),
+    codes(K::KeyX, "KeyX", 45, "AB02", 0x2D, Some(0x58), 0x07),
+    codes(K::KeyC, "KeyC", 46, "AB03", 0x2E, Some(0x43), 0x08),
+    codes(K::KeyV, "KeyV", 47, "AB04", 0x2F, Some(0x56), 0x09),
+    codes(K::KeyB, "KeyB", 48, "AB05", 0x30, Some(0x42), 0x0B),
+    codes(K::KeyN, "KeyN", 49, "AB06", 0x31, Some(0x4E), 0x2D),
+    codes(K::KeyM, "KeyM", 50, "AB07", 0x32, Some(0x4D), 0x2E),
+    codes(K::Comma, "Comma", 51, "AB08", 0x33, Some(0xBC), 0x2B),
+    codes(K::Period, "Period", 52, "AB09", 0x34, Some(0xBE), 0x2F),
+    codes(K::Slash, "Slash", 53, "AB10", 0x35, Some(0xBF), 0x2C),
+    codes(K::IntlRo, "IntlRo", 89, "AB11", 0x73, Some(0xC1), 0x5E),
+    codes(K::Space, "Space", 57, "SPCE", 0x39, Some(0x20), 0x31),
+    codes(K::Escape, "Escape", 1, "ESC", 0x01, Some(0x1B), 0x35),
+    codes(K::Tab, "Tab", 15, "TAB", 0x0F, Some(0x09), 0x30),
+    codes(K::Enter, "Enter", 28, "RTRN", 0x1C, Some(0x0D), 0x24),
+    codes(K::Backspace, "Backspace", 14, "BKSP", 0x0E, Some(0x08), 0x33),
+    codes(K::Delete, "Delete", 111, "DELE", 0xE053, Some(0x2E), 0x75),
+    codes(K::NumpadDivide, "NumpadDivide", 98, "KPDV", 0xE035, Some(0x6F), 0x4B),
+    codes(K::NumpadMultiply, "NumpadMultiply", 55, "KPMU", 0x37, Some(0x6A), 0x43),
+    codes(K::NumpadSubtract, "NumpadSubtract", 74, "KPSU", 0x4A, Some(0x6D), 0x4E),
+    codes(K::Numpad7, "Numpad7", 71, "KP7", 0x47, Some(0x67), 0x59),
+    codes(K::Numpad8, "Numpad8", 72, "KP8", 0x48, Some(0x68), 0x5B),
+    codes(K::Numpad9, "Numpad9", 73, "KP9", 0x49, Some(0x69), 0x5C),
+    codes(K::NumpadAdd, "NumpadAdd", 78, "KPAD", 0x4E, Some(0x6B), 0x45),
+    codes(K::Numpad4, "Numpad4", 75, "KP4", 0x4B, Some(0x64), 0x56),
+    codes(K::Numpad5, "Numpad5", 76, "KP5", 0x4C, Some(0x65), 0x57),
+    codes(K::Numpad6, "Numpad6", 77, "KP6", 0x4D, Some(0x66), 0x58),
+    codes(K::Numpad1, "Numpad1", 79, "KP1", 0x4F, Some(0x61), 0x53),
+    codes(K::Numpad2, "Numpad2", 80, "KP2", 0x50, Some(0x62), 0x54),
+    codes(K::Numpad3, "Numpad3", 81, "KP3", 0x51, Some(0x63), 0x55),
+    codes(K::Numpad0, "Numpad0", 82, "KP0", 0x52, Some(0x60), 0x52),
+    codes(K::NumpadDecimal, "NumpadDecimal", 83, "KPDL", 0x53, Some(0x6E), 0x41),
+    codes(K::NumpadEnter, "NumpadEnter", 96, "KPEN", 0xE01C, None, 0x4C),
+    codes(K::NumpadEqual, "NumpadEqual", 117, "KPEQ", 0x59, Some(0x92), 0x51),
+    codes(K::NumpadComma, "NumpadComma", 121, "I129", 0x7E, Some(0x6C), 0x5F),
+];
+
+/// Alternative XKB names that refer to a key in [`TABLE`].
+const XKB_ALIASES: &[(&str, PhysicalKey)] = &[
+    ("AC12", PhysicalKey::Backslash),
+    ("KPPT", PhysicalKey::NumpadComma),
+];
+
+impl PhysicalKey {
+    /// Every key, in declaration order.
+    pub const ALL: [PhysicalKey; 74] = {
+        let mut all = [PhysicalKey::Backquote; 74];
+        let mut i = 0;
+        while i < TABLE.len() {
+            all[i] = TABLE[i].key;
+            i += 1;
+        }
+        all
+    };
+
+    fn codes(self) -> &'static KeyCodes {
+        // TABLE rows follow declaration order, see the consistency test.
+        &TABLE[self as usize]
+    }
+
+    /// W3C `KeyboardEvent.code` name, e.g. `"KeyA"`.
+    pub fn code(self) -> &'static str {
+        self.codes().code
+    }
+
+    pub fn from_code(code: &str) -> Option<PhysicalKey> {
+        TABLE.iter().find(|row| row.code == code).map(|row| row.key)
+    }
+
+    /// Linux evdev code (`KEY_*` in `input-event-codes.h`).
+    pub fn evdev_code(self) -> u16 {
+        self.codes().evdev
+    }
+
+    pub fn from_evdev_code(code: u16) -> Option<PhysicalKey> {
+        TABLE
+            .iter()
+            .find(|row| row.evdev == code)
+            .map(|row| row.key)
+    }
+
+    /// XKB keycode as used by evdev-based keymaps (evdev code + 8).
+    pub fn xkb_keycode(self) -> u16 {
+        self.evdev_code() + 8
+    }
+
+    pub fn from_xkb_keycode(keycode: u16) -> Option<PhysicalKey> {
+        keycode
+            .checked_sub(8)
+            .and_then(PhysicalKey::from_evdev_code)
+    }
+
+    /// XKB key name without angle brackets, e.g. `"AC01"`.
+    pub fn xkb_name(self) -> &'static str {
+        self.codes().xkb_name
+    }
+
+    pub fn from_xkb_name(name: &str) -> Option<PhysicalKey> {
+        let name = name.trim_start_matches('<').trim_end_matches('>');
+        TABLE
+            .iter()
+            .find(|row| row.xkb_name == name)
+            .map(|row| row.key)
+            .or_else(|| {
+                XKB_ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == name)
+                    .map(|(_, key)| *key)
+            })
+    }
+
+    /// Set 1 scan code; extended keys carry `0xE0` in the high byte.
+    pub fn scan_code(self) -> u16 {
+        self.codes().scan_code
+    }
+
+    pub fn from_scan_code(scan_code: u16) -> Option<PhysicalKey> {
+        TABLE
+            .iter()
+            .find(|row| row.scan_code == scan_code)
+            .map(|row| row.key)
+    }
+
+    /// Windows virtual key the US layout assigns to this position.
+    pub fn windows_vk(self) -> Option<u16> {
+        self.codes().windows_vk
+    }
+
+    pub fn from_windows_vk(vk: u16) -> Option<PhysicalKey> {
+        TABLE
+            .iter()
+            .find(|row| row.windows_vk == Some(vk))
+            .map(|row| row.key)
+    }
+
+    /// macOS virtual key code, as used by `.keylayout` files and
+    /// `CGEventGetIntegerValueField`, e.g. `0x00` for `KeyA`.
+    pub fn mac_key_code(self) -> u16 {
+        self.codes().mac_key_code
+    }
+
+    pub fn from_mac_key_code(code: u16) -> Option<PhysicalKey> {
+        TABLE
+            .iter()
+            .find(|row| row.mac_key_code == code)
+            .map(|row| row.key)
+    }
+
+    pub fn is_numpad(self) -> bool {
+        self >= PhysicalKey::NumpadDivide
+    }
+
+    /// Part of the keyboard the key belongs to.
+    pub fn block(self) -> KeyBlock {
+        if self.is_numpad() {
+            KeyBlock::Numpad
+        } else if self >= PhysicalKey::Escape {
+            KeyBlock::Control
+        } else {
+            KeyBlock::Main
+        }
+    }
+
+    /// Whether every common keyboard has the key. The ISO key left of `Z`,
+    /// the JIS keys and the numeric keypad are missing on ANSI, laptop or
+    /// compact keyboards.
+    pub fn is_universal(self) -> bool {
+        !matches!(
+            self,
+            PhysicalKey::IntlBackslash | PhysicalKey::IntlYen | PhysicalKey::IntlRo
+        ) && !self.is_numpad()
+    }
+}
+
+/// Part of the keyboard a [`PhysicalKey`] belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyBlock {
+    /// The typing keys: number row, letter rows and Space.
+    Main,
+    /// Escape, Tab, Enter, Backspace and Delete.
+    Control,
+    /// The numeric keypad.
+    Numpad,
+}
+
+impl fmt::Display for PhysicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKeyCode(pub String);
+
+impl fmt::Display for UnknownKeyCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown physical key code `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownKeyCode {}
+
+impl FromStr for PhysicalKey {
+    type Err = UnknownKeyCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PhysicalKey::from_code(s).ok_or_else(|| UnknownKeyCode(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,123 @@
+use super::*;
+use std::collections::HashSet;
+
+#[test]
+fn test_table_follows_declaration_order() {
+    for (i, row) in TABLE.iter().enumerate() {
+        assert_eq!(row.key as usize, i, "row for {} is out of order", row.code);
+    }
+    assert_eq!(PhysicalKey::ALL.len(), TABLE.len());
+}
+
+#[test]
+fn test_code_round_trip() {
+    for key in PhysicalKey::ALL {
+        assert_eq!(PhysicalKey::from_code(key.code()), Some(key));
+        assert_eq!(key.to_string().parse::<PhysicalKey>(), Ok(key));
+    }
+}
+
+#[test]
+fn test_evdev_round_trip() {
+    for key in PhysicalKey::ALL {
+        assert_eq!(PhysicalKey::from_evdev_code(key.evdev_code()), Some(key));
+        assert_eq!(PhysicalKey::from_xkb_keycode(key.xkb_keycode()), Some(key));
+    }
+}
+
+#[test]
+fn test_xkb_name_round_trip() {
+    for key in PhysicalKey::ALL {
+        assert_eq!(PhysicalKey::from_xkb_name(key.xkb_name()), Some(key));
+    }
+}
+
+#[test]
+fn test_scan_code_round_trip() {
+    for key in PhysicalKey::ALL {
+        assert_eq!(PhysicalKey::from_scan_code(key.scan_code()), Some(key));
+    }
+}
+
+#[test]
+fn test_windows_vk_round_trip() {
+    for key in PhysicalKey::ALL {
+        if let Some(vk) = key.windows_vk() {
+            assert_eq!(PhysicalKey::from_windows_vk(vk), Some(key));
+        }
+    }
+}
+
+#[test]
+fn test_mac_key_code_round_trip() {
+    for key in PhysicalKey::ALL {
+        assert_eq!(
+            PhysicalKey::from_mac_key_code(key.mac_key_code()),
+            Some(key)
+        );
+    }
+    assert_eq!(PhysicalKey::KeyA.mac_key_code(), 0x00);
+    assert_eq!(PhysicalKey::KeyQ.mac_key_code(), 0x0C);
+    assert_eq!(PhysicalKey::from_mac_key_code(0x7F), None);
+}
+
+#[test]
+fn test_codes_are_unique() {
+    let codes: HashSet<_> = TABLE.iter().map(|row| row.code).collect();
+    let evdev: HashSet<_> = TABLE.iter().map(|row| row.evdev).collect();
+    let xkb: HashSet<_> = TABLE.iter().map(|row| row.xkb_name).collect();
+    let scan: HashSet<_> = TABLE.iter().map(|row| row.scan_code).collect();
+    assert_eq!(codes.len(), TABLE.len());
+    assert_eq!(evdev.len(), TABLE.len());
+    assert_eq!(xkb.len(), TABLE.len());
+    assert_eq!(scan.len(), TABLE.len());
+}
+
+#[test]
+fn test_known_codes() {
+    assert_eq!(PhysicalKey::KeyA.xkb_keycode(), 38);
+    assert_eq!(PhysicalKey::KeyA.xkb_name(), "AC01");
+    assert_eq!(PhysicalKey::KeyA.scan_code(), 0x1E);
+    assert_eq!(PhysicalKey::KeyA.windows_vk(), Some(0x41));
+    assert_eq!(PhysicalKey::Backquote.xkb_name(), "TLDE");
+    assert_eq!(
+        PhysicalKey::from_xkb_name("<AC12>"),
+        Some(PhysicalKey::Backslash)
+    );
+}
+
+#[test]
+fn test_unknown_code() {
+    assert_eq!(PhysicalKey::from_code("KeyÄ"), None);
+    assert!("NotAKey".parse::<PhysicalKey>().is_err());
+    assert_eq!(PhysicalKey::from_xkb_keycode(3), None);
+}
+
+#[test]
+fn test_is_numpad() {
+    assert!(PhysicalKey::Numpad0.is_numpad());
+    assert!(PhysicalKey::NumpadComma.is_numpad());
+    assert!(!PhysicalKey::Digit0.is_numpad());
+    assert!(!PhysicalKey::Delete.is_numpad());
+}
+
+#[test]
+fn test_block() {
+    assert_eq!(PhysicalKey::Digit1.block(), KeyBlock::Main);
+    assert_eq!(PhysicalKey::IntlRo.block(), KeyBlock::Main);
+    assert_eq!(PhysicalKey::Space.block(), KeyBlock::Main);
+    assert_eq!(PhysicalKey::Tab.block(), KeyBlock::Control);
+    assert_eq!(PhysicalKey::Delete.block(), KeyBlock::Control);
+    assert_eq!(PhysicalKey::Numpad1.block(), KeyBlock::Numpad);
+    assert_eq!(PhysicalKey::NumpadComma.block(), KeyBlock::Numpad);
+}
+
+#[test]
+fn test_is_universal() {
+    assert!(PhysicalKey::KeyA.is_universal());
+    assert!(PhysicalKey::Slash.is_universal());
+    assert!(PhysicalKey::Enter.is_universal());
+    assert!(!PhysicalKey::IntlBackslash.is_universal());
+    assert!(!PhysicalKey::IntlYen.is_universal());
+    assert!(!PhysicalKey::Numpad7.is_universal());
+}
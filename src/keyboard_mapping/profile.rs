@@ -0,0 +1,173 @@
+//! Portable snapshots of a machine's layouts, stored as JSON or TOML.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::KeyboardContext;
+use super::types::{LayoutId, LayoutMap};
+use crate::error::LayoutError;
+
+/// Format version written by this crate. Documents with a newer version are
+/// rejected instead of being half understood.
+pub const PROFILE_VERSION: u32 = 1;
+
+/// Every layout of a machine with its key maps and metadata, in the
+/// machine's order, so it can be used where no layout backend exists.
+///
+/// ```no_run
+/// # use keyboard_layout_lib::keyboard_mapping::profile::KeyboardProfile;
+/// let profile = KeyboardProfile::capture()?;
+/// profile.save("layouts.toml")?;
+/// let maps = KeyboardProfile::load("layouts.toml")?.layouts;
+/// # Ok::<(), keyboard_layout_lib::LayoutError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyboardProfile {
+    pub version: u32,
+    /// OS the layouts were read on, as in `std::env::consts::OS`.
+    pub platform: String,
+    /// Layout that was active when the profile was captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<LayoutId>,
+    /// Layouts in the order the system lists them.
+    pub layouts: Vec<LayoutMap>,
+}
+
+/// Serialization format of a profile file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Json,
+    Toml,
+}
+
+impl ProfileFormat {
+    /// Format implied by the extension of `path`: `.json` or `.toml`.
+    pub fn from_path(path: &Path) -> Option<ProfileFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ProfileFormat::Json),
+            "toml" => Some(ProfileFormat::Toml),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ProfileFormat::Json => "keyboard profile JSON",
+            ProfileFormat::Toml => "keyboard profile TOML",
+        }
+    }
+}
+
+/// Just the version of a document, read before the rest so that newer
+/// documents fail with a clear message.
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+impl KeyboardProfile {
+    /// Profile of `layouts` read on the current OS.
+    pub fn new(layouts: Vec<LayoutMap>) -> Self {
+        KeyboardProfile {
+            version: PROFILE_VERSION,
+            platform: env::consts::OS.to_string(),
+            active: None,
+            layouts,
+        }
+    }
+
+    /// Snapshot of the installed layouts and the active one.
+    pub fn capture() -> Result<Self, LayoutError> {
+        let mut ctx = KeyboardContext::new();
+        let mut profile = KeyboardProfile::new(ctx.all_layout_vk_maps()?.to_vec());
+        profile.active = ctx.current_layout().ok();
+        Ok(profile)
+    }
+
+    pub fn layout(&self, id: &LayoutId) -> Option<&LayoutMap> {
+        self.layouts.iter().find(|map| &map.layout.id == id)
+    }
+
+    pub fn to_json(&self) -> Result<String, LayoutError> {
+        serde_json::to_string_pretty(self).map_err(|err| invalid(ProfileFormat::Json, err))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, LayoutError> {
+        let format = ProfileFormat::Json;
+        let version: VersionOnly =
+            serde_json::from_str(json).map_err(|err| invalid(format, err))?;
+        check_version(format, version.version)?;
+        serde_json::from_str(json).map_err(|err| invalid(format, err))
+    }
+
+    pub fn to_toml(&self) -> Result<String, LayoutError> {
+        toml::to_string_pretty(self).map_err(|err| invalid(ProfileFormat::Toml, err))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, LayoutError> {
+        let format = ProfileFormat::Toml;
+        let version: VersionOnly = toml::from_str(text).map_err(|err| invalid(format, err))?;
+        check_version(format, version.version)?;
+        toml::from_str(text).map_err(|err| invalid(format, err))
+    }
+
+    /// Reads a profile, in the format given by the file's extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LayoutError> {
+        let path = path.as_ref();
+        let format = format_of(path)?;
+        let text = fs::read_to_string(path).map_err(|err| LayoutError::FileAccess {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+        match format {
+            ProfileFormat::Json => Self::from_json(&text),
+            ProfileFormat::Toml => Self::from_toml(&text),
+        }
+    }
+
+    /// Writes the profile, in the format given by the file's extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LayoutError> {
+        let path = path.as_ref();
+        let text = match format_of(path)? {
+            ProfileFormat::Json => self.to_json()?,
+            ProfileFormat::Toml => self.to_toml()?,
+        };
+        fs::write(path, text).map_err(|err| LayoutError::FileAccess {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })
+    }
+}
+
+fn format_of(path: &Path) -> Result<ProfileFormat, LayoutError> {
+    ProfileFormat::from_path(path).ok_or_else(|| LayoutError::InvalidData {
+        format: "keyboard profile",
+        reason: format!(
+            "{} has neither a .json nor a .toml extension",
+            path.display()
+        ),
+    })
+}
+
+fn check_version(format: ProfileFormat, version: u32) -> Result<(), LayoutError> {
+    if version > PROFILE_VERSION {
+        return Err(LayoutError::InvalidData {
+            format: format.name(),
+            reason: format!("version {version} is newer than the supported {PROFILE_VERSION}"),
+        });
+    }
+    Ok(())
+}
+
+fn invalid(format: ProfileFormat, err: impl std::fmt::Display) -> LayoutError {
+    LayoutError::InvalidData {
+        format: format.name(),
+        reason: err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
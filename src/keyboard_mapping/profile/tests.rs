@@ -0,0 +1,171 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::temp_path;
+use crate::keyboard_mapping::types::{
+    DeadKey, KeyStroke, KeyboardDirection, KeyboardLayout, ShiftLevel,
+};
+use std::collections::BTreeMap;
+
+fn stroke(key: PhysicalKey, level: ShiftLevel) -> KeyStroke {
+    KeyStroke::new(key, level)
+}
+
+fn create_profile() -> KeyboardProfile {
+    let mut spanish = LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new("es"),
+            lang_name: "Spanish".to_string(),
+            description: "Spanish".to_string(),
+            languages: vec!["spa".to_string()],
+            language_tag: Some("es-ES".to_string()),
+            scripts: vec!["Latn".to_string()],
+            short_label: "ES".to_string(),
+            native_id: "es".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    spanish.insert_level(stroke(PhysicalKey::KeyE, ShiftLevel::Base), "e".to_string());
+    spanish.insert_level(
+        stroke(PhysicalKey::KeyE, ShiftLevel::Shift),
+        "E".to_string(),
+    );
+    spanish.insert_level(
+        stroke(PhysicalKey::KeyE, ShiftLevel::AltGr),
+        "€".to_string(),
+    );
+    spanish.insert_dead_key(
+        stroke(PhysicalKey::Quote, ShiftLevel::Base),
+        DeadKey {
+            accent: "´".to_string(),
+            combinations: BTreeMap::from([('e', "é".to_string())]),
+        },
+    );
+
+    let mut hebrew = LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new("il(phonetic)"),
+            direction: KeyboardDirection::RTL,
+            variant: Some("phonetic".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    hebrew.insert_level(stroke(PhysicalKey::KeyA, ShiftLevel::Base), "א".to_string());
+
+    let mut profile = KeyboardProfile::new(vec![spanish, hebrew]);
+    profile.active = Some(LayoutId::new("es"));
+    profile
+}
+
+#[test]
+fn test_json_round_trip() {
+    let profile = create_profile();
+    let json = profile.to_json().unwrap();
+    assert_eq!(KeyboardProfile::from_json(&json).unwrap(), profile);
+}
+
+#[test]
+fn test_toml_round_trip() {
+    let profile = create_profile();
+    let toml = profile.to_toml().unwrap();
+    assert_eq!(KeyboardProfile::from_toml(&toml).unwrap(), profile);
+}
+
+#[test]
+fn test_json_keys_strokes_by_key_and_level() {
+    let json = create_profile().to_json().unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let spanish = &value["layouts"][0];
+    assert_eq!(spanish["map"]["KeyE"]["AltGr"], "€");
+    assert_eq!(spanish["dead_keys"]["Quote"]["Base"]["accent"], "´");
+    assert_eq!(value["layouts"][1]["layout"]["direction"], "RTL");
+    assert!(value["layouts"][1].get("dead_keys").is_none());
+}
+
+#[test]
+fn test_serialization_is_stable() {
+    let first = create_profile().to_json().unwrap();
+    for _ in 0..10 {
+        assert_eq!(create_profile().to_json().unwrap(), first);
+    }
+}
+
+#[test]
+fn test_missing_layout_fields_take_defaults() {
+    let json = r#"{
+        "version": 1,
+        "platform": "linux",
+        "layouts": [{ "layout": { "id": "us" }, "map": { "KeyA": { "Base": "a" } } }]
+    }"#;
+    let profile = KeyboardProfile::from_json(json).unwrap();
+    let us = profile.layout(&LayoutId::new("us")).unwrap();
+    assert_eq!(us.layout.direction, KeyboardDirection::LTR);
+    assert!(us.dead_keys.is_empty());
+    assert_eq!(us.get(PhysicalKey::KeyA, ShiftLevel::Base), Some("a"));
+    assert!(profile.active.is_none());
+}
+
+#[test]
+fn test_newer_version_is_rejected() {
+    let json = r#"{ "version": 2, "layouts": "some future shape" }"#;
+    let err = KeyboardProfile::from_json(json).unwrap_err();
+    assert!(
+        matches!(&err, LayoutError::InvalidData { reason, .. } if reason.contains("version 2")),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_invalid_documents_are_reported() {
+    assert!(matches!(
+        KeyboardProfile::from_json("{"),
+        Err(LayoutError::InvalidData {
+            format: "keyboard profile JSON",
+            ..
+        })
+    ));
+    assert!(matches!(
+        KeyboardProfile::from_toml("version = 1\nlayouts = 3"),
+        Err(LayoutError::InvalidData {
+            format: "keyboard profile TOML",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_save_and_load_by_extension() {
+    let profile = create_profile();
+    for name in ["profile.json", "profile.TOML"] {
+        let path = temp_path(name);
+        profile.save(&path).unwrap();
+        assert_eq!(KeyboardProfile::load(&path).unwrap(), profile);
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[test]
+fn test_load_and_save_errors() {
+    assert!(matches!(
+        KeyboardProfile::load(temp_path("missing.json")),
+        Err(LayoutError::FileAccess { .. })
+    ));
+    assert!(matches!(
+        create_profile().save(temp_path("profile.yaml")),
+        Err(LayoutError::InvalidData { .. })
+    ));
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(
+        ProfileFormat::from_path(Path::new("a/b.json")),
+        Some(ProfileFormat::Json)
+    );
+    assert_eq!(
+        ProfileFormat::from_path(Path::new("b.Toml")),
+        Some(ProfileFormat::Toml)
+    );
+    assert_eq!(ProfileFormat::from_path(Path::new("b")), None);
+}
@@ -1,4 +1,6 @@
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+use super::physical_key::{KeyBlock, PhysicalKey};
+use super::types::{DeadKey, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use unicode_bidi::{BidiClass, bidi_class};
 
@@ -26,90 +28,314 @@ fn direction_bonus(text_dir_is_rtl: Option<bool>, layout_dir: KeyboardDirection)
     }
 }
 
-fn coverage_score(text: &str, inverse: &HashMap<char, Vec<u16>>) -> (i32, usize) {
-    let mut score: i32 = 0;
-    let mut matches: usize = 0;
-    for ch in text.chars() {
-        if ch.is_control() {
-            continue;
-        }
-        if inverse.contains_key(&ch) {
-            score += 2;
-            matches += 1;
-        } else {
-            score -= 1;
+/// Keystroke sequences that type each output on a layout, best first.
+/// Outputs may be several characters long, e.g. the Arabic lam-alef key.
+type Inverse = HashMap<String, Vec<Vec<KeyStroke>>>;
+
+/// Piece of text produced by [`tokenize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'t> {
+    /// An output of one key or key sequence on the layout.
+    Typed(&'t str),
+    /// A character no key types.
+    Untyped(char),
+}
+
+/// Splits `text` into layout outputs, always taking the longest output that
+/// matches at the current position.
+fn tokenize<'t>(text: &'t str, inverse: &Inverse) -> Vec<Token<'t>> {
+    let longest = inverse.keys().map(|output| output.chars().count()).max();
+    let longest = longest.unwrap_or(1);
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(first) = rest.chars().next() {
+        let ends: Vec<usize> = rest
+            .char_indices()
+            .skip(1)
+            .map(|(index, _)| index)
+            .chain(std::iter::once(rest.len()))
+            .take(longest)
+            .collect();
+        let matched = ends
+            .iter()
+            .rev()
+            .map(|&end| &rest[..end])
+            .find(|candidate| inverse.contains_key(*candidate));
+        let token = match matched {
+            Some(output) => Token::Typed(output),
+            None => Token::Untyped(first),
+        };
+        let consumed = match token {
+            Token::Typed(output) => output.len(),
+            Token::Untyped(c) => c.len_utf8(),
+        };
+        tokens.push(token);
+        rest = &rest[consumed..];
+    }
+    tokens
+}
+
+/// Number of characters of `text` that `inverse` types, and the characters
+/// it does not, ignoring control characters.
+fn count_matches(text: &str, inverse: &Inverse) -> (usize, Vec<char>) {
+    let mut matched = 0;
+    let mut unmatched = Vec::new();
+    for token in tokenize(text, inverse) {
+        match token {
+            Token::Untyped(c) if c.is_control() => {}
+            Token::Untyped(c) => unmatched.push(c),
+            Token::Typed(output) => matched += output.chars().filter(|c| !c.is_control()).count(),
         }
     }
-    (score, matches)
+    (matched, unmatched)
 }
 
-pub fn get_text_leyaout_map<'a>(
-    text: &'a str,
-    layout_maps: &'a [LayoutMap],
-) -> Option<&'a LayoutMap> {
-    let text_dir = text_starts_rtl(text);
+/// +2 per typed character, -1 per character the layout cannot type.
+fn coverage_score(matched: usize, unmatched: usize) -> i32 {
+    2 * matched as i32 - unmatched as i32
+}
 
-    // Build all inverse maps once
-    let inverses = invert_layout_maps(layout_maps);
+/// Divides the scores before they are turned into confidences, so that one
+/// more typed character makes a layout about three times as likely.
+const SCORE_TEMPERATURE: f64 = 2.0;
 
-    let mut best_idx: Option<usize> = None;
-    let mut best_score: i32 = i32::MIN;
-    let mut best_matches: usize = 0;
+/// How well one layout types a text, see [`rank_layouts`].
+#[derive(Debug, Clone)]
+pub struct LayoutCandidate<'a> {
+    pub layout: &'a LayoutMap,
+    /// Coverage score plus `direction_bonus`: +2 per typed character, -1 per
+    /// character the layout cannot type.
+    pub score: i32,
+    /// Share of the text's characters the layout types, `0.0..=1.0`.
+    pub coverage: f64,
+    pub matched: usize,
+    pub unmatched: usize,
+    /// The characters the layout cannot type, in text order, repeats
+    /// included.
+    pub unmatched_chars: Vec<char>,
+    /// Contribution of the text's direction matching the layout's.
+    pub direction_bonus: i32,
+    /// Softmax of `score` over all candidates; the confidences sum to one.
+    pub confidence: f64,
+}
 
-    for (idx, lm) in layout_maps.iter().enumerate() {
-        let inverse = &inverses[idx];
-        let (mut score, matches) = coverage_score(text, inverse);
-        score += direction_bonus(text_dir, lm.layout.direction);
+/// Scores every layout against `text`, best first. This is the ranking
+/// [`get_text_layout_map`] takes its answer from: candidates are ordered by
+/// score, then by matched characters, then by their order in `layout_maps`.
+pub fn rank_layouts<'a>(text: &str, layout_maps: &'a [LayoutMap]) -> Vec<LayoutCandidate<'a>> {
+    let text_dir = text_starts_rtl(text);
+    let mut candidates: Vec<LayoutCandidate<'a>> = layout_maps
+        .iter()
+        .map(|layout| {
+            let (matched, unmatched_chars) = count_matches(text, &invert_layout_map(layout));
+            let unmatched = unmatched_chars.len();
+            let direction_bonus = direction_bonus(text_dir, layout.layout.direction);
+            LayoutCandidate {
+                layout,
+                score: coverage_score(matched, unmatched) + direction_bonus,
+                coverage: match matched + unmatched {
+                    0 => 0.0,
+                    total => matched as f64 / total as f64,
+                },
+                matched,
+                unmatched,
+                unmatched_chars,
+                direction_bonus,
+                confidence: 0.0,
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then(b.matched.cmp(&a.matched)));
 
-        if score > best_score || (score == best_score && matches > best_matches) {
-            best_score = score;
-            best_idx = Some(idx);
-            best_matches = matches;
-        }
+    let best = candidates.first().map_or(0, |candidate| candidate.score);
+    let weights: Vec<f64> = candidates
+        .iter()
+        .map(|candidate| (f64::from(candidate.score - best) / SCORE_TEMPERATURE).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    for (candidate, weight) in candidates.iter_mut().zip(weights) {
+        candidate.confidence = weight / total;
     }
+    candidates
+}
 
-    if best_matches == 0 {
-        return None;
-    }
-    best_idx.map(|i| &layout_maps[i])
+pub fn get_text_layout_map<'a>(
+    text: &'a str,
+    layout_maps: &'a [LayoutMap],
+) -> Option<&'a LayoutMap> {
+    rank_layouts(text, layout_maps)
+        .into_iter()
+        .next()
+        .filter(|best| best.matched > 0)
+        .map(|best| best.layout)
+}
+
+#[deprecated(note = "use `get_text_layout_map`")]
+pub fn get_text_leyaout_map<'a>(
+    text: &'a str,
+    layout_maps: &'a [LayoutMap],
+) -> Option<&'a LayoutMap> {
+    get_text_layout_map(text, layout_maps)
 }
 
+/// Retypes `text`, written on `curent_layout`, with the same keystrokes on
+/// `target_layout`. The text is split by longest match against the outputs
+/// of `curent_layout`, so multi-character keys are retyped as one key.
+/// Characters typed through a dead key are replayed as both keystrokes, and
+/// dead keys on the target combine with the following key.
 pub fn shift_text_language(
     text: &str,
     curent_layout: &LayoutMap,
     target_layout: &LayoutMap,
 ) -> String {
-    let inverse_current = invert_layout_map(curent_layout);
-    text.chars()
-        .map(|ch| {
-            inverse_current
-                .get(&ch)
-                .and_then(|vks| vks.first())
-                .and_then(|vk| target_layout.map.get(vk))
-                .filter(|s| !s.is_empty())
-                .cloned()
-                .unwrap_or_else(|| ch.to_string())
-        })
-        .collect()
+    shift_text_language_with(text, curent_layout, target_layout, &KeyRanking::default())
 }
 
-fn invert_layout_map(layout_map: &LayoutMap) -> HashMap<char, Vec<u16>> {
-    let mut inverse: HashMap<char, Vec<u16>> = HashMap::new();
-    for (vk, output) in &layout_map.map {
-        let mut chars = output.chars();
-        let first = match chars.next() {
-            Some(c) => c,
-            None => continue,
+/// [`shift_text_language`] choosing, among the keys that type a character on
+/// `curent_layout`, the best one under `ranking`.
+pub fn shift_text_language_with(
+    text: &str,
+    curent_layout: &LayoutMap,
+    target_layout: &LayoutMap,
+    ranking: &KeyRanking,
+) -> String {
+    let inverse_current = invert_layout_map_ranked(curent_layout, ranking);
+    let mut output = String::new();
+    let mut pending: Option<&DeadKey> = None;
+    for token in tokenize(text, &inverse_current) {
+        let strokes = match token {
+            Token::Typed(typed) => inverse_current
+                .get(typed)
+                .and_then(|sequences| sequences.first())
+                .filter(|strokes| {
+                    strokes
+                        .iter()
+                        .all(|stroke| target_layout.action(*stroke).is_some())
+                }),
+            Token::Untyped(_) => None,
         };
-        if chars.next().is_none() {
-            inverse.entry(first).or_default().push(*vk);
+        match strokes {
+            Some(strokes) => {
+                for stroke in strokes {
+                    target_layout.press(*stroke, &mut pending, &mut output);
+                }
+            }
+            None => {
+                if let Some(dead) = pending.take() {
+                    output.push_str(&dead.accent);
+                }
+                match token {
+                    Token::Typed(typed) => output.push_str(typed),
+                    Token::Untyped(c) => output.push(c),
+                }
+            }
         }
     }
-    inverse
+    if let Some(dead) = pending {
+        output.push_str(&dead.accent);
+    }
+    output
 }
 
-fn invert_layout_maps(layout_maps: &[LayoutMap]) -> Vec<HashMap<char, Vec<u16>>> {
-    layout_maps.iter().map(invert_layout_map).collect()
+/// Preference order among keystroke sequences that type the same output,
+/// e.g. `1` on the number row and on the keypad. Used when text is retyped
+/// on another layout; the best-ranked sequence is replayed.
+///
+/// Single keys always rank ahead of dead-key sequences. Among sequences of
+/// the same length, strokes are compared one by one on their key's block,
+/// whether the key is universal, their shift level, the key's position and
+/// finally the level's place in [`ShiftLevel::ALL`], so the choice never
+/// depends on hash order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRanking {
+    /// Blocks in order of preference; blocks not listed rank last.
+    pub blocks: Vec<KeyBlock>,
+    /// Put keys some keyboards lack (see [`PhysicalKey::is_universal`])
+    /// behind keys every keyboard has.
+    pub prefer_universal_keys: bool,
+    /// Shift levels in order of preference; levels not listed rank last.
+    pub levels: Vec<ShiftLevel>,
+}
+
+impl Default for KeyRanking {
+    /// Main block, then control keys, then the keypad; universal keys first;
+    /// levels in [`ShiftLevel::ALL`] order.
+    fn default() -> Self {
+        KeyRanking {
+            blocks: vec![KeyBlock::Main, KeyBlock::Control, KeyBlock::Numpad],
+            prefer_universal_keys: true,
+            levels: ShiftLevel::ALL.to_vec(),
+        }
+    }
+}
+
+impl KeyRanking {
+    /// Sort key of one stroke. It ends with the key and its level in
+    /// [`ShiftLevel::ALL`] order, so distinct strokes never tie even when
+    /// `levels` leaves some levels out.
+    fn stroke_rank(&self, stroke: &KeyStroke) -> (usize, bool, usize, PhysicalKey, usize) {
+        let block = stroke.key.block();
+        let block_rank = self.blocks.iter().position(|preferred| *preferred == block);
+        let level_rank = self.levels.iter().position(|level| *level == stroke.level);
+        let level_order = ShiftLevel::ALL
+            .iter()
+            .position(|level| *level == stroke.level);
+        (
+            block_rank.unwrap_or(self.blocks.len()),
+            self.prefer_universal_keys && !stroke.key.is_universal(),
+            level_rank.unwrap_or(self.levels.len()),
+            stroke.key,
+            level_order.unwrap_or(ShiftLevel::ALL.len()),
+        )
+    }
+
+    /// Orders `a` and `b`, best first.
+    pub fn compare(&self, a: &[KeyStroke], b: &[KeyStroke]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| {
+            let a = a.iter().map(|stroke| self.stroke_rank(stroke));
+            a.cmp(b.iter().map(|stroke| self.stroke_rank(stroke)))
+        })
+    }
+}
+
+fn invert_layout_map(layout_map: &LayoutMap) -> Inverse {
+    invert_layout_map_ranked(layout_map, &KeyRanking::default())
+}
+
+fn invert_layout_map_ranked(layout_map: &LayoutMap, ranking: &KeyRanking) -> Inverse {
+    let mut inverse: Inverse = HashMap::new();
+    for (stroke, output) in &layout_map.map {
+        if !output.is_empty() {
+            inverse
+                .entry(output.clone())
+                .or_default()
+                .push(vec![*stroke]);
+        }
+    }
+    for sequences in inverse.values_mut() {
+        sequences.sort_by(|a, b| ranking.compare(a, b));
+    }
+
+    let mut dead_sequences: Vec<(String, Vec<KeyStroke>)> = Vec::new();
+    for (dead_stroke, dead_key) in &layout_map.dead_keys {
+        // Space is tried even when not listed, as it types the accent.
+        let space = (!dead_key.combinations.contains_key(&' ')).then_some(' ');
+        for base in dead_key.combinations.keys().copied().chain(space) {
+            let base = base.to_string();
+            let composed = dead_key.combine(&base);
+            let base_strokes = inverse.get(&base).and_then(|sequences| sequences.first());
+            if let Some(base_strokes) = base_strokes.filter(|_| !composed.is_empty()) {
+                dead_sequences.push((composed, vec![*dead_stroke, base_strokes[0]]));
+            }
+        }
+    }
+    for (output, strokes) in dead_sequences {
+        inverse.entry(output).or_default().push(strokes);
+    }
+    for sequences in inverse.values_mut() {
+        sequences.sort_by(|a, b| ranking.compare(a, b));
+    }
+    inverse
 }
 
 #[cfg(test)]
@@ -1,22 +1,27 @@
 use super::*;
+use crate::keyboard_mapping::physical_key::{KeyBlock, PhysicalKey};
+use crate::keyboard_mapping::types::{LayoutId, ShiftLevel};
 use std::collections::HashMap;
 
 fn create_test_layout(
     name: &str,
     direction: KeyboardDirection,
-    pairs: Vec<(u16, &str)>,
+    pairs: Vec<(PhysicalKey, &str)>,
 ) -> LayoutMap {
     let mut map = HashMap::new();
     for (vk, output) in pairs {
-        map.insert(vk, output.to_string());
+        map.insert(KeyStroke::new(vk, ShiftLevel::Base), output.to_string());
     }
 
     LayoutMap {
         layout: KeyboardLayout {
+            id: LayoutId::new(name),
             lang_name: name.to_string(),
             direction,
+            ..Default::default()
         },
         map,
+        ..Default::default()
     }
 }
 
@@ -25,32 +30,32 @@ fn create_en_layout() -> LayoutMap {
         "English",
         KeyboardDirection::LTR,
         vec![
-            (0x41, "A"),
-            (0x42, "B"),
-            (0x43, "C"),
-            (0x44, "D"),
-            (0x45, "E"),
-            (0x46, "F"),
-            (0x47, "G"),
-            (0x48, "H"),
-            (0x49, "I"),
-            (0x4A, "J"),
-            (0x4B, "K"),
-            (0x4C, "L"),
-            (0x4D, "M"),
-            (0x4E, "N"),
-            (0x4F, "O"),
-            (0x50, "P"),
-            (0x51, "Q"),
-            (0x52, "R"),
-            (0x53, "S"),
-            (0x54, "T"),
-            (0x55, "U"),
-            (0x56, "V"),
-            (0x57, "W"),
-            (0x58, "X"),
-            (0x59, "Y"),
-            (0x5A, "Z"),
+            (PhysicalKey::KeyA, "A"),
+            (PhysicalKey::KeyB, "B"),
+            (PhysicalKey::KeyC, "C"),
+            (PhysicalKey::KeyD, "D"),
+            (PhysicalKey::KeyE, "E"),
+            (PhysicalKey::KeyF, "F"),
+            (PhysicalKey::KeyG, "G"),
+            (PhysicalKey::KeyH, "H"),
+            (PhysicalKey::KeyI, "I"),
+            (PhysicalKey::KeyJ, "J"),
+            (PhysicalKey::KeyK, "K"),
+            (PhysicalKey::KeyL, "L"),
+            (PhysicalKey::KeyM, "M"),
+            (PhysicalKey::KeyN, "N"),
+            (PhysicalKey::KeyO, "O"),
+            (PhysicalKey::KeyP, "P"),
+            (PhysicalKey::KeyQ, "Q"),
+            (PhysicalKey::KeyR, "R"),
+            (PhysicalKey::KeyS, "S"),
+            (PhysicalKey::KeyT, "T"),
+            (PhysicalKey::KeyU, "U"),
+            (PhysicalKey::KeyV, "V"),
+            (PhysicalKey::KeyW, "W"),
+            (PhysicalKey::KeyX, "X"),
+            (PhysicalKey::KeyY, "Y"),
+            (PhysicalKey::KeyZ, "Z"),
         ],
     )
 }
@@ -60,32 +65,32 @@ fn create_he_layout() -> LayoutMap {
         "Hebrew",
         KeyboardDirection::RTL,
         vec![
-            (0x41, "ש"),
-            (0x42, "נ"),
-            (0x43, "ב"),
-            (0x44, "ג"),
-            (0x45, "כ"),
-            (0x46, "ע"),
-            (0x47, "י"),
-            (0x48, "ח"),
-            (0x49, "ל"),
-            (0x4A, "ך"),
-            (0x4B, "פ"),
-            (0x4C, "ם"),
-            (0x4D, "צ"),
-            (0x4E, "ת"),
-            (0x4F, "ק"),
-            (0x50, "ר"),
-            (0x51, "א"),
-            (0x52, "ט"),
-            (0x53, "ו"),
-            (0x54, "ן"),
-            (0x55, "מ"),
-            (0x56, "ה"),
-            (0x57, "ז"),
-            (0x58, "ס"),
-            (0x59, "ד"),
-            (0x5A, "ג"),
+            (PhysicalKey::KeyA, "ש"),
+            (PhysicalKey::KeyB, "נ"),
+            (PhysicalKey::KeyC, "ב"),
+            (PhysicalKey::KeyD, "ג"),
+            (PhysicalKey::KeyE, "כ"),
+            (PhysicalKey::KeyF, "ע"),
+            (PhysicalKey::KeyG, "י"),
+            (PhysicalKey::KeyH, "ח"),
+            (PhysicalKey::KeyI, "ל"),
+            (PhysicalKey::KeyJ, "ך"),
+            (PhysicalKey::KeyK, "פ"),
+            (PhysicalKey::KeyL, "ם"),
+            (PhysicalKey::KeyM, "צ"),
+            (PhysicalKey::KeyN, "ת"),
+            (PhysicalKey::KeyO, "ק"),
+            (PhysicalKey::KeyP, "ר"),
+            (PhysicalKey::KeyQ, "א"),
+            (PhysicalKey::KeyR, "ט"),
+            (PhysicalKey::KeyS, "ו"),
+            (PhysicalKey::KeyT, "ן"),
+            (PhysicalKey::KeyU, "מ"),
+            (PhysicalKey::KeyV, "ה"),
+            (PhysicalKey::KeyW, "ז"),
+            (PhysicalKey::KeyX, "ס"),
+            (PhysicalKey::KeyY, "ד"),
+            (PhysicalKey::KeyZ, "ג"),
         ],
     )
 }
@@ -95,32 +100,83 @@ fn create_ar_layout() -> LayoutMap {
         "Arabic",
         KeyboardDirection::RTL,
         vec![
-            (0x41, "ش"),
-            (0x42, "س"),
-            (0x43, "ي"),
-            (0x44, "ب"),
-            (0x45, "ل"),
-            (0x46, "ا"),
-            (0x47, "ت"),
-            (0x48, "ن"),
-            (0x49, "م"),
-            (0x4A, "ك"),
-            (0x4B, "ط"),
-            (0x4C, "ئ"),
-            (0x4D, "ء"),
-            (0x4E, "ؤ"),
-            (0x4F, "ر"),
-            (0x50, "لا"),
-            (0x51, "ى"),
-            (0x52, "ة"),
-            (0x53, "و"),
-            (0x54, "ز"),
-            (0x55, "ظ"),
-            (0x56, "د"),
-            (0x57, "ج"),
-            (0x58, "ح"),
-            (0x59, "خ"),
-            (0x5A, "ذ"),
+            (PhysicalKey::KeyA, "ش"),
+            (PhysicalKey::KeyB, "س"),
+            (PhysicalKey::KeyC, "ي"),
+            (PhysicalKey::KeyD, "ب"),
+            (PhysicalKey::KeyE, "ل"),
+            (PhysicalKey::KeyF, "ا"),
+            (PhysicalKey::KeyG, "ت"),
+            (PhysicalKey::KeyH, "ن"),
+            (PhysicalKey::KeyI, "م"),
+            (PhysicalKey::KeyJ, "ك"),
+            (PhysicalKey::KeyK, "ط"),
+            (PhysicalKey::KeyL, "ئ"),
+            (PhysicalKey::KeyM, "ء"),
+            (PhysicalKey::KeyN, "ؤ"),
+            (PhysicalKey::KeyO, "ر"),
+            (PhysicalKey::KeyP, "لا"),
+            (PhysicalKey::KeyQ, "ى"),
+            (PhysicalKey::KeyR, "ة"),
+            (PhysicalKey::KeyS, "و"),
+            (PhysicalKey::KeyT, "ز"),
+            (PhysicalKey::KeyU, "ظ"),
+            (PhysicalKey::KeyV, "د"),
+            (PhysicalKey::KeyW, "ج"),
+            (PhysicalKey::KeyX, "ح"),
+            (PhysicalKey::KeyY, "خ"),
+            (PhysicalKey::KeyZ, "ذ"),
+        ],
+    )
+}
+
+fn create_leveled_layout(
+    name: &str,
+    direction: KeyboardDirection,
+    keys: Vec<(PhysicalKey, &str, &str)>,
+) -> LayoutMap {
+    let mut map = HashMap::new();
+    for (vk, base, shifted) in keys {
+        map.insert(KeyStroke::new(vk, ShiftLevel::Base), base.to_string());
+        map.insert(KeyStroke::new(vk, ShiftLevel::Shift), shifted.to_string());
+    }
+
+    LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new(name),
+            lang_name: name.to_string(),
+            direction,
+            ..Default::default()
+        },
+        map,
+        ..Default::default()
+    }
+}
+
+fn create_en_leveled_layout() -> LayoutMap {
+    create_leveled_layout(
+        "English",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::Digit1, "1", "!"),
+            (PhysicalKey::KeyE, "e", "E"),
+            (PhysicalKey::KeyH, "h", "H"),
+            (PhysicalKey::KeyL, "l", "L"),
+            (PhysicalKey::KeyO, "o", "O"),
+        ],
+    )
+}
+
+fn create_ru_leveled_layout() -> LayoutMap {
+    create_leveled_layout(
+        "Russian",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::Digit1, "1", "!"),
+            (PhysicalKey::KeyE, "у", "У"),
+            (PhysicalKey::KeyH, "р", "Р"),
+            (PhysicalKey::KeyL, "д", "Д"),
+            (PhysicalKey::KeyO, "щ", "Щ"),
         ],
     )
 }
@@ -221,13 +277,25 @@ fn test_direction_bonus_no_text_direction() {
     assert_eq!(direction_bonus(None, KeyboardDirection::RTL), 0);
 }
 
+/// Coverage score and typed characters of `text`.
+fn score_text(text: &str, inverse: &Inverse) -> (i32, usize) {
+    let (matched, unmatched) = count_matches(text, inverse);
+    (coverage_score(matched, unmatched.len()), matched)
+}
+
 #[test]
 fn test_coverage_score_perfect_match() {
     let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
-    inverse.insert('B', vec![0x42]);
-
-    let (score, matches) = coverage_score("AB", &inverse);
+    inverse.insert(
+        "A".to_string(),
+        vec![vec![KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base)]],
+    );
+    inverse.insert(
+        "B".to_string(),
+        vec![vec![KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Base)]],
+    );
+
+    let (score, matches) = score_text("AB", &inverse);
     assert_eq!(matches, 2);
     assert_eq!(score, 4);
 }
@@ -235,9 +303,12 @@ fn test_coverage_score_perfect_match() {
 #[test]
 fn test_coverage_score_partial_match() {
     let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
+    inverse.insert(
+        "A".to_string(),
+        vec![vec![KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base)]],
+    );
 
-    let (score, matches) = coverage_score("ABC", &inverse);
+    let (score, matches) = score_text("ABC", &inverse);
     assert_eq!(matches, 1);
     assert_eq!(score, 0);
 }
@@ -246,7 +317,7 @@ fn test_coverage_score_partial_match() {
 fn test_coverage_score_no_match() {
     let inverse = HashMap::new();
 
-    let (score, matches) = coverage_score("ABC", &inverse);
+    let (score, matches) = score_text("ABC", &inverse);
     assert_eq!(matches, 0);
     assert_eq!(score, -3);
 }
@@ -254,9 +325,12 @@ fn test_coverage_score_no_match() {
 #[test]
 fn test_coverage_score_ignores_control_chars() {
     let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
+    inverse.insert(
+        "A".to_string(),
+        vec![vec![KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base)]],
+    );
 
-    let (score, matches) = coverage_score("A\n\t", &inverse);
+    let (score, matches) = score_text("A\n\t", &inverse);
     assert_eq!(matches, 1);
     assert_eq!(score, 2);
 }
@@ -264,7 +338,7 @@ fn test_coverage_score_ignores_control_chars() {
 #[test]
 fn test_get_text_layout_map_single_match() {
     let layouts = vec![create_en_layout()];
-    let result = get_text_leyaout_map("HELLO", &layouts);
+    let result = get_text_layout_map("HELLO", &layouts);
 
     assert!(result.is_some());
     assert_eq!(result.unwrap().layout.lang_name, "English");
@@ -273,7 +347,7 @@ fn test_get_text_layout_map_single_match() {
 #[test]
 fn test_get_text_layout_map_multiple_layouts_best_match() {
     let layouts = vec![create_en_layout(), create_he_layout()];
-    let result = get_text_leyaout_map("HELLO", &layouts);
+    let result = get_text_layout_map("HELLO", &layouts);
 
     assert!(result.is_some());
     assert_eq!(result.unwrap().layout.lang_name, "English");
@@ -282,7 +356,7 @@ fn test_get_text_layout_map_multiple_layouts_best_match() {
 #[test]
 fn test_get_text_layout_map_rtl_preference() {
     let layouts = vec![create_en_layout(), create_he_layout()];
-    let result = get_text_leyaout_map("שלום", &layouts);
+    let result = get_text_layout_map("שלום", &layouts);
 
     assert!(result.is_some());
     assert_eq!(result.unwrap().layout.lang_name, "Hebrew");
@@ -291,7 +365,7 @@ fn test_get_text_layout_map_rtl_preference() {
 #[test]
 fn test_get_text_layout_map_no_match() {
     let layouts = vec![create_en_layout()];
-    let result = get_text_leyaout_map("中文", &layouts);
+    let result = get_text_layout_map("中文", &layouts);
 
     assert!(result.is_none());
 }
@@ -299,11 +373,20 @@ fn test_get_text_layout_map_no_match() {
 #[test]
 fn test_get_text_layout_map_empty_text() {
     let layouts = vec![create_en_layout()];
-    let result = get_text_leyaout_map("", &layouts);
+    let result = get_text_layout_map("", &layouts);
 
     assert!(result.is_none());
 }
 
+#[test]
+#[allow(deprecated)]
+fn test_get_text_leyaout_map_is_an_alias() {
+    let layouts = vec![create_en_layout(), create_he_layout()];
+    let result = get_text_leyaout_map("שלום", &layouts);
+
+    assert_eq!(result.unwrap().layout.lang_name, "Hebrew");
+}
+
 #[test]
 fn test_shift_text_language_full_match() {
     let current = create_en_layout();
@@ -334,29 +417,41 @@ fn test_shift_text_language_no_match() {
 #[test]
 fn test_shift_text_language_multi_char_sequences() {
     let mut current_map = HashMap::new();
-    current_map.insert(0x50, "لا".to_string());
+    current_map.insert(
+        KeyStroke::new(PhysicalKey::KeyP, ShiftLevel::Base),
+        "لا".to_string(),
+    );
 
     let mut target_map = HashMap::new();
-    target_map.insert(0x50, "LA".to_string());
+    target_map.insert(
+        KeyStroke::new(PhysicalKey::KeyP, ShiftLevel::Base),
+        "LA".to_string(),
+    );
 
     let current = LayoutMap {
         layout: KeyboardLayout {
+            id: LayoutId::new("arabic"),
             lang_name: "Arabic".to_string(),
             direction: KeyboardDirection::RTL,
+            ..Default::default()
         },
         map: current_map,
+        ..Default::default()
     };
 
     let target = LayoutMap {
         layout: KeyboardLayout {
+            id: LayoutId::new("english"),
             lang_name: "English".to_string(),
             direction: KeyboardDirection::LTR,
+            ..Default::default()
         },
         map: target_map,
+        ..Default::default()
     };
 
     let result = shift_text_language("لا", &current, &target);
-    assert_eq!(result, "لا");
+    assert_eq!(result, "LA");
 }
 
 #[test]
@@ -364,53 +459,496 @@ fn test_invert_layout_map_single_chars() {
     let layout = create_en_layout();
     let inverse = invert_layout_map(&layout);
 
-    assert_eq!(inverse.get(&'A'), Some(&vec![0x41]));
-    assert_eq!(inverse.get(&'B'), Some(&vec![0x42]));
+    assert_eq!(
+        inverse.get("A"),
+        Some(&vec![vec![KeyStroke::new(
+            PhysicalKey::KeyA,
+            ShiftLevel::Base
+        )]])
+    );
+    assert_eq!(
+        inverse.get("B"),
+        Some(&vec![vec![KeyStroke::new(
+            PhysicalKey::KeyB,
+            ShiftLevel::Base
+        )]])
+    );
 }
 
 #[test]
-fn test_invert_layout_map_ignores_multi_char() {
+fn test_invert_layout_map_keeps_multi_char() {
     let mut map = HashMap::new();
-    map.insert(0x41, "AB".to_string());
-    map.insert(0x42, "C".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "AB".to_string(),
+    );
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Base),
+        "C".to_string(),
+    );
 
     let layout = LayoutMap {
         layout: KeyboardLayout {
+            id: LayoutId::new("test"),
             lang_name: "Test".to_string(),
             direction: KeyboardDirection::LTR,
+            ..Default::default()
         },
         map,
+        ..Default::default()
     };
 
     let inverse = invert_layout_map(&layout);
-    assert!(!inverse.contains_key(&'A'));
-    assert_eq!(inverse.get(&'C'), Some(&vec![0x42]));
+    assert!(!inverse.contains_key("A"));
+    assert_eq!(
+        inverse.get("AB"),
+        Some(&vec![vec![KeyStroke::new(
+            PhysicalKey::KeyA,
+            ShiftLevel::Base
+        )]])
+    );
+    assert_eq!(
+        inverse.get("C"),
+        Some(&vec![vec![KeyStroke::new(
+            PhysicalKey::KeyB,
+            ShiftLevel::Base
+        )]])
+    );
 }
 
 #[test]
 fn test_invert_layout_map_empty_strings() {
     let mut map = HashMap::new();
-    map.insert(0x41, "".to_string());
-    map.insert(0x42, "A".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "".to_string(),
+    );
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Base),
+        "A".to_string(),
+    );
 
     let layout = LayoutMap {
         layout: KeyboardLayout {
+            id: LayoutId::new("test"),
             lang_name: "Test".to_string(),
             direction: KeyboardDirection::LTR,
+            ..Default::default()
         },
         map,
+        ..Default::default()
     };
 
     let inverse = invert_layout_map(&layout);
-    assert_eq!(inverse.get(&'A'), Some(&vec![0x42]));
+    assert_eq!(
+        inverse.get("A"),
+        Some(&vec![vec![KeyStroke::new(
+            PhysicalKey::KeyB,
+            ShiftLevel::Base
+        )]])
+    );
+}
+
+#[test]
+fn test_shift_text_language_keeps_shift_level() {
+    let current = create_en_leveled_layout();
+    let target = create_ru_leveled_layout();
+
+    let result = shift_text_language("Hello!", &current, &target);
+    assert_eq!(result, "Руддщ!");
+}
+
+#[test]
+fn test_shift_text_language_round_trip_with_levels() {
+    let en = create_en_leveled_layout();
+    let ru = create_ru_leveled_layout();
+
+    let shifted = shift_text_language("HeLLo", &en, &ru);
+    assert_eq!(shift_text_language(&shifted, &ru, &en), "HeLLo");
 }
 
 #[test]
-fn test_invert_layout_maps_batch() {
+fn test_invert_layout_map_prefers_lower_level() {
+    let mut map = HashMap::new();
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Caps),
+        "A".to_string(),
+    );
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift),
+        "A".to_string(),
+    );
+
+    let layout = LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new("test"),
+            lang_name: "Test".to_string(),
+            direction: KeyboardDirection::LTR,
+            ..Default::default()
+        },
+        map,
+        ..Default::default()
+    };
+
+    let inverse = invert_layout_map(&layout);
+    assert_eq!(
+        inverse.get("A").and_then(|sequences| sequences.first()),
+        Some(&vec![KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift)])
+    );
+}
+
+#[test]
+fn test_shift_text_language_caps_falls_back_to_base() {
+    let mut current = create_en_leveled_layout();
+    current.map.insert(
+        KeyStroke::new(PhysicalKey::KeyH, ShiftLevel::Caps),
+        "H".to_string(),
+    );
+    current
+        .map
+        .remove(&KeyStroke::new(PhysicalKey::KeyH, ShiftLevel::Shift));
+    let target = create_he_layout();
+
+    let result = shift_text_language("H", &current, &target);
+    assert_eq!(result, "ח");
+}
+
+fn create_es_dead_key_layout() -> LayoutMap {
+    let mut layout = create_test_layout(
+        "Spanish",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::KeyA, "a"),
+            (PhysicalKey::KeyE, "e"),
+            (PhysicalKey::KeyN, "n"),
+            (PhysicalKey::Space, " "),
+        ],
+    );
+    layout.dead_keys.insert(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base),
+        DeadKey {
+            accent: "´".to_string(),
+            combinations: [('a', "á"), ('e', "é")]
+                .into_iter()
+                .map(|(base, composed)| (base, composed.to_string()))
+                .collect(),
+        },
+    );
+    layout
+}
+
+fn create_us_punctuation_layout() -> LayoutMap {
+    create_test_layout(
+        "English",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::KeyA, "a"),
+            (PhysicalKey::KeyE, "e"),
+            (PhysicalKey::KeyN, "n"),
+            (PhysicalKey::Quote, "'"),
+            (PhysicalKey::Space, " "),
+        ],
+    )
+}
+
+#[test]
+fn test_invert_layout_map_composes_through_dead_keys() {
+    let inverse = invert_layout_map(&create_es_dead_key_layout());
+    let acute = KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base);
+
+    assert_eq!(
+        inverse.get("é"),
+        Some(&vec![vec![
+            acute,
+            KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Base)
+        ]])
+    );
+    assert_eq!(
+        inverse.get("´"),
+        Some(&vec![vec![
+            acute,
+            KeyStroke::new(PhysicalKey::Space, ShiftLevel::Base)
+        ]])
+    );
+}
+
+#[test]
+fn test_shift_text_language_replays_dead_key_sequences() {
+    let es = create_es_dead_key_layout();
+    let us = create_us_punctuation_layout();
+
+    assert_eq!(shift_text_language("né", &es, &us), "n'e");
+    assert_eq!(shift_text_language("n'e", &us, &es), "né");
+    assert_eq!(shift_text_language("a'", &us, &es), "a´");
+    assert_eq!(shift_text_language("'x", &us, &es), "´x");
+}
+
+#[test]
+fn test_shift_text_language_replays_accent_through_space() {
+    let mut es = create_es_dead_key_layout();
+    es.dead_keys.insert(
+        KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Shift),
+        DeadKey {
+            accent: "`".to_string(),
+            combinations: [('e', "è".to_string())].into_iter().collect(),
+        },
+    );
+    assert_eq!(shift_text_language("`", &es, &es), "`");
+    assert_eq!(shift_text_language("è`a", &es, &es), "è`a");
+
+    // A Space entry is the same sequence as the accent, not a second one.
+    let acute = KeyStroke::new(PhysicalKey::Quote, ShiftLevel::Base);
+    es.dead_keys
+        .get_mut(&acute)
+        .unwrap()
+        .combinations
+        .insert(' ', "´".to_string());
+    let inverse = invert_layout_map(&es);
+    assert_eq!(
+        inverse["´"],
+        [vec![
+            acute,
+            KeyStroke::new(PhysicalKey::Space, ShiftLevel::Base)
+        ]]
+    );
+}
+
+#[test]
+fn test_tokenize_prefers_longest_output() {
+    let inverse = invert_layout_map(&create_ar_layout());
+    assert_eq!(
+        tokenize("سلام!", &inverse),
+        [
+            Token::Typed("س"),
+            Token::Typed("لا"),
+            Token::Typed("م"),
+            Token::Untyped('!')
+        ]
+    );
+}
+
+#[test]
+fn test_coverage_score_counts_multi_char_outputs() {
+    let inverse = invert_layout_map(&create_ar_layout());
+    assert_eq!(score_text("سلا", &inverse), (6, 3));
+}
+
+#[test]
+fn test_shift_text_language_round_trips_lam_alef() {
+    let ar = create_ar_layout();
+    let en = create_en_layout();
+
+    let latin = shift_text_language("سلام", &ar, &en);
+    assert_eq!(latin, "BPI");
+    assert_eq!(shift_text_language(&latin, &en, &ar), "سلام");
+}
+
+#[test]
+fn test_rank_layouts_returns_every_layout_best_first() {
+    let layouts = vec![create_en_layout(), create_he_layout(), create_ar_layout()];
+    let ranked = rank_layouts("שלום", &layouts);
+    assert_eq!(ranked.len(), 3);
+    assert_eq!(ranked[0].layout.layout.lang_name, "Hebrew");
+    assert!(ranked.windows(2).all(|pair| pair[0].score >= pair[1].score));
+}
+
+#[test]
+fn test_rank_layouts_explains_scores() {
     let layouts = vec![create_en_layout(), create_he_layout()];
-    let inverses = invert_layout_maps(&layouts);
+    let ranked = rank_layouts("HI!", &layouts);
+    let english = &ranked[0];
+    assert_eq!(english.layout.layout.lang_name, "English");
+    assert_eq!((english.matched, english.unmatched), (2, 1));
+    assert_eq!(english.unmatched_chars, ['!']);
+    assert!((english.coverage - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(english.direction_bonus, 3);
+    assert_eq!(english.score, 2 * 2 - 1 + 3);
+
+    let hebrew = &ranked[1];
+    assert_eq!((hebrew.matched, hebrew.unmatched), (0, 3));
+    assert_eq!(hebrew.unmatched_chars, ['H', 'I', '!']);
+    assert_eq!(hebrew.coverage, 0.0);
+    assert_eq!(hebrew.direction_bonus, -2);
+    assert_eq!(hebrew.score, -5);
+}
+
+#[test]
+fn test_rank_layouts_confidences_sum_to_one() {
+    let layouts = vec![create_en_layout(), create_he_layout(), create_ar_layout()];
+    let ranked = rank_layouts("HELLO", &layouts);
+    let total: f64 = ranked.iter().map(|candidate| candidate.confidence).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    assert!(ranked[0].confidence > 0.99);
+}
 
-    assert_eq!(inverses.len(), 2);
-    assert_eq!(inverses[0].get(&'A'), Some(&vec![0x41]));
-    assert_eq!(inverses[1].get(&'ש'), Some(&vec![0x41]));
+#[test]
+fn test_rank_layouts_ties_keep_input_order() {
+    let layouts = vec![create_he_layout(), create_he_layout()];
+    let ranked = rank_layouts("שלום", &layouts);
+    assert!(std::ptr::eq(ranked[0].layout, &layouts[0]));
+    assert_eq!(ranked[0].score, ranked[1].score);
+    assert!((ranked[0].confidence - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_rank_layouts_empty_text() {
+    let layouts = vec![create_en_layout()];
+    let ranked = rank_layouts("", &layouts);
+    assert_eq!(ranked[0].score, 0);
+    assert_eq!(ranked[0].coverage, 0.0);
+    assert_eq!(ranked[0].confidence, 1.0);
+    assert!(rank_layouts("text", &[]).is_empty());
+}
+
+fn create_duplicate_keys_layout() -> LayoutMap {
+    let mut layout = create_test_layout(
+        "Duplicates",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::Numpad1, "1"),
+            (PhysicalKey::Digit1, "1"),
+            (PhysicalKey::NumpadDivide, "/"),
+            (PhysicalKey::Slash, "/"),
+            (PhysicalKey::IntlBackslash, "<"),
+        ],
+    );
+    layout.map.insert(
+        KeyStroke::new(PhysicalKey::Comma, ShiftLevel::Shift),
+        "<".to_string(),
+    );
+    layout.map.insert(
+        KeyStroke::new(PhysicalKey::Digit7, ShiftLevel::Shift),
+        "/".to_string(),
+    );
+    layout
+}
+
+fn first_sequence(inverse: &Inverse, output: &str) -> Vec<KeyStroke> {
+    inverse[output][0].clone()
+}
+
+#[test]
+fn test_invert_layout_map_prefers_main_block() {
+    let inverse = invert_layout_map(&create_duplicate_keys_layout());
+    assert_eq!(
+        first_sequence(&inverse, "1"),
+        [KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::Base)]
+    );
+    // The main block wins even over a lower level on the keypad.
+    assert_eq!(
+        first_sequence(&inverse, "/"),
+        [KeyStroke::new(PhysicalKey::Slash, ShiftLevel::Base)]
+    );
+}
+
+#[test]
+fn test_invert_layout_map_prefers_universal_keys() {
+    let inverse = invert_layout_map(&create_duplicate_keys_layout());
+    assert_eq!(
+        first_sequence(&inverse, "<"),
+        [KeyStroke::new(PhysicalKey::Comma, ShiftLevel::Shift)]
+    );
+}
+
+#[test]
+fn test_invert_layout_map_is_deterministic() {
+    let expected = invert_layout_map(&create_duplicate_keys_layout());
+    for _ in 0..20 {
+        // Every HashMap gets a new hash seed, so iteration order varies.
+        let inverse = invert_layout_map(&create_duplicate_keys_layout());
+        assert_eq!(inverse, expected);
+    }
+}
+
+#[test]
+fn test_key_ranking_is_configurable() {
+    let layout = create_duplicate_keys_layout();
+    let ranking = KeyRanking {
+        blocks: vec![KeyBlock::Numpad, KeyBlock::Main],
+        prefer_universal_keys: false,
+        ..Default::default()
+    };
+    let inverse = invert_layout_map_ranked(&layout, &ranking);
+    assert_eq!(
+        first_sequence(&inverse, "1"),
+        [KeyStroke::new(PhysicalKey::Numpad1, ShiftLevel::Base)]
+    );
+    assert_eq!(
+        first_sequence(&inverse, "<"),
+        [KeyStroke::new(PhysicalKey::IntlBackslash, ShiftLevel::Base)]
+    );
+}
+
+#[test]
+fn test_key_ranking_levels() {
+    let ranking = KeyRanking {
+        levels: vec![ShiftLevel::Shift, ShiftLevel::Base],
+        ..Default::default()
+    };
+    let base = [KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base)];
+    let shift = [KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Shift)];
+    let altgr = [KeyStroke::new(PhysicalKey::KeyC, ShiftLevel::AltGr)];
+    assert_eq!(ranking.compare(&shift, &base), std::cmp::Ordering::Less);
+    assert_eq!(ranking.compare(&altgr, &base), std::cmp::Ordering::Greater);
+    assert_eq!(
+        KeyRanking::default().compare(&base, &shift),
+        std::cmp::Ordering::Less
+    );
+}
+
+#[test]
+fn test_key_ranking_breaks_ties_on_unlisted_levels() {
+    let base_only = KeyRanking {
+        levels: vec![ShiftLevel::Base],
+        ..Default::default()
+    };
+    let create_current = || {
+        let mut layout = create_test_layout(
+            "Current",
+            KeyboardDirection::LTR,
+            vec![(PhysicalKey::KeyE, "e")],
+        );
+        for level in [ShiftLevel::Shift, ShiftLevel::AltGr] {
+            layout
+                .map
+                .insert(KeyStroke::new(PhysicalKey::KeyE, level), "€".to_string());
+        }
+        layout
+    };
+    let mut target = create_test_layout("Target", KeyboardDirection::LTR, vec![]);
+    target.map.insert(
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift),
+        "E".to_string(),
+    );
+    target.map.insert(
+        KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::AltGr),
+        "ę".to_string(),
+    );
+    for _ in 0..20 {
+        // Every HashMap gets a new hash seed, so iteration order varies.
+        let current = create_current();
+        assert_eq!(
+            shift_text_language_with("€", &current, &target, &base_only),
+            "E"
+        );
+    }
+}
+
+#[test]
+fn test_shift_text_language_with_ranking() {
+    let current = create_duplicate_keys_layout();
+    let target = create_test_layout(
+        "Target",
+        KeyboardDirection::LTR,
+        vec![(PhysicalKey::Digit1, "!"), (PhysicalKey::Numpad1, "1")],
+    );
+    assert_eq!(shift_text_language("1", &current, &target), "!");
+    let keypad_first = KeyRanking {
+        blocks: vec![KeyBlock::Numpad],
+        ..Default::default()
+    };
+    assert_eq!(
+        shift_text_language_with("1", &current, &target, &keypad_first),
+        "1"
+    );
 }
@@ -1,21 +1,350 @@
-use std::collections::HashMap;
+use super::language;
+use super::physical_key::PhysicalKey;
+use super::remap::is_rtl_char;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Stable identifier of an installed layout, accepted by every backend.
+///
+/// On Linux this is the XKB layout name with an optional variant in
+/// parentheses (`"us"`, `"il(phonetic)"`); on Windows it is the keyboard
+/// layout handle in hexadecimal (`"040D040D"`). Unlike a group index it stays
+/// valid across process restarts and layout reordering.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LayoutId(String);
+
+impl LayoutId {
+    pub fn new(raw: impl Into<String>) -> Self {
+        LayoutId(raw.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for LayoutId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Fields missing from serialized data take their default, so documents
+/// written before a field existed still load.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeyboardLayout {
+    pub id: LayoutId,
     pub lang_name: String,
     pub direction: KeyboardDirection,
+    /// Layout variant, e.g. `phonetic` for `il(phonetic)`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Human-readable name, e.g. `Hebrew (phonetic)`.
+    pub description: String,
+    /// ISO 639 codes of the languages the layout is meant for.
+    pub languages: Vec<String>,
+    /// BCP-47 tag of the primary language, e.g. `he-IL`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language_tag: Option<String>,
+    /// ISO 15924 codes of the scripts the layout types, e.g. `Hebr`.
+    pub scripts: Vec<String>,
+    /// Short indicator label as shown in a panel, e.g. `HE`.
+    pub short_label: String,
+    /// Identifier the platform itself uses: the XKB `layout(variant)` on
+    /// Linux, the KLID (`0000040D`) on Windows.
+    pub native_id: String,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl KeyboardLayout {
+    /// Fills `language_tag`, `languages`, `scripts` and, when empty,
+    /// `short_label` from a BCP-47 tag such as `he-IL`, for layouts read from
+    /// files that only name their locale.
+    pub(crate) fn set_language_tag(&mut self, tag: &str) {
+        let tag = tag.replace('_', "-");
+        self.languages = language::iso639_2(&tag)
+            .map(str::to_string)
+            .into_iter()
+            .collect();
+        self.scripts = language::default_script(&tag)
+            .map(str::to_string)
+            .into_iter()
+            .collect();
+        if self.short_label.is_empty() {
+            let primary = tag.split('-').next().unwrap_or_default();
+            self.short_label = language::language_subtag(primary).to_uppercase();
+        }
+        self.language_tag = Some(tag);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KeyboardDirection {
+    #[default]
     LTR,
     RTL,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Modifier combination a key output was captured under.
+///
+/// `Caps` is the output with Caps Lock engaged and no other modifier held,
+/// which differs from `Base` only for keys the layout marks as caps-affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ShiftLevel {
+    Base,
+    Shift,
+    AltGr,
+    ShiftAltGr,
+    Caps,
+}
+
+impl ShiftLevel {
+    pub const ALL: [ShiftLevel; 5] = [
+        ShiftLevel::Base,
+        ShiftLevel::Shift,
+        ShiftLevel::AltGr,
+        ShiftLevel::ShiftAltGr,
+        ShiftLevel::Caps,
+    ];
+
+    /// Level a key falls back to when it has no distinct output at `self`.
+    pub fn fallback(self) -> Option<ShiftLevel> {
+        match self {
+            ShiftLevel::Base | ShiftLevel::Shift => None,
+            ShiftLevel::AltGr | ShiftLevel::Caps => Some(ShiftLevel::Base),
+            ShiftLevel::ShiftAltGr => Some(ShiftLevel::Shift),
+        }
+    }
+}
+
+/// A single key pressed at a given shift level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct KeyStroke {
+    pub key: PhysicalKey,
+    pub level: ShiftLevel,
+}
+
+impl KeyStroke {
+    pub fn new(key: PhysicalKey, level: ShiftLevel) -> Self {
+        KeyStroke { key, level }
+    }
+}
+
+/// A key that types nothing by itself but modifies the next key, e.g. the
+/// acute accent key on a Spanish layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadKey {
+    /// What the dead key types when followed by Space, e.g. `´`.
+    pub accent: String,
+    /// Composed output keyed by the character typed after the dead key,
+    /// e.g. `'e'` to `"é"`.
+    pub combinations: BTreeMap<char, String>,
+}
+
+impl DeadKey {
+    /// Output of this dead key followed by a key that types `next`. Space
+    /// types the accent unless `combinations` lists it; other keys it does
+    /// not combine with type the accent followed by their own output.
+    pub fn combine(&self, next: &str) -> String {
+        let mut chars = next.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if let Some(composed) = self.combinations.get(&c) {
+                return composed.clone();
+            }
+            if c == ' ' {
+                return self.accent.clone();
+            }
+        }
+        format!("{}{next}", self.accent)
+    }
+}
+
+/// What pressing a key does on a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction<'a> {
+    Text(&'a str),
+    Dead(&'a DeadKey),
+}
+
+/// Serializes `map` and `dead_keys` as nested tables keyed by key and then
+/// level, e.g. `{"KeyA": {"Base": "a", "Shift": "A"}}`, in a stable order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LayoutMap {
     pub layout: KeyboardLayout,
-    pub map: HashMap<u16, String>,
+    #[serde(with = "stroke_table")]
+    pub map: HashMap<KeyStroke, String>,
+    /// Dead keys, by the stroke that arms them. [`LayoutMap::insert_level`]
+    /// and [`LayoutMap::insert_dead_key`] keep a stroke in `map` or here,
+    /// never both; when both are filled directly, `map` wins.
+    #[serde(
+        default,
+        with = "stroke_table",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub dead_keys: HashMap<KeyStroke, DeadKey>,
+}
+
+/// Serde representation of a map keyed by [`KeyStroke`]. JSON and TOML only
+/// allow string keys, so strokes are split into a key table of level tables.
+mod stroke_table {
+    use super::{KeyStroke, PhysicalKey, ShiftLevel};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+
+    pub fn serialize<S, V>(map: &HashMap<KeyStroke, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        V: Serialize,
+    {
+        let mut table: BTreeMap<PhysicalKey, BTreeMap<ShiftLevel, &V>> = BTreeMap::new();
+        for (stroke, value) in map {
+            table
+                .entry(stroke.key)
+                .or_default()
+                .insert(stroke.level, value);
+        }
+        table.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, V>(deserializer: D) -> Result<HashMap<KeyStroke, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Deserialize<'de>,
+    {
+        let table = BTreeMap::<PhysicalKey, BTreeMap<ShiftLevel, V>>::deserialize(deserializer)?;
+        Ok(table
+            .into_iter()
+            .flat_map(|(key, levels)| {
+                levels
+                    .into_iter()
+                    .map(move |(level, value)| (KeyStroke::new(key, level), value))
+            })
+            .collect())
+    }
+}
+
+impl LayoutMap {
+    pub fn get(&self, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+        self.map
+            .get(&KeyStroke::new(key, level))
+            .map(String::as_str)
+    }
+
+    /// Output of `stroke`, following [`ShiftLevel::fallback`] when the key
+    /// has nothing distinct at that level.
+    pub fn resolve(&self, stroke: KeyStroke) -> Option<&str> {
+        match self.action(stroke)? {
+            KeyAction::Text(output) => Some(output),
+            KeyAction::Dead(_) => None,
+        }
+    }
+
+    /// What `stroke` does, following [`ShiftLevel::fallback`] like
+    /// [`LayoutMap::resolve`] but also reporting dead keys.
+    pub fn action(&self, stroke: KeyStroke) -> Option<KeyAction<'_>> {
+        let mut level = Some(stroke.level);
+        while let Some(current) = level {
+            let at_level = KeyStroke::new(stroke.key, current);
+            if let Some(output) = self.map.get(&at_level) {
+                return Some(KeyAction::Text(output));
+            }
+            if let Some(dead) = self.dead_keys.get(&at_level) {
+                return Some(KeyAction::Dead(dead));
+            }
+            level = current.fallback();
+        }
+        None
+    }
+
+    /// Types `strokes` in order the way the OS would: a dead key is held
+    /// until the next key and combined with it, and a trailing dead key types
+    /// its accent. Returns `None` if a stroke does nothing on this layout.
+    pub fn type_strokes(&self, strokes: &[KeyStroke]) -> Option<String> {
+        let mut output = String::new();
+        let mut pending = None;
+        for stroke in strokes {
+            if !self.press(*stroke, &mut pending, &mut output) {
+                return None;
+            }
+        }
+        if let Some(dead) = pending {
+            output.push_str(&dead.accent);
+        }
+        Some(output)
+    }
+
+    /// Presses one key: appends its output to `output`, or arms it in
+    /// `pending` if it is a dead key. Returns `false` if `stroke` does
+    /// nothing on this layout.
+    pub(crate) fn press<'a>(
+        &'a self,
+        stroke: KeyStroke,
+        pending: &mut Option<&'a DeadKey>,
+        output: &mut String,
+    ) -> bool {
+        let Some(action) = self.action(stroke) else {
+            return false;
+        };
+        match (action, pending.take()) {
+            (KeyAction::Text(text), Some(dead)) => output.push_str(&dead.combine(text)),
+            (KeyAction::Dead(next), Some(dead)) => output.push_str(&dead.combine(&next.accent)),
+            (KeyAction::Text(text), None) => output.push_str(text),
+            (KeyAction::Dead(dead), None) => *pending = Some(dead),
+        }
+        true
+    }
+
+    /// Direction of the script the unshifted keys type: RTL when any of
+    /// them types a right-to-left character.
+    pub(crate) fn base_direction(&self) -> KeyboardDirection {
+        let rtl = self.map.iter().any(|(stroke, output)| {
+            stroke.level == ShiftLevel::Base && output.chars().next().is_some_and(is_rtl_char)
+        });
+        if rtl {
+            KeyboardDirection::RTL
+        } else {
+            KeyboardDirection::LTR
+        }
+    }
+
+    /// Keys with an output or a dead key at any level, in key order.
+    pub(crate) fn keys(&self) -> BTreeSet<PhysicalKey> {
+        self.map
+            .keys()
+            .chain(self.dead_keys.keys())
+            .map(|stroke| stroke.key)
+            .collect()
+    }
+
+    /// Records `output` for `stroke` unless it only repeats what the key
+    /// already produces at the fallback level, or `stroke` already has an
+    /// output or a dead key.
+    pub fn insert_level(&mut self, stroke: KeyStroke, output: String) {
+        if let Some(fallback) = stroke.level.fallback()
+            && self.get(stroke.key, fallback) == Some(output.as_str())
+        {
+            return;
+        }
+        if !self.dead_keys.contains_key(&stroke) {
+            self.map.entry(stroke).or_insert(output);
+        }
+    }
+
+    /// Records a dead key at `stroke` unless the key already has the same
+    /// dead key at the fallback level, or `stroke` already has an output or
+    /// a dead key.
+    pub fn insert_dead_key(&mut self, stroke: KeyStroke, dead_key: DeadKey) {
+        if let Some(fallback) = stroke.level.fallback()
+            && self.dead_keys.get(&KeyStroke::new(stroke.key, fallback)) == Some(&dead_key)
+        {
+            return;
+        }
+        if !self.map.contains_key(&stroke) {
+            self.dead_keys.entry(stroke).or_insert(dead_key);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,5 @@
 use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
 use std::collections::HashMap;
 
 #[test]
@@ -18,8 +19,10 @@ fn test_keyboard_direction_copy() {
 #[test]
 fn test_keyboard_layout_creation() {
     let layout = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     assert_eq!(layout.lang_name, "English");
@@ -29,18 +32,24 @@ fn test_keyboard_layout_creation() {
 #[test]
 fn test_keyboard_layout_equality() {
     let layout1 = KeyboardLayout {
+        id: LayoutId::new("hebrew"),
         lang_name: "Hebrew".to_string(),
         direction: KeyboardDirection::RTL,
+        ..Default::default()
     };
 
     let layout2 = KeyboardLayout {
+        id: LayoutId::new("hebrew"),
         lang_name: "Hebrew".to_string(),
         direction: KeyboardDirection::RTL,
+        ..Default::default()
     };
 
     let layout3 = KeyboardLayout {
+        id: LayoutId::new("arabic"),
         lang_name: "Arabic".to_string(),
         direction: KeyboardDirection::RTL,
+        ..Default::default()
     };
 
     assert_eq!(layout1, layout2);
@@ -50,8 +59,10 @@ fn test_keyboard_layout_equality() {
 #[test]
 fn test_keyboard_layout_clone() {
     let original = KeyboardLayout {
+        id: LayoutId::new("french"),
         lang_name: "French".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let cloned = original.clone();
@@ -63,8 +74,10 @@ fn test_keyboard_layout_clone() {
 #[test]
 fn test_keyboard_layout_debug() {
     let layout = KeyboardLayout {
+        id: LayoutId::new("german"),
         lang_name: "German".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let debug_str = format!("{:?}", layout);
@@ -75,48 +88,78 @@ fn test_keyboard_layout_debug() {
 #[test]
 fn test_layout_map_creation() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
-    map.insert(0x42, "B".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "A".to_string(),
+    );
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Base),
+        "B".to_string(),
+    );
 
     let layout = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
-    let layout_map = LayoutMap { layout, map };
+    let layout_map = LayoutMap {
+        layout,
+        map,
+        ..Default::default()
+    };
 
     assert_eq!(layout_map.layout.lang_name, "English");
     assert_eq!(layout_map.map.len(), 2);
-    assert_eq!(layout_map.map.get(&0x41), Some(&"A".to_string()));
-    assert_eq!(layout_map.map.get(&0x42), Some(&"B".to_string()));
+    assert_eq!(
+        layout_map.get(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("A")
+    );
+    assert_eq!(
+        layout_map.get(PhysicalKey::KeyB, ShiftLevel::Base),
+        Some("B")
+    );
 }
 
 #[test]
 fn test_layout_map_equality() {
     let mut map1 = HashMap::new();
-    map1.insert(0x41, "A".to_string());
+    map1.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "A".to_string(),
+    );
 
     let mut map2 = HashMap::new();
-    map2.insert(0x41, "A".to_string());
+    map2.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "A".to_string(),
+    );
 
     let layout1 = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let layout2 = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let layout_map1 = LayoutMap {
         layout: layout1,
         map: map1,
+        ..Default::default()
     };
 
     let layout_map2 = LayoutMap {
         layout: layout2,
         map: map2,
+        ..Default::default()
     };
 
     assert_eq!(layout_map1, layout_map2);
@@ -125,16 +168,22 @@ fn test_layout_map_equality() {
 #[test]
 fn test_layout_map_clone() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "A".to_string(),
+    );
 
     let layout = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let original = LayoutMap {
         layout: layout.clone(),
         map: map.clone(),
+        ..Default::default()
     };
 
     let cloned = original.clone();
@@ -146,14 +195,23 @@ fn test_layout_map_clone() {
 #[test]
 fn test_layout_map_debug() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "A".to_string(),
+    );
 
     let layout = KeyboardLayout {
+        id: LayoutId::new("english"),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
-    let layout_map = LayoutMap { layout, map };
+    let layout_map = LayoutMap {
+        layout,
+        map,
+        ..Default::default()
+    };
 
     let debug_str = format!("{:?}", layout_map);
     assert!(debug_str.contains("English"));
@@ -163,15 +221,195 @@ fn test_layout_map_debug() {
 #[test]
 fn test_empty_layout_map() {
     let layout = KeyboardLayout {
+        id: LayoutId::new("empty"),
         lang_name: "Empty".to_string(),
         direction: KeyboardDirection::LTR,
+        ..Default::default()
     };
 
     let layout_map = LayoutMap {
         layout,
         map: HashMap::new(),
+        ..Default::default()
     };
 
     assert_eq!(layout_map.map.len(), 0);
     assert_eq!(layout_map.layout.lang_name, "Empty");
 }
+
+#[test]
+fn test_shift_level_order() {
+    assert!(ShiftLevel::Base < ShiftLevel::Shift);
+    assert!(ShiftLevel::Shift < ShiftLevel::AltGr);
+    assert!(ShiftLevel::AltGr < ShiftLevel::ShiftAltGr);
+    assert!(ShiftLevel::ShiftAltGr < ShiftLevel::Caps);
+    assert_eq!(ShiftLevel::ALL.len(), 5);
+}
+
+#[test]
+fn test_layout_map_levels_are_distinct() {
+    let mut map = HashMap::new();
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base),
+        "a".to_string(),
+    );
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift),
+        "A".to_string(),
+    );
+
+    let layout_map = LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new("english"),
+            lang_name: "English".to_string(),
+            direction: KeyboardDirection::LTR,
+            ..Default::default()
+        },
+        map,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        layout_map.get(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("a")
+    );
+    assert_eq!(
+        layout_map.get(PhysicalKey::KeyA, ShiftLevel::Shift),
+        Some("A")
+    );
+    assert_eq!(layout_map.get(PhysicalKey::KeyA, ShiftLevel::AltGr), None);
+}
+
+#[test]
+fn test_layout_map_resolve_falls_back() {
+    let mut layout_map = LayoutMap {
+        layout: KeyboardLayout {
+            id: LayoutId::new("english"),
+            lang_name: "English".to_string(),
+            direction: KeyboardDirection::LTR,
+            ..Default::default()
+        },
+        map: HashMap::new(),
+        ..Default::default()
+    };
+    layout_map.insert_level(
+        KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::Base),
+        "1".to_string(),
+    );
+    layout_map.insert_level(
+        KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::Shift),
+        "!".to_string(),
+    );
+    layout_map.insert_level(
+        KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::AltGr),
+        "1".to_string(),
+    );
+    layout_map.insert_level(
+        KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::ShiftAltGr),
+        "¡".to_string(),
+    );
+
+    assert_eq!(layout_map.get(PhysicalKey::Digit1, ShiftLevel::AltGr), None);
+    assert_eq!(
+        layout_map.resolve(KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::AltGr)),
+        Some("1")
+    );
+    assert_eq!(
+        layout_map.resolve(KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::ShiftAltGr)),
+        Some("¡")
+    );
+    assert_eq!(
+        layout_map.resolve(KeyStroke::new(PhysicalKey::Digit1, ShiftLevel::Caps)),
+        Some("1")
+    );
+}
+
+#[test]
+fn test_layout_id_round_trip() {
+    let id = LayoutId::new("il(phonetic)");
+    assert_eq!(id.as_str(), "il(phonetic)");
+    assert_eq!(id.to_string(), "il(phonetic)");
+    assert_eq!(LayoutId::new(id.to_string()), id);
+}
+
+#[test]
+fn test_keyboard_layout_default() {
+    let layout = KeyboardLayout::default();
+    assert_eq!(layout.id.as_str(), "");
+    assert_eq!(layout.direction, KeyboardDirection::LTR);
+}
+
+fn grave() -> DeadKey {
+    DeadKey {
+        accent: "`".to_string(),
+        combinations: [('a', "à".to_string())].into_iter().collect(),
+    }
+}
+
+#[test]
+fn test_dead_key_combine() {
+    let dead = grave();
+    assert_eq!(dead.combine("a"), "à");
+    assert_eq!(dead.combine("x"), "`x");
+    assert_eq!(dead.combine(" "), "`");
+    assert_eq!(dead.combine("  "), "`  ");
+
+    let mut spacing = grave();
+    spacing.combinations.insert(' ', "ˋ".to_string());
+    assert_eq!(spacing.combine(" "), "ˋ");
+}
+
+#[test]
+fn test_stroke_is_text_or_dead_key() {
+    let stroke = KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base);
+    let mut map = LayoutMap::default();
+    map.insert_level(stroke, "`".to_string());
+    map.insert_dead_key(stroke, grave());
+    assert!(map.dead_keys.is_empty());
+
+    let mut map = LayoutMap::default();
+    map.insert_dead_key(stroke, grave());
+    map.insert_level(stroke, "`".to_string());
+    assert!(map.map.is_empty());
+}
+
+#[test]
+fn test_type_strokes_handles_dead_keys() {
+    let grave_stroke = KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base);
+    let a = KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Base);
+    let mut layout_map = LayoutMap::default();
+    layout_map.insert_level(a, "a".to_string());
+    layout_map.insert_dead_key(grave_stroke, grave());
+
+    assert_eq!(layout_map.action(a), Some(KeyAction::Text("a")));
+    assert_eq!(layout_map.resolve(grave_stroke), None);
+    assert_eq!(
+        layout_map.type_strokes(&[grave_stroke, a, a]).as_deref(),
+        Some("àa")
+    );
+    assert_eq!(
+        layout_map.type_strokes(&[a, grave_stroke]).as_deref(),
+        Some("a`")
+    );
+    assert_eq!(
+        layout_map.type_strokes(&[KeyStroke::new(PhysicalKey::KeyB, ShiftLevel::Base)]),
+        None
+    );
+}
+
+#[test]
+fn test_dead_key_falls_back_across_levels() {
+    let grave_stroke = KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base);
+    let mut layout_map = LayoutMap::default();
+    layout_map.insert_dead_key(grave_stroke, grave());
+    layout_map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::AltGr),
+        grave(),
+    );
+
+    assert_eq!(layout_map.dead_keys.len(), 1);
+    assert!(matches!(
+        layout_map.action(KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::AltGr)),
+        Some(KeyAction::Dead(_))
+    ));
+}
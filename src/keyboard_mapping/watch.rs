@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::types::LayoutId;
+
+/// How often blocked workers check whether the watcher was dropped.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Change reported by [`LayoutWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutEvent {
+    /// The user switched to another layout.
+    ActiveChanged(LayoutId),
+    /// Layouts were added, removed or reordered. Cached maps, e.g. in a
+    /// `KeyboardContext`, should be invalidated.
+    LayoutsChanged,
+}
+
+/// Stream of [`LayoutEvent`]s returned by `watch_layout_changes()`.
+///
+/// Iterating blocks until the next event. Consecutive `ActiveChanged` events
+/// for the same layout are reported once. Dropping the watcher stops its
+/// background threads.
+pub struct LayoutWatcher {
+    events: Receiver<LayoutEvent>,
+    sender: Option<Sender<LayoutEvent>>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+    last_active: Option<LayoutId>,
+}
+
+/// Handle a worker thread reports events through.
+#[derive(Clone)]
+pub(crate) struct EventSink {
+    sender: Sender<LayoutEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl EventSink {
+    /// Returns `false` once nobody is listening any more.
+    pub(crate) fn send(&self, event: LayoutEvent) -> bool {
+        self.sender.send(event).is_ok()
+    }
+
+    /// Whether the owning watcher was dropped; workers should then return.
+    pub(crate) fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+impl LayoutWatcher {
+    pub(crate) fn new() -> Self {
+        let (sender, events) = mpsc::channel();
+        LayoutWatcher {
+            events,
+            sender: Some(sender),
+            stop: Arc::new(AtomicBool::new(false)),
+            workers: Vec::new(),
+            last_active: None,
+        }
+    }
+
+    /// Runs `work` on a background thread. Workers must return soon after
+    /// [`EventSink::stopped`] becomes true.
+    pub(crate) fn spawn(&mut self, work: impl FnOnce(EventSink) + Send + 'static) {
+        let sink = EventSink {
+            sender: self.sender.clone().expect("spawn after start"),
+            stop: Arc::clone(&self.stop),
+        };
+        self.workers.push(thread::spawn(move || work(sink)));
+    }
+
+    /// Records the layout active when watching started, so that it is not
+    /// reported as a change.
+    pub(crate) fn assume_active(&mut self, id: Option<LayoutId>) {
+        self.last_active = id;
+    }
+
+    /// Finishes setup; the stream ends once every worker has returned.
+    pub(crate) fn start(mut self) -> Self {
+        self.sender = None;
+        self
+    }
+
+    /// Waits at most `timeout` for the next event.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<LayoutEvent> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(remaining) {
+                Ok(event) => {
+                    if let Some(event) = self.dedupe(event) {
+                        return Some(event);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    fn dedupe(&mut self, event: LayoutEvent) -> Option<LayoutEvent> {
+        match &event {
+            LayoutEvent::ActiveChanged(id) if self.last_active.as_ref() == Some(id) => None,
+            LayoutEvent::ActiveChanged(id) => {
+                self.last_active = Some(id.clone());
+                Some(event)
+            }
+            LayoutEvent::LayoutsChanged => Some(event),
+        }
+    }
+}
+
+impl Iterator for LayoutWatcher {
+    type Item = LayoutEvent;
+
+    fn next(&mut self) -> Option<LayoutEvent> {
+        loop {
+            let event = self.events.recv().ok()?;
+            if let Some(event) = self.dedupe(event) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+impl Drop for LayoutWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
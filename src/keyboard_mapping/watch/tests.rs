@@ -0,0 +1,69 @@
+use super::*;
+
+fn active(id: &str) -> LayoutEvent {
+    LayoutEvent::ActiveChanged(LayoutId::new(id))
+}
+
+#[test]
+fn test_repeated_active_events_are_reported_once() {
+    let mut watcher = LayoutWatcher::new();
+    watcher.spawn(|sink| {
+        for event in [
+            active("us"),
+            active("us"),
+            LayoutEvent::LayoutsChanged,
+            active("il"),
+            active("us"),
+        ] {
+            sink.send(event);
+        }
+    });
+    let events: Vec<_> = watcher.start().collect();
+    assert_eq!(
+        events,
+        [
+            active("us"),
+            LayoutEvent::LayoutsChanged,
+            active("il"),
+            active("us")
+        ]
+    );
+}
+
+#[test]
+fn test_next_timeout_returns_none_when_idle() {
+    let mut watcher = LayoutWatcher::new();
+    watcher.spawn(|sink| {
+        while !sink.stopped() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+    let mut watcher = watcher.start();
+    assert_eq!(watcher.next_timeout(Duration::from_millis(50)), None);
+}
+
+#[test]
+fn test_drop_stops_workers() {
+    let finished = Arc::new(AtomicBool::new(false));
+    let mut watcher = LayoutWatcher::new();
+    let flag = Arc::clone(&finished);
+    watcher.spawn(move |sink| {
+        while !sink.stopped() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        flag.store(true, Ordering::Relaxed);
+    });
+    drop(watcher.start());
+    assert!(finished.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_assumed_active_layout_is_not_reported() {
+    let mut watcher = LayoutWatcher::new();
+    watcher.assume_active(Some(LayoutId::new("us")));
+    watcher.spawn(|sink| {
+        sink.send(active("us"));
+        sink.send(active("il"));
+    });
+    assert_eq!(watcher.start().collect::<Vec<_>>(), [active("il")]);
+}
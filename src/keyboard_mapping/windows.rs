@@ -1,14 +1,30 @@
 #![cfg(target_os = "windows")]
 use windows::Win32::{
-    Globalization::{GetLocaleInfoEx, LCIDToLocaleName, LOCALE_SLANGUAGE},
+    Globalization::{
+        GetLocaleInfoEx, LCIDToLocaleName, LOCALE_SISO639LANGNAME, LOCALE_SISO639LANGNAME2,
+        LOCALE_SLANGUAGE, LOCALE_SSCRIPTS,
+    },
     UI::Input::KeyboardAndMouse::{
-        GetKeyboardLayout, GetKeyboardLayoutList, HKL, MAPVK_VK_TO_VSC_EX, MapVirtualKeyExW,
+        GetKeyboardLayout, GetKeyboardLayoutList, HKL, MAPVK_VSC_TO_VK_EX, MapVirtualKeyExW,
         ToUnicodeEx,
     },
 };
 
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
-use std::collections::HashMap;
+use windows::Win32::{
+    Foundation::{LPARAM, WPARAM},
+    UI::Input::KeyboardAndMouse::{ActivateKeyboardLayout, KLF_SETFORPROCESS},
+    UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowThreadProcessId, PostMessageW, WM_INPUTLANGCHANGEREQUEST,
+    },
+};
+
+use super::physical_key::PhysicalKey;
+use super::types::{
+    DeadKey, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutId, LayoutMap, ShiftLevel,
+};
+use super::watch::{LayoutEvent, LayoutWatcher, POLL_INTERVAL};
+use crate::error::LayoutError;
+use std::collections::{BTreeMap, HashMap};
 
 #[cfg(target_os = "windows")]
 fn windows_langid_is_rtl(langid: u16) -> bool {
@@ -28,100 +44,414 @@ fn enumerate_hkls() -> Vec<HKL> {
     }
 }
 
-fn lang_name_from_langid(langid: u16) -> String {
-    unsafe {
-        let lcid = langid as u32;
-        const LOCALE_NAME_MAX_LENGTH: usize = 85;
-        let mut locale_name_buf = [0u16; LOCALE_NAME_MAX_LENGTH];
-        let len = LCIDToLocaleName(lcid, Some(&mut locale_name_buf), 0);
+fn hkl_bits(hkl: HKL) -> u32 {
+    hkl.0 as usize as u32
+}
 
-        if len <= 0 {
-            return format!("0x{:04X}", langid);
-        }
+fn layout_id_from_hkl(hkl: HKL) -> LayoutId {
+    LayoutId::new(format!("{:08X}", hkl_bits(hkl)))
+}
 
-        let bcp47 = String::from_utf16_lossy(&locale_name_buf[..(len as usize - 1)]);
+fn hkl_from_layout_id(id: &LayoutId) -> Result<HKL, LayoutError> {
+    let not_found = || LayoutError::LayoutNotFound { id: id.clone() };
+    let bits = u32::from_str_radix(id.as_str(), 16).map_err(|_| not_found())?;
+    enumerate_hkls()
+        .into_iter()
+        .find(|&hkl| hkl_bits(hkl) == bits)
+        .ok_or_else(not_found)
+}
 
-        let needed = GetLocaleInfoEx(
-            windows::core::PCWSTR(locale_name_buf.as_ptr()),
-            LOCALE_SLANGUAGE,
-            None,
-        );
+/// Null-terminated locale name (`he-IL`) of `langid`.
+fn locale_name_from_langid(langid: u16) -> Option<Vec<u16>> {
+    const LOCALE_NAME_MAX_LENGTH: usize = 85;
+    let mut buf = vec![0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { LCIDToLocaleName(langid as u32, Some(&mut buf), 0) };
+    if len <= 0 {
+        return None;
+    }
+    buf.truncate(len as usize);
+    Some(buf)
+}
 
+fn locale_info(locale_name: &[u16], lctype: u32) -> Option<String> {
+    unsafe {
+        let name = windows::core::PCWSTR(locale_name.as_ptr());
+        let needed = GetLocaleInfoEx(name, lctype, None);
         if needed <= 0 {
-            return bcp47;
+            return None;
         }
-
-        let mut display_buf = vec![0u16; needed as usize];
-        let written = GetLocaleInfoEx(
-            windows::core::PCWSTR(locale_name_buf.as_ptr()),
-            LOCALE_SLANGUAGE,
-            Some(display_buf.as_mut_slice()),
-        );
-
+        let mut buf = vec![0u16; needed as usize];
+        let written = GetLocaleInfoEx(name, lctype, Some(buf.as_mut_slice()));
         if written <= 0 {
-            return bcp47;
+            return None;
         }
+        let value = String::from_utf16_lossy(&buf[..(written as usize - 1)]);
+        (!value.is_empty()).then_some(value)
+    }
+}
 
-        let name = String::from_utf16_lossy(&display_buf[..(written as usize - 1)]);
-        if name.is_empty() {
-            return bcp47;
-        }
-        name
+fn lang_name_from_langid(langid: u16) -> String {
+    let Some(locale_name) = locale_name_from_langid(langid) else {
+        return format!("0x{:04X}", langid);
+    };
+    locale_info(&locale_name, LOCALE_SLANGUAGE)
+        .unwrap_or_else(|| String::from_utf16_lossy(&locale_name[..locale_name.len() - 1]))
+}
+
+/// KLID of a layout handle, e.g. `0000040D`. Handles whose high word is
+/// `Fxxx` refer to a layout id that only the registry resolves to a KLID;
+/// those keep the full handle in hex.
+fn klid_from_hkl(hkl: HKL) -> String {
+    let device = hkl_bits(hkl) >> 16;
+    if device & 0xF000 == 0xF000 {
+        format!("{:08X}", hkl_bits(hkl))
+    } else {
+        format!("{device:08X}")
     }
 }
 
 fn keyboard_layout_from_hkl(hkl: HKL) -> KeyboardLayout {
-    let langid = (hkl.0 as usize & 0xFFFF) as u16;
+    let bits = hkl_bits(hkl);
+    let langid = (bits & 0xFFFF) as u16;
+    let device = (bits >> 16) as u16;
     let direction = if windows_langid_is_rtl(langid) {
         KeyboardDirection::RTL
     } else {
         KeyboardDirection::LTR
     };
+    let lang_name = lang_name_from_langid(langid);
+    // The high word names the physical layout; it equals the language id
+    // for the language's default layout and differs for alternatives such
+    // as Dvorak or phonetic layouts.
+    let variant = (device != langid).then(|| format!("{device:04X}"));
+    let description = match &variant {
+        Some(variant) => format!("{lang_name} ({variant})"),
+        None => lang_name.clone(),
+    };
+    let locale_name = locale_name_from_langid(langid);
+    let info = |lctype| {
+        locale_name
+            .as_deref()
+            .and_then(|name| locale_info(name, lctype))
+    };
+    let scripts = info(LOCALE_SSCRIPTS)
+        .map(|scripts| {
+            scripts
+                .split(';')
+                .filter(|script| !script.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
     KeyboardLayout {
-        lang_name: lang_name_from_langid(langid),
+        id: layout_id_from_hkl(hkl),
+        lang_name,
         direction,
+        variant,
+        description,
+        languages: info(LOCALE_SISO639LANGNAME2).into_iter().collect(),
+        language_tag: locale_name
+            .as_deref()
+            .map(|name| String::from_utf16_lossy(&name[..name.len() - 1])),
+        scripts,
+        short_label: info(LOCALE_SISO639LANGNAME)
+            .map(|code| code.to_uppercase())
+            .unwrap_or_else(|| format!("{langid:04X}")),
+        native_id: klid_from_hkl(hkl),
+    }
+}
+
+const VK_SHIFT: usize = 0x10;
+const VK_CONTROL: usize = 0x11;
+const VK_MENU: usize = 0x12;
+const VK_CAPITAL: usize = 0x14;
+
+/// Virtual key `hkl` assigns to the physical `key`. Numpad keys are looked
+/// up directly since their scan codes map to navigation VKs without NumLock.
+fn layout_vk(key: PhysicalKey, hkl: HKL) -> Option<u32> {
+    if key.is_numpad() {
+        return key.windows_vk().map(u32::from);
     }
+    let vk = unsafe { MapVirtualKeyExW(key.scan_code() as u32, MAPVK_VSC_TO_VK_EX, Some(hkl)) };
+    (vk != 0).then_some(vk)
 }
 
-pub fn get_layout(index: usize) -> Option<KeyboardLayout> {
-    let hkls = enumerate_hkls();
-    hkls.get(index).map(|&hkl| keyboard_layout_from_hkl(hkl))
+/// Keyboard state passed to `ToUnicodeEx` to select `level`.
+/// AltGr is reported by Windows as Ctrl+Alt.
+fn level_key_state(level: ShiftLevel) -> [u8; 256] {
+    let mut state = [0u8; 256];
+    if matches!(level, ShiftLevel::Shift | ShiftLevel::ShiftAltGr) {
+        state[VK_SHIFT] = 0x80;
+    }
+    if matches!(level, ShiftLevel::AltGr | ShiftLevel::ShiftAltGr) {
+        state[VK_CONTROL] = 0x80;
+        state[VK_MENU] = 0x80;
+    }
+    if level == ShiftLevel::Caps {
+        state[VK_CAPITAL] = 0x01;
+    }
+    state
 }
 
-pub fn list_layouts() -> Vec<KeyboardLayout> {
-    let hkls = enumerate_hkls();
-    hkls.into_iter().map(keyboard_layout_from_hkl).collect()
+/// Result of pressing one key through `ToUnicodeEx`.
+enum Typed {
+    Nothing,
+    Text(String),
+    /// A dead key was armed; holds its spacing accent.
+    Dead(String),
 }
 
-pub fn vk_to_char_map_for_layout(hkl: HKL) -> LayoutMap {
-    let hkls = enumerate_hkls();
+/// Presses `key` at `level` on `hkl`. A dead key stays armed in the
+/// thread's keyboard state until the next call.
+fn press_key(key: PhysicalKey, vk: u32, level: ShiftLevel, hkl: HKL) -> Typed {
+    let state = level_key_state(level);
+    let mut buf = [0u16; 8];
+    let sc = key.scan_code() as u32;
+    let written = unsafe { ToUnicodeEx(vk, sc, &state, &mut buf, 0, Some(hkl)) };
+    match written {
+        0 => Typed::Nothing,
+        n if n < 0 => Typed::Dead(String::from_utf16_lossy(&buf[..1])),
+        n => Typed::Text(String::from_utf16_lossy(&buf[..n as usize])),
+    }
+}
+
+/// Disarms a dead key left armed by [`press_key`], which would otherwise
+/// combine with the next key probed.
+fn clear_dead_key(key: PhysicalKey, vk: u32, level: ShiftLevel, hkl: HKL) {
+    for _ in 0..2 {
+        if !matches!(press_key(key, vk, level, hkl), Typed::Dead(_)) {
+            break;
+        }
+    }
+}
+
+fn layout_map_from_hkl(hkl: HKL) -> LayoutMap {
     let layout = keyboard_layout_from_hkl(hkl);
-    unsafe {
-        let mut map: HashMap<u16, String> = HashMap::new();
-        let state = [0u8; 256];
-        let mut buf = [0u16; 8];
-        for vk in 0u16..=255u16 {
-            let sc = MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC_EX, Some(hkl));
-            if sc == 0 {
+    let mut layout_map = LayoutMap {
+        layout,
+        map: HashMap::new(),
+        dead_keys: HashMap::new(),
+    };
+    let mut dead_strokes = Vec::new();
+    for level in ShiftLevel::ALL {
+        for key in PhysicalKey::ALL {
+            let Some(vk) = layout_vk(key, hkl) else {
                 continue;
+            };
+            let stroke = KeyStroke::new(key, level);
+            match press_key(key, vk, level, hkl) {
+                Typed::Nothing => {}
+                Typed::Text(s) => layout_map.insert_level(stroke, s),
+                Typed::Dead(accent) => {
+                    clear_dead_key(key, vk, level, hkl);
+                    dead_strokes.push((stroke, vk, accent));
+                }
             }
-            let written = ToUnicodeEx(vk as u32, sc, &state, &mut buf, 0, Some(hkl));
-            if written > 0 {
-                let s = String::from_utf16_lossy(&buf[..written as usize]);
-                map.entry(vk).or_insert(s);
+        }
+    }
+
+    let bases: Vec<(KeyStroke, u32, char)> = layout_map
+        .map
+        .iter()
+        .filter_map(|(stroke, output)| {
+            let mut chars = output.chars();
+            let c = chars.next().filter(|_| chars.next().is_none())?;
+            Some((*stroke, layout_vk(stroke.key, hkl)?, c))
+        })
+        .collect();
+    for (stroke, vk, accent) in dead_strokes {
+        let mut dead_key = DeadKey {
+            accent,
+            combinations: BTreeMap::new(),
+        };
+        for &(base_stroke, base_vk, base) in &bases {
+            if !matches!(press_key(stroke.key, vk, stroke.level, hkl), Typed::Dead(_)) {
+                continue;
+            }
+            // Keys the dead key does not combine with type two characters.
+            match press_key(base_stroke.key, base_vk, base_stroke.level, hkl) {
+                Typed::Text(composed)
+                    if composed.chars().count() == 1 && !composed.starts_with(base) =>
+                {
+                    dead_key.combinations.insert(base, composed);
+                }
+                Typed::Text(_) => {}
+                Typed::Nothing | Typed::Dead(_) => {
+                    clear_dead_key(stroke.key, vk, stroke.level, hkl)
+                }
             }
         }
-        LayoutMap { layout, map }
+        layout_map.insert_dead_key(stroke, dead_key);
+    }
+    layout_map
+}
+
+/// Active layout of the foreground window, falling back to the calling
+/// thread's when no window has focus.
+fn foreground_hkl() -> HKL {
+    unsafe {
+        let window = GetForegroundWindow();
+        let thread = if window.is_invalid() {
+            0
+        } else {
+            GetWindowThreadProcessId(window, None)
+        };
+        GetKeyboardLayout(thread)
     }
 }
 
-pub fn vk_to_char_map_default() -> LayoutMap {
-    unsafe { vk_to_char_map_for_layout(GetKeyboardLayout(0)) }
+/// Handle over the installed layouts that builds their maps once.
+///
+/// The layout list and every [`LayoutMap`] are built on first use and reused
+/// by later calls until [`KeyboardContext::invalidate`] is called.
+#[derive(Default)]
+pub struct KeyboardContext {
+    maps: Option<Vec<LayoutMap>>,
+}
+
+impl KeyboardContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops everything cached so the next call re-enumerates the layouts.
+    pub fn invalidate(&mut self) {
+        self.maps = None;
+    }
+
+    pub fn all_layout_vk_maps(&mut self) -> Result<&[LayoutMap], LayoutError> {
+        if self.maps.is_none() {
+            let hkls = enumerate_hkls();
+            self.maps = Some(hkls.into_iter().map(layout_map_from_hkl).collect());
+        }
+        Ok(self.maps.as_deref().unwrap())
+    }
+
+    pub fn list_layouts(&mut self) -> Result<Vec<KeyboardLayout>, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        Ok(maps.iter().map(|map| map.layout.clone()).collect())
+    }
+
+    pub fn get_layout(&mut self, id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        Ok(maps
+            .iter()
+            .find(|map| &map.layout.id == id)
+            .map(|map| map.layout.clone()))
+    }
+
+    pub fn vk_to_char_map_for_layout(&mut self, id: &LayoutId) -> Result<&LayoutMap, LayoutError> {
+        let maps = self.all_layout_vk_maps()?;
+        maps.iter()
+            .find(|map| &map.layout.id == id)
+            .ok_or_else(|| LayoutError::LayoutNotFound { id: id.clone() })
+    }
+
+    /// Layout of the foreground window's thread, i.e. the one the user is
+    /// typing with, rather than the calling thread's.
+    pub fn current_layout(&mut self) -> Result<LayoutId, LayoutError> {
+        Ok(layout_id_from_hkl(foreground_hkl()))
+    }
+
+    /// Makes `id` the active layout of the foreground window and of this
+    /// process, and returns the layout active before so the switch can be
+    /// undone.
+    pub fn activate_layout(&mut self, id: &LayoutId) -> Result<LayoutId, LayoutError> {
+        let hkl = hkl_from_layout_id(id)?;
+        let previous = self.current_layout()?;
+        let failed = |api: &'static str, err: windows::core::Error| LayoutError::ToolFailed {
+            tool: api,
+            reason: err.message(),
+        };
+        unsafe {
+            let window = GetForegroundWindow();
+            if !window.is_invalid() {
+                PostMessageW(
+                    Some(window),
+                    WM_INPUTLANGCHANGEREQUEST,
+                    WPARAM(0),
+                    LPARAM(hkl.0 as isize),
+                )
+                .map_err(|err| failed("PostMessageW", err))?;
+            }
+            ActivateKeyboardLayout(hkl, KLF_SETFORPROCESS)
+                .map_err(|err| failed("ActivateKeyboardLayout", err))?;
+        }
+        Ok(previous)
+    }
+
+    /// Map of the active layout.
+    pub fn vk_to_char_map_default(&mut self) -> Result<&LayoutMap, LayoutError> {
+        let id = self.current_layout()?;
+        self.vk_to_char_map_for_layout(&id)
+    }
+}
+
+/// Follows the foreground window's layout and the installed layout list.
+///
+/// Windows only notifies the window whose thread switched layouts
+/// (`WM_INPUTLANGCHANGE`), so both are sampled every
+/// [`POLL_INTERVAL`] instead.
+pub fn watch_layout_changes() -> Result<LayoutWatcher, LayoutError> {
+    let mut watcher = LayoutWatcher::new();
+    watcher.spawn(|sink| {
+        let installed = || {
+            enumerate_hkls()
+                .into_iter()
+                .map(layout_id_from_hkl)
+                .collect::<Vec<_>>()
+        };
+        let mut layouts = installed();
+        let mut active = layout_id_from_hkl(foreground_hkl());
+        while !sink.stopped() {
+            std::thread::sleep(POLL_INTERVAL);
+            let current_layouts = installed();
+            if current_layouts != layouts {
+                layouts = current_layouts;
+                if !sink.send(LayoutEvent::LayoutsChanged) {
+                    return;
+                }
+            }
+            let current = layout_id_from_hkl(foreground_hkl());
+            if current != active {
+                active = current;
+                if !sink.send(LayoutEvent::ActiveChanged(active.clone())) {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(watcher.start())
+}
+
+pub fn activate_layout(id: &LayoutId) -> Result<LayoutId, LayoutError> {
+    KeyboardContext::new().activate_layout(id)
+}
+
+pub fn current_layout() -> Result<LayoutId, LayoutError> {
+    KeyboardContext::new().current_layout()
+}
+
+pub fn get_layout(id: &LayoutId) -> Result<Option<KeyboardLayout>, LayoutError> {
+    KeyboardContext::new().get_layout(id)
+}
+
+pub fn list_layouts() -> Result<Vec<KeyboardLayout>, LayoutError> {
+    KeyboardContext::new().list_layouts()
+}
+
+pub fn vk_to_char_map_for_layout(id: &LayoutId) -> Result<LayoutMap, LayoutError> {
+    hkl_from_layout_id(id).map(layout_map_from_hkl)
+}
+
+pub fn vk_to_char_map_default() -> Result<LayoutMap, LayoutError> {
+    vk_to_char_map_for_layout(&current_layout()?)
 }
 
-pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
-    let hkls = enumerate_hkls();
-    hkls.into_iter().map(vk_to_char_map_for_layout).collect()
+pub fn all_layout_vk_maps() -> Result<Vec<LayoutMap>, LayoutError> {
+    KeyboardContext::new()
+        .all_layout_vk_maps()
+        .map(<[LayoutMap]>::to_vec)
 }
 
 #[cfg(all(test, target_os = "windows"))]
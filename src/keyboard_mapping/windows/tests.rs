@@ -1,8 +1,10 @@
 use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::ShiftLevel;
 
 #[test]
 fn test_list_layouts_returns_non_empty() {
-    let layouts = list_layouts();
+    let layouts = list_layouts().unwrap();
     assert!(
         !layouts.is_empty(),
         "Windows should have at least one keyboard layout"
@@ -10,13 +12,13 @@ fn test_list_layouts_returns_non_empty() {
 }
 
 #[test]
-fn test_get_layout_valid_index() {
-    let layouts = list_layouts();
-    if !layouts.is_empty() {
-        let layout = get_layout(0);
+fn test_get_layout_listed_id() {
+    let layouts = list_layouts().unwrap();
+    if let Some(first) = layouts.first() {
+        let layout = get_layout(&first.id).unwrap();
         assert!(
             layout.is_some(),
-            "get_layout(0) should return Some for valid index"
+            "get_layout should return Some for a listed id"
         );
 
         let layout = layout.unwrap();
@@ -28,19 +30,17 @@ fn test_get_layout_valid_index() {
 }
 
 #[test]
-fn test_get_layout_invalid_index() {
-    let layouts = list_layouts();
-    let invalid_index = layouts.len() + 100;
-    let layout = get_layout(invalid_index);
+fn test_get_layout_unknown_id() {
+    let layout = get_layout(&LayoutId::new("no-such-layout")).unwrap();
     assert!(
         layout.is_none(),
-        "get_layout with invalid index should return None"
+        "get_layout with an unknown id should return None"
     );
 }
 
 #[test]
 fn test_vk_to_char_map_default_structure() {
-    let layout_map = vk_to_char_map_default();
+    let layout_map = vk_to_char_map_default().unwrap();
 
     assert!(
         !layout_map.layout.lang_name.is_empty(),
@@ -54,25 +54,26 @@ fn test_vk_to_char_map_default_structure() {
 
 #[test]
 fn test_vk_to_char_map_default_contains_common_keys() {
-    let layout_map = vk_to_char_map_default();
+    let layout_map = vk_to_char_map_default().unwrap();
 
-    let common_vks = vec![0x41, 0x42, 0x43, 0x20, 0x0D];
+    let common_keys = vec![
+        PhysicalKey::KeyA,
+        PhysicalKey::KeyB,
+        PhysicalKey::KeyC,
+        PhysicalKey::Space,
+        PhysicalKey::Enter,
+    ];
 
-    for vk in common_vks {
-        if layout_map.map.contains_key(&vk) {
-            let output = &layout_map.map[&vk];
-            assert!(
-                !output.is_empty(),
-                "VK_{:02X} should have non-empty output",
-                vk
-            );
+    for key in common_keys {
+        if let Some(output) = layout_map.get(key, ShiftLevel::Base) {
+            assert!(!output.is_empty(), "{} should have non-empty output", key);
         }
     }
 }
 
 #[test]
 fn test_all_layout_vk_maps_structure() {
-    let layout_maps = all_layout_vk_maps();
+    let layout_maps = all_layout_vk_maps().unwrap();
 
     assert!(
         !layout_maps.is_empty(),
@@ -93,8 +94,8 @@ fn test_all_layout_vk_maps_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_consistency() {
-    let layouts = list_layouts();
-    let layout_maps = all_layout_vk_maps();
+    let layouts = list_layouts().unwrap();
+    let layout_maps = all_layout_vk_maps().unwrap();
 
     assert_eq!(
         layouts.len(),
@@ -102,8 +103,8 @@ fn test_all_layout_vk_maps_consistency() {
         "Number of layouts should match number of layout maps"
     );
 
-    for (i, layout_map) in layout_maps.iter().enumerate() {
-        if let Some(layout) = get_layout(i) {
+    for layout_map in &layout_maps {
+        if let Some(layout) = get_layout(&layout_map.layout.id).unwrap() {
             assert_eq!(
                 layout_map.layout.lang_name, layout.lang_name,
                 "Layout names should match"
@@ -115,3 +116,42 @@ fn test_all_layout_vk_maps_consistency() {
         }
     }
 }
+
+#[test]
+fn test_vk_to_char_map_for_unknown_id() {
+    let id = LayoutId::new("no-such-layout");
+    assert_eq!(
+        vk_to_char_map_for_layout(&id),
+        Err(LayoutError::LayoutNotFound { id })
+    );
+}
+
+#[test]
+fn test_layout_ids_round_trip() {
+    for layout in list_layouts().unwrap() {
+        let restored = LayoutId::new(layout.id.to_string());
+        let map = vk_to_char_map_for_layout(&restored).unwrap();
+        assert_eq!(map.layout.id, layout.id);
+    }
+}
+
+#[test]
+fn test_context_matches_free_functions() {
+    let mut context = KeyboardContext::new();
+    assert_eq!(context.list_layouts().unwrap(), list_layouts().unwrap());
+    assert_eq!(
+        context.all_layout_vk_maps().unwrap(),
+        all_layout_vk_maps().unwrap().as_slice()
+    );
+}
+
+#[test]
+fn test_context_caches_until_invalidated() {
+    let mut context = KeyboardContext::new();
+    let first = context.all_layout_vk_maps().unwrap().as_ptr();
+    let second = context.all_layout_vk_maps().unwrap().as_ptr();
+    assert_eq!(first, second, "maps should be built once");
+
+    context.invalidate();
+    assert!(context.maps.is_none());
+}
@@ -0,0 +1,348 @@
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::types::LayoutId;
+use crate::error::LayoutError;
+
+/// Registry shipped by xkeyboard-config on most Linux distributions.
+pub const DEFAULT_REGISTRY_PATH: &str = "/usr/share/X11/xkb/rules/evdev.xml";
+
+/// An XKB layout name with its optional variant, e.g. `il(phonetic)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XkbLayoutSpec {
+    pub layout: String,
+    pub variant: Option<String>,
+}
+
+impl XkbLayoutSpec {
+    pub fn new(layout: impl Into<String>, variant: Option<String>) -> Self {
+        XkbLayoutSpec {
+            layout: layout.into(),
+            variant: variant.filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// Parses the `layout(variant)` form used by [`LayoutId`] and setxkbmap.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        let (layout, variant) = match spec.split_once('(') {
+            Some((layout, rest)) => (layout, Some(rest.strip_suffix(')')?.to_string())),
+            None => (spec, None),
+        };
+        if layout.is_empty() {
+            return None;
+        }
+        Some(XkbLayoutSpec::new(layout, variant))
+    }
+
+    pub fn id(&self) -> LayoutId {
+        LayoutId::new(self.to_string())
+    }
+}
+
+impl fmt::Display for XkbLayoutSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.variant {
+            Some(variant) => write!(f, "{}({variant})", self.layout),
+            None => f.write_str(&self.layout),
+        }
+    }
+}
+
+/// The `configItem` shared by models, layouts, variants and options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbConfigItem {
+    pub name: String,
+    /// Indicator label, e.g. `en`; usually only set on layouts and variants.
+    pub short_description: Option<String>,
+    pub description: String,
+    pub vendor: Option<String>,
+    /// ISO 639-2/3 codes, e.g. `heb`.
+    pub languages: Vec<String>,
+    /// ISO 3166 codes, e.g. `IL`.
+    pub countries: Vec<String>,
+    /// Marked `popularity="exotic"` in the registry.
+    pub exotic: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbLayoutInfo {
+    pub item: XkbConfigItem,
+    pub variants: Vec<XkbConfigItem>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbOptionGroup {
+    pub item: XkbConfigItem,
+    pub allow_multiple_selection: bool,
+    pub options: Vec<XkbConfigItem>,
+}
+
+/// Registry entry resolved for one [`XkbLayoutSpec`]. Variants inherit the
+/// layout's short description, languages and countries when they have none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XkbLayoutDescription<'a> {
+    pub layout: &'a XkbConfigItem,
+    pub variant: Option<&'a XkbConfigItem>,
+}
+
+impl XkbLayoutDescription<'_> {
+    fn most_specific(&self) -> &XkbConfigItem {
+        self.variant.unwrap_or(self.layout)
+    }
+
+    pub fn description(&self) -> &str {
+        &self.most_specific().description
+    }
+
+    pub fn short_description(&self) -> Option<&str> {
+        self.variant
+            .and_then(|variant| variant.short_description.as_deref())
+            .or(self.layout.short_description.as_deref())
+    }
+
+    pub fn languages(&self) -> &[String] {
+        match self.variant {
+            Some(variant) if !variant.languages.is_empty() => &variant.languages,
+            _ => &self.layout.languages,
+        }
+    }
+
+    pub fn countries(&self) -> &[String] {
+        match self.variant {
+            Some(variant) if !variant.countries.is_empty() => &variant.countries,
+            _ => &self.layout.countries,
+        }
+    }
+}
+
+/// Typed view of an `evdev.xml`-style XKB rules registry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbRegistry {
+    pub models: Vec<XkbConfigItem>,
+    pub layouts: Vec<XkbLayoutInfo>,
+    pub option_groups: Vec<XkbOptionGroup>,
+}
+
+impl XkbRegistry {
+    /// Loads [`DEFAULT_REGISTRY_PATH`].
+    pub fn load() -> Result<Self, LayoutError> {
+        Self::from_path(DEFAULT_REGISTRY_PATH)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LayoutError> {
+        let path = path.as_ref();
+        let unreadable = |reason: String| LayoutError::RegistryUnreadable {
+            path: PathBuf::from(path),
+            reason,
+        };
+        let xml = fs::read_to_string(path).map_err(|e| unreadable(e.to_string()))?;
+        Self::from_xml(&xml).map_err(|e| unreadable(e.to_string()))
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, LayoutError> {
+        let raw: RawRegistry = from_str(xml).map_err(|e| LayoutError::InvalidData {
+            format: "XKB registry",
+            reason: e.to_string(),
+        })?;
+        Ok(raw.into())
+    }
+
+    pub fn layout(&self, name: &str) -> Option<&XkbLayoutInfo> {
+        self.layouts.iter().find(|layout| layout.item.name == name)
+    }
+
+    pub fn variant(&self, layout: &str, variant: &str) -> Option<&XkbConfigItem> {
+        self.layout(layout)?
+            .variants
+            .iter()
+            .find(|item| item.name == variant)
+    }
+
+    /// Looks up `spec`. Returns `None` if the layout or the requested variant
+    /// is not in the registry.
+    pub fn describe(&self, spec: &XkbLayoutSpec) -> Option<XkbLayoutDescription<'_>> {
+        let layout = self.layout(&spec.layout)?;
+        let variant = match &spec.variant {
+            Some(name) => Some(layout.variants.iter().find(|item| &item.name == name)?),
+            None => None,
+        };
+        Some(XkbLayoutDescription {
+            layout: &layout.item,
+            variant,
+        })
+    }
+
+    /// Every layout and variant whose language list contains `iso639`.
+    pub fn layouts_for_language(&self, iso639: &str) -> Vec<XkbLayoutSpec> {
+        let mut specs = Vec::new();
+        for layout in &self.layouts {
+            let layout_matches = layout.item.languages.iter().any(|lang| lang == iso639);
+            if layout_matches {
+                specs.push(XkbLayoutSpec::new(&layout.item.name, None));
+            }
+            for variant in &layout.variants {
+                let matches = if variant.languages.is_empty() {
+                    layout_matches
+                } else {
+                    variant.languages.iter().any(|lang| lang == iso639)
+                };
+                if matches {
+                    specs.push(XkbLayoutSpec::new(
+                        &layout.item.name,
+                        Some(variant.name.clone()),
+                    ));
+                }
+            }
+        }
+        specs
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRegistry {
+    #[serde(rename = "modelList", default)]
+    model_list: RawModelList,
+    #[serde(rename = "layoutList", default)]
+    layout_list: RawLayoutList,
+    #[serde(rename = "optionList", default)]
+    option_list: RawOptionList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawModelList {
+    #[serde(rename = "model", default)]
+    models: Vec<RawWrapped>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawLayoutList {
+    #[serde(rename = "layout", default)]
+    layouts: Vec<RawLayout>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawOptionList {
+    #[serde(rename = "group", default)]
+    groups: Vec<RawGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawWrapped {
+    #[serde(rename = "configItem")]
+    config_item: RawConfigItem,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLayout {
+    #[serde(rename = "configItem")]
+    config_item: RawConfigItem,
+    #[serde(rename = "variantList", default)]
+    variant_list: RawVariantList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawVariantList {
+    #[serde(rename = "variant", default)]
+    variants: Vec<RawWrapped>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawGroup {
+    #[serde(rename = "@allowMultipleSelection", default)]
+    allow_multiple_selection: bool,
+    #[serde(rename = "configItem")]
+    config_item: RawConfigItem,
+    #[serde(rename = "option", default)]
+    options: Vec<RawWrapped>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawConfigItem {
+    #[serde(rename = "@popularity", default)]
+    popularity: Option<String>,
+    name: String,
+    #[serde(rename = "shortDescription", default)]
+    short_description: Option<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    vendor: Option<String>,
+    #[serde(rename = "languageList", default)]
+    language_list: RawLanguageList,
+    #[serde(rename = "countryList", default)]
+    country_list: RawCountryList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawLanguageList {
+    #[serde(rename = "iso639Id", default)]
+    ids: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawCountryList {
+    #[serde(rename = "iso3166Id", default)]
+    ids: Vec<String>,
+}
+
+impl From<RawConfigItem> for XkbConfigItem {
+    fn from(raw: RawConfigItem) -> Self {
+        XkbConfigItem {
+            name: raw.name,
+            short_description: raw.short_description,
+            description: raw.description,
+            vendor: raw.vendor,
+            languages: raw.language_list.ids,
+            countries: raw.country_list.ids,
+            exotic: raw.popularity.as_deref() == Some("exotic"),
+        }
+    }
+}
+
+impl From<RawRegistry> for XkbRegistry {
+    fn from(raw: RawRegistry) -> Self {
+        XkbRegistry {
+            models: raw
+                .model_list
+                .models
+                .into_iter()
+                .map(|model| model.config_item.into())
+                .collect(),
+            layouts: raw
+                .layout_list
+                .layouts
+                .into_iter()
+                .map(|layout| XkbLayoutInfo {
+                    item: layout.config_item.into(),
+                    variants: layout
+                        .variant_list
+                        .variants
+                        .into_iter()
+                        .map(|variant| variant.config_item.into())
+                        .collect(),
+                })
+                .collect(),
+            option_groups: raw
+                .option_list
+                .groups
+                .into_iter()
+                .map(|group| XkbOptionGroup {
+                    item: group.config_item.into(),
+                    allow_multiple_selection: group.allow_multiple_selection,
+                    options: group
+                        .options
+                        .into_iter()
+                        .map(|option| option.config_item.into())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,173 @@
+use super::*;
+
+const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xkbConfigRegistry SYSTEM "xkb.dtd">
+<xkbConfigRegistry version="1.1">
+  <modelList>
+    <model>
+      <configItem>
+        <name>pc105</name>
+        <description>Generic 105-key PC</description>
+        <vendor>Generic</vendor>
+      </configItem>
+    </model>
+  </modelList>
+  <layoutList>
+    <layout>
+      <configItem>
+        <name>il</name>
+        <shortDescription>he</shortDescription>
+        <description>Hebrew</description>
+        <countryList>
+          <iso3166Id>IL</iso3166Id>
+        </countryList>
+        <languageList>
+          <iso639Id>heb</iso639Id>
+        </languageList>
+      </configItem>
+      <variantList>
+        <variant>
+          <configItem>
+            <name>phonetic</name>
+            <description>Hebrew (phonetic)</description>
+          </configItem>
+        </variant>
+        <variant>
+          <configItem popularity="exotic">
+            <name>biblical</name>
+            <description>Hebrew (Biblical, Tiro)</description>
+            <languageList>
+              <iso639Id>hbo</iso639Id>
+            </languageList>
+          </configItem>
+        </variant>
+      </variantList>
+    </layout>
+    <layout>
+      <configItem>
+        <name>us</name>
+        <shortDescription>en</shortDescription>
+        <description>English (US)</description>
+        <languageList>
+          <iso639Id>eng</iso639Id>
+        </languageList>
+      </configItem>
+    </layout>
+  </layoutList>
+  <optionList>
+    <group allowMultipleSelection="true">
+      <configItem>
+        <name>grp</name>
+        <description>Switching to another layout</description>
+      </configItem>
+      <option>
+        <configItem>
+          <name>grp:alt_shift_toggle</name>
+          <description>Alt+Shift</description>
+        </configItem>
+      </option>
+    </group>
+  </optionList>
+</xkbConfigRegistry>
+"#;
+
+fn spec(layout: &str, variant: Option<&str>) -> XkbLayoutSpec {
+    XkbLayoutSpec::new(layout, variant.map(str::to_string))
+}
+
+#[test]
+fn test_spec_parse_and_display() {
+    let phonetic = XkbLayoutSpec::parse("il(phonetic)").unwrap();
+    assert_eq!(phonetic, spec("il", Some("phonetic")));
+    assert_eq!(phonetic.to_string(), "il(phonetic)");
+    assert_eq!(phonetic.id(), LayoutId::new("il(phonetic)"));
+
+    assert_eq!(XkbLayoutSpec::parse("us"), Some(spec("us", None)));
+    assert_eq!(XkbLayoutSpec::parse("us()"), Some(spec("us", None)));
+    assert_eq!(XkbLayoutSpec::parse("us(dvorak"), None);
+    assert_eq!(XkbLayoutSpec::parse(""), None);
+}
+
+#[test]
+fn test_from_xml_reads_every_list() {
+    let registry = XkbRegistry::from_xml(SAMPLE).unwrap();
+
+    assert_eq!(registry.models.len(), 1);
+    assert_eq!(registry.models[0].vendor.as_deref(), Some("Generic"));
+
+    let il = registry.layout("il").unwrap();
+    assert_eq!(il.item.short_description.as_deref(), Some("he"));
+    assert_eq!(il.item.countries, ["IL"]);
+    assert_eq!(il.item.languages, ["heb"]);
+    assert_eq!(il.variants.len(), 2);
+    assert!(!il.variants[0].exotic);
+    assert!(il.variants[1].exotic);
+
+    let group = &registry.option_groups[0];
+    assert!(group.allow_multiple_selection);
+    assert_eq!(group.options[0].name, "grp:alt_shift_toggle");
+}
+
+#[test]
+fn test_describe_inherits_from_layout() {
+    let registry = XkbRegistry::from_xml(SAMPLE).unwrap();
+
+    let phonetic = registry.describe(&spec("il", Some("phonetic"))).unwrap();
+    assert_eq!(phonetic.description(), "Hebrew (phonetic)");
+    assert_eq!(phonetic.short_description(), Some("he"));
+    assert_eq!(phonetic.languages(), ["heb"]);
+    assert_eq!(phonetic.countries(), ["IL"]);
+
+    let biblical = registry.describe(&spec("il", Some("biblical"))).unwrap();
+    assert_eq!(biblical.languages(), ["hbo"]);
+
+    let us = registry.describe(&spec("us", None)).unwrap();
+    assert_eq!(us.description(), "English (US)");
+    assert!(us.variant.is_none());
+
+    assert!(registry.describe(&spec("il", Some("missing"))).is_none());
+    assert!(registry.describe(&spec("xx", None)).is_none());
+}
+
+#[test]
+fn test_layouts_for_language() {
+    let registry = XkbRegistry::from_xml(SAMPLE).unwrap();
+    assert_eq!(
+        registry.layouts_for_language("heb"),
+        [spec("il", None), spec("il", Some("phonetic"))]
+    );
+    assert_eq!(
+        registry.layouts_for_language("hbo"),
+        [spec("il", Some("biblical"))]
+    );
+    assert!(registry.layouts_for_language("fra").is_empty());
+}
+
+#[test]
+fn test_invalid_xml_is_reported() {
+    let err = XkbRegistry::from_xml("<xkbConfigRegistry><layoutList><layout>").unwrap_err();
+    assert!(matches!(
+        err,
+        LayoutError::InvalidData {
+            format: "XKB registry",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_from_path_reports_missing_file() {
+    let err = XkbRegistry::from_path("/nonexistent/evdev.xml").unwrap_err();
+    assert!(matches!(err, LayoutError::RegistryUnreadable { .. }));
+}
+
+#[test]
+fn test_load_system_registry_if_present() {
+    if !Path::new(DEFAULT_REGISTRY_PATH).exists() {
+        return;
+    }
+    let registry = XkbRegistry::load().unwrap();
+    let us = registry.describe(&spec("us", Some("dvorak"))).unwrap();
+    assert!(us.description().contains("Dvorak"));
+    assert!(us.languages().iter().any(|lang| lang == "eng"));
+}
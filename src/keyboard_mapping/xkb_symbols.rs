@@ -0,0 +1,138 @@
+//! XKB `symbols` files written from a [`LayoutMap`], on every platform.
+
+use super::types::{DeadKey, KeyAction, KeyStroke, LayoutMap, ShiftLevel};
+
+/// Dead keysyms by the accent they type, spacing or combining.
+const DEAD_KEYSYMS: &[(&str, &str)] = &[
+    ("`", "dead_grave"),
+    ("\u{300}", "dead_grave"),
+    ("´", "dead_acute"),
+    ("'", "dead_acute"),
+    ("\u{301}", "dead_acute"),
+    ("^", "dead_circumflex"),
+    ("\u{302}", "dead_circumflex"),
+    ("~", "dead_tilde"),
+    ("˜", "dead_tilde"),
+    ("\u{303}", "dead_tilde"),
+    ("¯", "dead_macron"),
+    ("\u{304}", "dead_macron"),
+    ("˘", "dead_breve"),
+    ("\u{306}", "dead_breve"),
+    ("˙", "dead_abovedot"),
+    ("\u{307}", "dead_abovedot"),
+    ("¨", "dead_diaeresis"),
+    ("\"", "dead_diaeresis"),
+    ("\u{308}", "dead_diaeresis"),
+    ("°", "dead_abovering"),
+    ("˚", "dead_abovering"),
+    ("\u{30A}", "dead_abovering"),
+    ("˝", "dead_doubleacute"),
+    ("\u{30B}", "dead_doubleacute"),
+    ("ˇ", "dead_caron"),
+    ("\u{30C}", "dead_caron"),
+    ("¸", "dead_cedilla"),
+    ("\u{327}", "dead_cedilla"),
+    ("˛", "dead_ogonek"),
+    ("\u{328}", "dead_ogonek"),
+];
+
+impl LayoutMap {
+    /// XKB `symbols` file of this layout, with a default `basic` section.
+    ///
+    /// Saved as `~/.config/xkb/symbols/<name>` it can be selected as layout
+    /// `<name>`, or read back with `LayoutMap::from_xkb_symbols_file`. Keys
+    /// get their Base and Shift outputs, and AltGr and Shift+AltGr as levels
+    /// 3 and 4 through `level3(ralt_switch)` when the layout has any. XKB
+    /// derives Caps Lock from the key type, so the Caps level is not
+    /// written, and outputs of several characters have no keysym and are
+    /// left out. Dead keys become the `dead_*` keysym of their accent and
+    /// combine through the system's Compose table; a dead key whose accent
+    /// has no such keysym types the accent instead.
+    pub fn to_xkb_symbols(&self) -> String {
+        let name = [&self.layout.lang_name, &self.layout.description]
+            .into_iter()
+            .find(|name| !name.is_empty())
+            .map_or(self.layout.id.as_str(), String::as_str);
+        let mut keys = String::new();
+        let mut level3 = false;
+        for key in self.keys() {
+            let has_level3 = [ShiftLevel::AltGr, ShiftLevel::ShiftAltGr]
+                .into_iter()
+                .map(|level| KeyStroke::new(key, level))
+                .any(|stroke| {
+                    self.map.contains_key(&stroke) || self.dead_keys.contains_key(&stroke)
+                });
+            let levels: &[ShiftLevel] = if has_level3 {
+                &ShiftLevel::ALL[..4]
+            } else {
+                &ShiftLevel::ALL[..2]
+            };
+            let mut symbols: Vec<String> = levels
+                .iter()
+                .map(|&level| self.keysym(KeyStroke::new(key, level)))
+                .map(|keysym| keysym.unwrap_or_else(|| "NoSymbol".to_string()))
+                .collect();
+            while symbols.last().is_some_and(|keysym| keysym == "NoSymbol") {
+                symbols.pop();
+            }
+            if symbols.is_empty() {
+                continue;
+            }
+            level3 |= symbols.len() > 2;
+            keys.push_str(&format!(
+                "    key <{}> {{ [ {} ] }};\n",
+                key.xkb_name(),
+                symbols.join(", ")
+            ));
+        }
+        let mut symbols = format!(
+            "// Keyboard layout `{}`.\ndefault partial alphanumeric_keys\nxkb_symbols \"basic\" {{\n    name[Group1] = \"{}\";\n\n",
+            self.layout.id,
+            escape(name)
+        );
+        symbols.push_str(&keys);
+        if level3 {
+            symbols.push_str("\n    include \"level3(ralt_switch)\"\n");
+        }
+        symbols.push_str("};\n");
+        symbols
+    }
+
+    /// Keysym name of what `stroke` does, following the level fallbacks.
+    fn keysym(&self, stroke: KeyStroke) -> Option<String> {
+        match self.action(stroke)? {
+            KeyAction::Text(text) => char_keysym(text),
+            KeyAction::Dead(dead_key) => dead_keysym(dead_key),
+        }
+    }
+}
+
+/// Keysym of a single character: letters and digits by name, anything else
+/// as a Unicode keysym such as `U05E9`.
+fn char_keysym(text: &str) -> Option<String> {
+    let mut chars = text.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    Some(match c {
+        ' ' => "space".to_string(),
+        c if c.is_ascii_alphanumeric() => c.to_string(),
+        c => format!("U{:04X}", u32::from(c)),
+    })
+}
+
+fn dead_keysym(dead_key: &DeadKey) -> Option<String> {
+    DEAD_KEYSYMS
+        .iter()
+        .find(|(accent, _)| *accent == dead_key.accent)
+        .map(|(_, keysym)| keysym.to_string())
+        .or_else(|| char_keysym(&dead_key.accent))
+}
+
+/// `name` as an XKB string, with quotes and backslashes as octal escapes.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\134").replace('"', "\\042")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,99 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::LayoutId;
+use std::collections::BTreeMap;
+
+/// A German-like layout with AltGr outputs and a dead circumflex.
+fn sample() -> LayoutMap {
+    let mut map = LayoutMap::default();
+    map.layout.id = LayoutId::new("custom");
+    map.layout.lang_name = "Custom \"German\"".to_string();
+    let outputs = [
+        (PhysicalKey::KeyQ, ShiftLevel::Base, "q"),
+        (PhysicalKey::KeyQ, ShiftLevel::Shift, "Q"),
+        (PhysicalKey::KeyQ, ShiftLevel::AltGr, "@"),
+        (PhysicalKey::KeyE, ShiftLevel::Base, "e"),
+        (PhysicalKey::KeyE, ShiftLevel::Shift, "E"),
+        (PhysicalKey::KeyE, ShiftLevel::AltGr, "€"),
+        (PhysicalKey::KeyY, ShiftLevel::Base, "z"),
+        (PhysicalKey::KeyY, ShiftLevel::Shift, "Z"),
+        (PhysicalKey::Digit1, ShiftLevel::Base, "1"),
+        (PhysicalKey::Digit1, ShiftLevel::Shift, "!"),
+        (PhysicalKey::Quote, ShiftLevel::Base, "ä"),
+        (PhysicalKey::Quote, ShiftLevel::Shift, "Ä"),
+        (PhysicalKey::Minus, ShiftLevel::Base, "ß"),
+        (PhysicalKey::Space, ShiftLevel::Base, " "),
+        (PhysicalKey::Slash, ShiftLevel::Base, "ae"),
+    ];
+    for (key, level, output) in outputs {
+        map.insert_level(KeyStroke::new(key, level), output.to_string());
+    }
+    map.insert_dead_key(
+        KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base),
+        DeadKey {
+            accent: "^".to_string(),
+            combinations: BTreeMap::from([('a', "â".to_string())]),
+        },
+    );
+    map
+}
+
+#[test]
+fn test_to_xkb_symbols() {
+    let symbols = sample().to_xkb_symbols();
+    assert!(symbols.contains("default partial alphanumeric_keys\nxkb_symbols \"basic\" {\n"));
+    assert!(symbols.contains("    name[Group1] = \"Custom \\042German\\042\";\n"));
+    assert!(symbols.contains("    key <AD01> { [ q, Q, U0040, Q ] };\n"));
+    assert!(symbols.contains("    key <AD06> { [ z, Z ] };\n"));
+    assert!(symbols.contains("    key <AE01> { [ 1, U0021 ] };\n"));
+    assert!(symbols.contains("    key <AC11> { [ U00E4, U00C4 ] };\n"));
+    assert!(symbols.contains("    key <AE11> { [ U00DF ] };\n"));
+    assert!(symbols.contains("    key <SPCE> { [ space ] };\n"));
+    assert!(symbols.contains("    key <TLDE> { [ dead_circumflex ] };\n"));
+    assert!(symbols.contains("    include \"level3(ralt_switch)\"\n"));
+    assert!(symbols.ends_with("};\n"));
+    // Several characters have no keysym.
+    assert!(!symbols.contains("<AB10>"));
+}
+
+#[test]
+fn test_dead_keysym() {
+    let dead = |accent: &str| {
+        dead_keysym(&DeadKey {
+            accent: accent.to_string(),
+            combinations: BTreeMap::new(),
+        })
+    };
+    assert_eq!(dead("´").as_deref(), Some("dead_acute"));
+    assert_eq!(dead("\u{308}").as_deref(), Some("dead_diaeresis"));
+    assert_eq!(dead("ʻ").as_deref(), Some("U02BB"));
+    assert_eq!(dead(""), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_to_xkb_symbols_round_trip() {
+    use std::fs;
+
+    let map = sample();
+    let root = crate::keyboard_mapping::temp_path("xkb-export");
+    let path = root.join("symbols").join("custom");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, map.to_xkb_symbols()).unwrap();
+    let read = LayoutMap::from_xkb_symbols_file(&path, None);
+    fs::remove_dir_all(&root).unwrap();
+
+    let read = read.unwrap();
+    assert_eq!(read.layout.lang_name, map.layout.lang_name);
+    for (stroke, output) in &map.map {
+        if output.chars().count() == 1 {
+            assert_eq!(read.resolve(*stroke), Some(output.as_str()), "{stroke:?}");
+        }
+    }
+    for level in &ShiftLevel::ALL[..4] {
+        let stroke = KeyStroke::new(PhysicalKey::KeyY, *level);
+        assert_eq!(read.resolve(stroke), map.resolve(stroke), "{stroke:?}");
+    }
+    let stroke = KeyStroke::new(PhysicalKey::Backquote, ShiftLevel::Base);
+    assert!(read.dead_keys.contains_key(&stroke));
+}
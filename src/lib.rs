@@ -1,9 +1,24 @@
+pub mod error;
 pub mod get_highlighted;
 pub mod keyboard_mapping;
 
+pub use error::LayoutError;
 pub use get_highlighted::{get_highlighted_text, replace_highlighted_text};
+#[allow(deprecated)]
+pub use keyboard_mapping::get_text_leyaout_map;
+#[cfg(target_os = "linux")]
+pub use keyboard_mapping::layout_maps_from_xkb_keymap;
 pub use keyboard_mapping::{
-    KeyboardDirection, KeyboardLayout, LayoutMap, all_layout_vk_maps, get_layout,
-    get_text_leyaout_map, list_layouts, shift_text_language, vk_to_char_map_default,
-    vk_to_char_map_for_layout,
+    Correction, DeadKey, KeyAction, KeyBlock, KeyRanking, KeyStroke, KeyboardContext,
+    KeyboardDirection, KeyboardLayout, KeyboardProfile, LanguageModel, LanguageModels,
+    LayoutCandidate, LayoutEvent, LayoutGuess, LayoutId, LayoutMap, LayoutWatcher, PhysicalKey,
+    ShiftLevel, TextSegment, XkbLayoutSpec, XkbRegistry, activate_layout, all_layout_vk_maps,
+    correct_mixed_text_with, current_layout, detect_mistyped_with, get_layout, get_text_layout_map,
+    infer_layout, list_layouts, load_cldr_keyboards, mistyped_segments_with, rank_layouts,
+    shift_text_language, shift_text_language_with, vk_to_char_map_default,
+    vk_to_char_map_for_layout, watch_layout_changes,
 };
+#[cfg(feature = "language-models")]
+pub use keyboard_mapping::{correct_mixed_text, detect_mistyped};
+#[cfg(feature = "embedded-layouts")]
+pub use keyboard_mapping::{embedded_layout, embedded_layouts, embedded_layouts_for_language};